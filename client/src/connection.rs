@@ -0,0 +1,121 @@
+// connection.rs
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+
+/// Réponse RESP2 renvoyée par le serveur
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    Array(Vec<Value>),
+}
+
+/// Connexion RESP au serveur Redust
+pub struct Connection {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Connection> {
+        let stream = TcpStream::connect(addr)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Connection { stream, reader })
+    }
+
+    /// Envoie une commande et attend sa réponse
+    pub fn command(&mut self, args: &[&[u8]]) -> io::Result<Value> {
+        let mut buf = Vec::new();
+        encode_command(&mut buf, args);
+        self.stream.write_all(&buf)?;
+        read_value(&mut self.reader)
+    }
+
+    /// Envoie toutes les commandes du pipeline d'un coup, puis lit les réponses dans l'ordre
+    pub fn execute(&mut self, pipeline: &Pipeline) -> io::Result<Vec<Value>> {
+        let mut writer = self.stream.try_clone()?;
+        let reader = &mut self.reader;
+        // L'écriture se fait dans un thread séparé : le serveur peut commencer à répondre
+        // avant d'avoir tout reçu sans que les deux côtés se bloquent mutuellement.
+        thread::scope(|scope| {
+            let sender = scope.spawn(move || writer.write_all(&pipeline.buffer));
+            let replies = (0..pipeline.len).map(|_| read_value(reader)).collect();
+            sender.join().unwrap()?;
+            replies
+        })
+    }
+}
+
+/// Suite de commandes envoyées sans attendre les réponses intermédiaires
+#[derive(Default)]
+pub struct Pipeline {
+    buffer: Vec<u8>,
+    len: usize,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    pub fn cmd(&mut self, args: &[&[u8]]) -> &mut Pipeline {
+        encode_command(&mut self.buffer, args);
+        self.len += 1;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Encode une commande sous forme de tableau de chaînes bulk
+fn encode_command(buf: &mut Vec<u8>, args: &[&[u8]]) {
+    buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+fn read_value<R: BufRead>(reader: &mut R) -> io::Result<Value> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connexion fermée"));
+    }
+    let line = line.trim_end_matches(['\r', '\n']);
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("réponse invalide: {}", line));
+    let (prefix, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+    match prefix {
+        "+" => Ok(Value::Simple(rest.to_string())),
+        "-" => Ok(Value::Error(rest.to_string())),
+        ":" => rest.parse().map(Value::Integer).map_err(|_| invalid()),
+        "$" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok(Value::Nil);
+            }
+            let mut data = vec![0u8; len as usize + 2];
+            reader.read_exact(&mut data)?;
+            data.truncate(len as usize);
+            Ok(Value::Bulk(data))
+        },
+        "*" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok(Value::Nil);
+            }
+            (0..len).map(|_| read_value(reader)).collect::<io::Result<_>>().map(Value::Array)
+        },
+        _ => Err(invalid()),
+    }
+}
@@ -1 +1,2 @@
 pub mod automated_test;
+pub mod connection;
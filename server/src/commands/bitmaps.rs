@@ -0,0 +1,388 @@
+// src/commands/bitmaps.rs
+//! Commandes de bits sur les chaînes. Le bit 0 est le bit de poids fort du premier octet, comme dans Redis.
+
+use super::strings::get_string;
+use super::{log_command, wrong_arity};
+use crate::db::{get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, parse_arg, Reply};
+use std::sync::mpsc::Sender;
+
+/// Une chaîne fait au plus 512 Mo : les positions de bit restent inférieures à 2^32
+const MAX_BITS: u64 = 512 * 1024 * 1024 * 8;
+
+fn parse_offset(arg: &[u8]) -> Result<u64, CommandError> {
+    parse_arg::<u64>(arg).filter(|offset| *offset < MAX_BITS).ok_or(CommandError::BitOffset)
+}
+
+fn get_bit(bytes: &[u8], offset: u64) -> u8 {
+    bytes.get((offset / 8) as usize).map_or(0, |byte| (byte >> (7 - offset % 8)) & 1)
+}
+
+/// Écrit un bit ; la chaîne doit déjà être assez longue
+fn put_bit(bytes: &mut [u8], offset: u64, bit: u8) {
+    let mask = 1 << (7 - offset % 8);
+    let byte = &mut bytes[(offset / 8) as usize];
+    *byte = if bit == 1 { *byte | mask } else { *byte & !mask };
+}
+
+/// Complète la chaîne par des octets nuls pour qu'elle contienne le bit `last_bit`
+fn grow(bytes: &mut Vec<u8>, last_bit: u64) {
+    let len = (last_bit / 8 + 1) as usize;
+    if bytes.len() < len {
+        bytes.resize(len, 0);
+    }
+}
+
+/// Chaîne de `key` pour une écriture, créée vide si la clé est absente
+fn string_for_write<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<&'a mut Vec<u8>, CommandError> {
+    match &mut get_or_insert(db, key, || Value::String(Vec::new())).value {
+        Value::String(bytes) => Ok(bytes),
+        _ => Err(CommandError::WrongType),
+    }
+}
+
+/// SETBIT key offset value
+pub(super) fn setbit(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let offset = parse_offset(&parts[2])?;
+    let bit = match parts[3].as_slice() {
+        b"0" => 0,
+        b"1" => 1,
+        _ => return Err(CommandError::BitValue),
+    };
+    let bytes = string_for_write(db, &parts[1])?;
+    grow(bytes, offset);
+    let previous = get_bit(bytes, offset);
+    put_bit(bytes, offset, bit);
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(previous as i64))
+}
+
+/// GETBIT key offset
+pub(super) fn getbit(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let offset = parse_offset(&parts[2])?;
+    let bytes = get_string(db, &parts[1])?.map_or(&[][..], |bytes| bytes.as_slice());
+    Ok(Reply::Integer(get_bit(bytes, offset) as i64))
+}
+
+/// Intervalle `start end [BYTE | BIT]` de BITCOUNT / BITPOS, converti en positions de bit inclusives.
+///
+/// Les bornes négatives partent de la fin ; `None` si l'intervalle est vide.
+fn bit_range(bytes: &[u8], start: i64, end: i64, unit: Option<&Vec<u8>>) -> Result<Option<(u64, u64)>, CommandError> {
+    let in_bits = match unit.map(|unit| unit.to_ascii_uppercase()) {
+        None => false,
+        Some(unit) if unit == b"BYTE" => false,
+        Some(unit) if unit == b"BIT" => true,
+        Some(_) => return Err(CommandError::Syntax),
+    };
+    let len = if in_bits { bytes.len() as i64 * 8 } else { bytes.len() as i64 };
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { (len + end).max(0) } else { end.min(len - 1) };
+    if len == 0 || start > end {
+        return Ok(None);
+    }
+    let (start, end) = (start as u64, end as u64);
+    Ok(Some(if in_bits { (start, end) } else { (start * 8, end * 8 + 7) }))
+}
+
+fn parse_bound(arg: &[u8]) -> Result<i64, CommandError> {
+    parse_arg::<i64>(arg).ok_or(CommandError::NotInteger)
+}
+
+/// BITCOUNT key [start end [BYTE | BIT]]
+pub(super) fn bitcount(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    if parts.len() == 3 || parts.len() > 5 {
+        return Err(CommandError::Syntax);
+    }
+    let (start, end) = match parts.len() {
+        2 => (0, -1),
+        _ => (parse_bound(&parts[2])?, parse_bound(&parts[3])?),
+    };
+    let bytes = get_string(db, &parts[1])?.map_or(&[][..], |bytes| bytes.as_slice());
+    let Some((first, last)) = bit_range(bytes, start, end, parts.get(4))? else {
+        return Ok(Reply::Integer(0));
+    };
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    let count: u32 = bytes[first_byte..=last_byte]
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| {
+            let mut mask = 0xFFu8;
+            if i == 0 {
+                mask &= 0xFF >> (first % 8);
+            }
+            if first_byte + i == last_byte {
+                mask &= 0xFF << (7 - last % 8);
+            }
+            (byte & mask).count_ones()
+        })
+        .sum();
+    Ok(Reply::Integer(count as i64))
+}
+
+/// BITPOS key bit [start [end [BYTE | BIT]]]
+///
+/// En cherchant un 0 sans borne de fin, une chaîne entièrement à 1 renvoie la position qui suit sa fin
+/// (les bits au-delà de la chaîne valent 0).
+pub(super) fn bitpos(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    if parts.len() > 6 {
+        return Err(CommandError::Syntax);
+    }
+    let bit = match parts[2].as_slice() {
+        b"0" => 0,
+        b"1" => 1,
+        _ => return Err(CommandError::BitposBit),
+    };
+    let start = parts.get(3).map_or(Ok(0), |arg| parse_bound(arg))?;
+    let end = parts.get(4).map_or(Ok(-1), |arg| parse_bound(arg))?;
+    let bytes = match get_string(db, &parts[1])? {
+        Some(bytes) if !bytes.is_empty() => bytes.as_slice(),
+        _ => return Ok(Reply::Integer(if bit == 1 { -1 } else { 0 })),
+    };
+    let Some((first, last)) = bit_range(bytes, start, end, parts.get(5))? else {
+        return Ok(Reply::Integer(-1));
+    };
+
+    // Les octets qui ne contiennent pas le bit cherché sont sautés d'un coup
+    let skipped = if bit == 1 { 0x00 } else { 0xFF };
+    let mut offset = first;
+    while offset <= last {
+        let byte = bytes[(offset / 8) as usize];
+        if offset % 8 == 0 && offset + 7 <= last && byte == skipped {
+            offset += 8;
+            continue;
+        }
+        if (byte >> (7 - offset % 8)) & 1 == bit {
+            return Ok(Reply::Integer(offset as i64));
+        }
+        offset += 1;
+    }
+    Ok(Reply::Integer(if bit == 0 && parts.len() <= 4 { last as i64 + 1 } else { -1 }))
+}
+
+/// BITOP <AND | OR | XOR | NOT> destkey key [key ...]
+///
+/// Les sources plus courtes (ou absentes) sont complétées par des octets nuls. Un résultat vide supprime `destkey`.
+pub(super) fn bitop(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let op = parts[1].to_ascii_uppercase();
+    let combine: fn(u8, u8) -> u8 = match op.as_slice() {
+        b"AND" => |a, b| a & b,
+        b"OR" => |a, b| a | b,
+        b"XOR" => |a, b| a ^ b,
+        b"NOT" if parts.len() == 4 => |a, _| !a,
+        b"NOT" => return Err(CommandError::BitopNotSingleSource),
+        _ => return Err(CommandError::Syntax),
+    };
+    let mut sources = Vec::with_capacity(parts.len() - 3);
+    for key in &parts[3..] {
+        sources.push(get_string(db, key)?.cloned().unwrap_or_default());
+    }
+    let len = sources.iter().map(Vec::len).max().unwrap_or(0);
+    let result: Vec<u8> = (0..len)
+        .map(|i| {
+            let mut bytes = sources.iter().map(|source| source.get(i).copied().unwrap_or(0));
+            let first = bytes.next().unwrap_or(0);
+            match op.as_slice() {
+                b"NOT" => combine(first, 0),
+                _ => bytes.fold(first, combine),
+            }
+        })
+        .collect();
+
+    let destination = parts[2].clone();
+    if result.is_empty() {
+        db.remove(&destination);
+    } else {
+        db.insert(destination, Entry::new(Value::String(result)));
+    }
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(len as i64))
+}
+
+/// Type d'un champ BITFIELD : `i1` à `i64` ou `u1` à `u63`
+#[derive(Clone, Copy)]
+struct FieldType {
+    signed: bool,
+    bits: u64,
+}
+
+impl FieldType {
+    fn parse(arg: &[u8]) -> Result<FieldType, CommandError> {
+        let (signed, max_bits) = match arg.first().map(u8::to_ascii_lowercase) {
+            Some(b'i') => (true, 64),
+            Some(b'u') => (false, 63),
+            _ => return Err(CommandError::BitfieldType),
+        };
+        let bits = parse_arg::<u64>(&arg[1..]).filter(|bits| (1..=max_bits).contains(bits));
+        Ok(FieldType { signed, bits: bits.ok_or(CommandError::BitfieldType)? })
+    }
+
+    fn min(self) -> i128 {
+        if self.signed { -(1 << (self.bits - 1)) } else { 0 }
+    }
+
+    fn max(self) -> i128 {
+        if self.signed { (1 << (self.bits - 1)) - 1 } else { (1 << self.bits) - 1 }
+    }
+
+    /// Ramène `value` dans l'intervalle du type selon le mode de débordement (`None` pour FAIL)
+    fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                let modulus = 1i128 << self.bits;
+                let wrapped = value.rem_euclid(modulus);
+                Some(if wrapped > self.max() { wrapped - modulus } else { wrapped } as i64)
+            },
+            Overflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            Overflow::Fail => None,
+        }
+    }
+
+    fn read(self, bytes: &[u8], offset: u64) -> i64 {
+        let raw = (0..self.bits).fold(0u64, |raw, i| (raw << 1) | get_bit(bytes, offset + i) as u64);
+        let sign = self.bits < 64 && self.signed && raw >> (self.bits - 1) == 1;
+        if sign { (raw | (u64::MAX << self.bits)) as i64 } else { raw as i64 }
+    }
+
+    /// Écrit `value` (déjà dans l'intervalle du type) ; la chaîne doit être assez longue
+    fn write(self, bytes: &mut [u8], offset: u64, value: i64) {
+        for i in 0..self.bits {
+            put_bit(bytes, offset + i, ((value as u64) >> (self.bits - 1 - i)) as u8 & 1);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+enum FieldOp {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+/// BITFIELD key [GET type offset] [SET type offset value] [INCRBY type offset increment]
+/// [OVERFLOW WRAP | SAT | FAIL] ... | BITFIELD_RO key [GET type offset ...]
+///
+/// Un décalage `#n` désigne le n-ième champ du type (`n * largeur`). OVERFLOW s'applique aux
+/// sous-commandes qui le suivent ; en mode FAIL, une écriture qui déborde est ignorée et renvoie nil.
+/// Les écritures sont journalisées sous forme de `SETRANGE` des octets touchés, pour qu'un INCRBY
+/// rejoué ne s'ajoute pas une seconde fois.
+pub(super) fn bitfield(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let read_only = parts[0].eq_ignore_ascii_case(b"BITFIELD_RO");
+
+    // Toutes les sous-commandes sont vérifiées avant d'en exécuter une seule
+    let mut ops = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut i = 2;
+    while i < parts.len() {
+        let subcommand = parts[i].to_ascii_uppercase();
+        if subcommand == b"OVERFLOW" {
+            overflow = match parts.get(i + 1).map(|arg| arg.to_ascii_uppercase()).as_deref() {
+                Some(b"WRAP") => Overflow::Wrap,
+                Some(b"SAT") => Overflow::Sat,
+                Some(b"FAIL") => Overflow::Fail,
+                _ => return Err(CommandError::Syntax),
+            };
+            i += 2;
+            continue;
+        }
+        let arity = match subcommand.as_slice() {
+            b"GET" => 3,
+            b"SET" | b"INCRBY" => 4,
+            _ => return Err(CommandError::Syntax),
+        };
+        if i + arity > parts.len() {
+            return Err(CommandError::Syntax);
+        }
+        if read_only && arity != 3 {
+            return Err(CommandError::BitfieldReadOnly);
+        }
+        let field = FieldType::parse(&parts[i + 1])?;
+        let offset = match parts[i + 2].strip_prefix(b"#") {
+            Some(index) => parse_arg::<u64>(index).and_then(|index| index.checked_mul(field.bits)),
+            None => parse_arg::<u64>(&parts[i + 2]),
+        };
+        let offset = offset
+            .filter(|offset| offset.checked_add(field.bits).is_some_and(|end| end <= MAX_BITS))
+            .ok_or(CommandError::BitOffset)?;
+        let op = match subcommand.as_slice() {
+            b"GET" => FieldOp::Get,
+            _ => {
+                let value = parse_arg::<i64>(&parts[i + 3]).ok_or(CommandError::NotInteger)?;
+                if subcommand == b"SET" { FieldOp::Set(value) } else { FieldOp::IncrBy(value) }
+            },
+        };
+        ops.push((op, field, offset, overflow));
+        i += arity;
+    }
+
+    // La chaîne n'est créée ou agrandie qu'au moment d'une écriture : si toutes échouent (OVERFLOW FAIL),
+    // la clé reste telle quelle et rien n'est journalisé
+    let key = &parts[1];
+    let mut created = Vec::new();
+    let existing = get_string(db, key)?;
+    let exists = existing.is_some();
+    let bytes = existing.unwrap_or(&mut created);
+    let mut touched: Option<(u64, u64)> = None;
+    let replies: Vec<Reply> = ops
+        .into_iter()
+        .map(|(op, field, offset, overflow)| {
+            let current = field.read(bytes, offset);
+            let (written, reply) = match op {
+                FieldOp::Get => return Reply::Integer(current),
+                FieldOp::Set(value) => (field.fit(value as i128, overflow), current),
+                FieldOp::IncrBy(increment) => {
+                    let result = field.fit(current as i128 + increment as i128, overflow);
+                    (result, result.unwrap_or_default())
+                },
+            };
+            match written {
+                Some(value) => {
+                    let last = offset + field.bits - 1;
+                    grow(bytes, last);
+                    field.write(bytes, offset, value);
+                    touched = Some(touched.map_or((offset, last), |(first, end)| (first.min(offset), end.max(last))));
+                    Reply::Integer(reply)
+                },
+                None => Reply::Nil,
+            }
+        })
+        .collect();
+    if let Some((first_bit, last_bit)) = touched {
+        let (first_byte, last_byte) = ((first_bit / 8) as usize, (last_bit / 8) as usize);
+        let first_byte_arg = first_byte.to_string();
+        aof_tx
+            .send(format_command(&[b"SETRANGE", key, first_byte_arg.as_bytes(), &bytes[first_byte..=last_byte]]))
+            .unwrap();
+        if !exists {
+            db.insert(key.clone(), Entry::new(Value::String(created)));
+        }
+    }
+    Ok(Reply::Array(replies))
+}
@@ -0,0 +1,152 @@
+// src/commands/expire.rs
+//! Expiration des clés. Les dates sont en millisecondes depuis l'époque Unix ; l'AOF les enregistre
+//! toujours sous forme absolue (`PEXPIREAT`), si bien que le rejeu ne dépend pas de l'heure.
+
+use super::{log_command, wrong_arity};
+use crate::db::{get_live, peek_live, Keyspace};
+use crate::error::CommandError;
+use crate::protocol::{format_command, parse_arg, Reply};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
+
+/// Date en millisecondes depuis l'époque Unix
+pub(super) fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+fn from_unix_millis(millis: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+/// Condition posée sur l'expiration actuelle
+#[derive(Clone, Copy, PartialEq)]
+enum Condition {
+    Always,
+    /// NX : seulement si la clé n'a pas d'expiration
+    Nx,
+    /// XX : seulement si la clé a déjà une expiration
+    Xx,
+    /// GT : seulement si la nouvelle date est plus lointaine (une clé sans expiration n'expire jamais)
+    Gt,
+    /// LT : seulement si la nouvelle date est plus proche
+    Lt,
+}
+
+impl Condition {
+    fn parse(options: &[Vec<u8>]) -> Result<Condition, CommandError> {
+        let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+        for option in options {
+            match option.to_ascii_uppercase().as_slice() {
+                b"NX" => nx = true,
+                b"XX" => xx = true,
+                b"GT" => gt = true,
+                b"LT" => lt = true,
+                _ => return Err(CommandError::UnsupportedOption(String::from_utf8_lossy(option).into_owned())),
+            }
+        }
+        if nx && (xx || gt || lt) {
+            return Err(CommandError::IncompatibleOptions("NX and XX, GT or LT options at the same time are not compatible"));
+        }
+        if gt && lt {
+            return Err(CommandError::IncompatibleOptions("GT and LT options at the same time are not compatible"));
+        }
+        Ok(match (nx, xx, gt, lt) {
+            (true, ..) => Condition::Nx,
+            (_, _, true, _) => Condition::Gt,
+            (_, _, _, true) => Condition::Lt,
+            (_, true, ..) => Condition::Xx,
+            _ => Condition::Always,
+        })
+    }
+
+    /// Vrai si la date `new` peut remplacer l'expiration `current`
+    fn allows(self, current: Option<i64>, new: i64) -> bool {
+        match (self, current) {
+            (Condition::Always, _) => true,
+            (Condition::Nx, current) => current.is_none(),
+            (Condition::Xx, current) => current.is_some(),
+            (Condition::Gt, current) => current.is_some_and(|current| new > current),
+            (Condition::Lt, current) => current.is_none_or(|current| new < current),
+        }
+    }
+}
+
+/// EXPIRE key seconds [NX | XX | GT | LT], ainsi que PEXPIRE, EXPIREAT et PEXPIREAT
+///
+/// Renvoie 1 si l'expiration a été posée, 0 si la clé est absente ou si la condition n'est pas
+/// remplie. Une date déjà passée supprime la clé, ce qui est journalisé sous forme de `DELETE`,
+/// sauf pendant le rejeu de l'AOF : l'expiration est alors posée telle quelle.
+pub(super) fn expire(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let command = String::from_utf8_lossy(&parts[0]).to_lowercase();
+    let amount = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let condition = Condition::parse(&parts[3..])?;
+    let invalid = || CommandError::InvalidExpireTime(command.clone());
+    let millis = match command.as_str() {
+        "expire" | "expireat" => amount.checked_mul(1000).ok_or_else(invalid)?,
+        _ => amount,
+    };
+    let now = unix_millis(SystemTime::now());
+    let when = match command.as_str() {
+        "expire" | "pexpire" => millis.checked_add(now).ok_or_else(invalid)?,
+        _ => millis,
+    };
+
+    let key = &parts[1];
+    let Some(entry) = get_live(db, key) else {
+        return Ok(Reply::Integer(0));
+    };
+    if !condition.allows(entry.expire_at.map(unix_millis), when) {
+        return Ok(Reply::Integer(0));
+    }
+    if when <= now && !db.is_loading() {
+        db.remove(key);
+        aof_tx.send(format_command(&[b"DELETE", key])).unwrap();
+        return Ok(Reply::Integer(1));
+    }
+    db.set_expire(key, Some(from_unix_millis(when)));
+    let when = when.to_string();
+    aof_tx.send(format_command(&[b"PEXPIREAT", key, when.as_bytes()])).unwrap();
+    Ok(Reply::Integer(1))
+}
+
+/// TTL key, PTTL key, EXPIRETIME key et PEXPIRETIME key
+///
+/// Renvoient -2 si la clé est absente et -1 si elle n'expire pas. TTL arrondit à la seconde la plus proche.
+pub(super) fn ttl(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let Some(entry) = peek_live(db, &parts[1]) else {
+        return Ok(Reply::Integer(-2));
+    };
+    let Some(expire_at) = entry.expire_at.map(unix_millis) else {
+        return Ok(Reply::Integer(-1));
+    };
+    let remaining = (expire_at - unix_millis(SystemTime::now())).max(0);
+    Ok(Reply::Integer(match String::from_utf8_lossy(&parts[0]).to_uppercase().as_str() {
+        "TTL" => (remaining + 500) / 1000,
+        "PTTL" => remaining,
+        "EXPIRETIME" => expire_at / 1000,
+        _ => expire_at,
+    }))
+}
+
+/// PERSIST key
+///
+/// Renvoie 1 si une expiration a été retirée, 0 si la clé est absente ou n'expirait pas.
+pub(super) fn persist(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    match get_live(db, &parts[1]) {
+        Some(entry) if entry.expire_at.is_some() => {
+            db.set_expire(&parts[1], None);
+            log_command(aof_tx, parts);
+            Ok(Reply::Integer(1))
+        },
+        _ => Ok(Reply::Integer(0)),
+    }
+}
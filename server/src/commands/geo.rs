@@ -0,0 +1,282 @@
+// src/commands/geo.rs
+//! Index géographiques : des ensembles triés dont le score est le géohash de chaque membre.
+
+use super::zsets::{get_or_create_zset, get_zset, remove_if_empty};
+use super::{log_command, wrong_arity};
+use crate::db::Keyspace;
+use crate::error::CommandError;
+use crate::geo::{self, Shape};
+use crate::protocol::{parse_arg, Reply};
+use crate::sorted_set::ScoreRange;
+use std::sync::mpsc::Sender;
+
+fn parse_coordinate(arg: &[u8]) -> Result<f64, CommandError> {
+    parse_arg::<f64>(arg).filter(|value| value.is_finite()).ok_or(CommandError::NotFloat)
+}
+
+/// Longitude et latitude, refusées hors des limites indexables
+fn parse_position(lon: &[u8], lat: &[u8]) -> Result<(f64, f64), CommandError> {
+    let (lon, lat) = (parse_coordinate(lon)?, parse_coordinate(lat)?);
+    if !geo::is_valid(lon, lat) {
+        return Err(CommandError::InvalidCoordinates(lon, lat));
+    }
+    Ok((lon, lat))
+}
+
+/// Nombre de mètres dans l'unité `m`, `km`, `ft` ou `mi`
+fn parse_unit(arg: &[u8]) -> Result<f64, CommandError> {
+    match arg.to_ascii_lowercase().as_slice() {
+        b"m" => Ok(1.0),
+        b"km" => Ok(1000.0),
+        b"ft" => Ok(0.3048),
+        b"mi" => Ok(1609.34),
+        _ => Err(CommandError::UnsupportedUnit),
+    }
+}
+
+fn parse_length(arg: &[u8]) -> Result<f64, CommandError> {
+    parse_coordinate(arg).and_then(|value| if value < 0.0 { Err(CommandError::OutOfRange) } else { Ok(value) })
+}
+
+/// Distance renvoyée au client, avec quatre décimales comme dans Redis
+fn distance_reply(meters: f64, unit: f64) -> Reply {
+    Reply::Bulk(format!("{:.4}", meters / unit).into_bytes())
+}
+
+fn position_reply((lon, lat): (f64, f64)) -> Reply {
+    Reply::Array(vec![Reply::Bulk(lon.to_string().into_bytes()), Reply::Bulk(lat.to_string().into_bytes())])
+}
+
+/// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+pub(super) fn geoadd(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 5 {
+        return Err(wrong_arity(parts));
+    }
+    let (mut nx, mut xx, mut ch) = (false, false, false);
+    let mut i = 2;
+    while i < parts.len() {
+        match parts[i].to_ascii_uppercase().as_slice() {
+            b"NX" => nx = true,
+            b"XX" => xx = true,
+            b"CH" => ch = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    let triples = &parts[i..];
+    if triples.is_empty() || !triples.len().is_multiple_of(3) {
+        return Err(CommandError::Syntax);
+    }
+    if nx && xx {
+        return Err(CommandError::IncompatibleOptions("XX and NX options at the same time are not compatible"));
+    }
+    let positions = triples
+        .chunks(3)
+        .map(|triple| parse_position(&triple[0], &triple[1]))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let key = &parts[1];
+    if get_zset(db, key)?.is_none() && xx {
+        return Ok(Reply::Integer(0));
+    }
+    let zset = get_or_create_zset(db, key)?;
+    let (mut added, mut updated) = (0, 0);
+    for (triple, (lon, lat)) in triples.chunks(3).zip(positions) {
+        let member = &triple[2];
+        let score = geo::encode(lon, lat) as f64;
+        match zset.score(member) {
+            Some(_) if nx => continue,
+            None if xx => continue,
+            Some(old) if old == score => continue,
+            Some(_) => updated += 1,
+            None => added += 1,
+        }
+        zset.insert(member.clone(), score);
+    }
+    if added + updated > 0 {
+        log_command(aof_tx, parts);
+    }
+    remove_if_empty(db, key);
+    Ok(Reply::Integer(if ch { added + updated } else { added }))
+}
+
+/// Position (centre de la cellule du géohash) de `member`
+fn member_position(db: &mut Keyspace, key: &[u8], member: &[u8]) -> Result<Option<(f64, f64)>, CommandError> {
+    Ok(get_zset(db, key)?.and_then(|zset| zset.score(member)).map(|score| geo::decode(score as u64)))
+}
+
+/// GEODIST key member1 member2 [M | KM | FT | MI]
+pub(super) fn geodist(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 4 && parts.len() != 5 {
+        return Err(wrong_arity(parts));
+    }
+    let unit = parts.get(4).map_or(Ok(1.0), |arg| parse_unit(arg))?;
+    let from = member_position(db, &parts[1], &parts[2])?;
+    let to = member_position(db, &parts[1], &parts[3])?;
+    Ok(match (from, to) {
+        (Some((lon1, lat1)), Some((lon2, lat2))) => distance_reply(geo::distance(lon1, lat1, lon2, lat2), unit),
+        _ => Reply::Nil,
+    })
+}
+
+/// GEOPOS key [member ...]
+pub(super) fn geopos(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let mut positions = Vec::with_capacity(parts.len() - 2);
+    for member in &parts[2..] {
+        positions.push(member_position(db, &parts[1], member)?.map_or(Reply::NilArray, position_reply));
+    }
+    Ok(Reply::Array(positions))
+}
+
+/// GEOHASH key [member ...]
+pub(super) fn geohash(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let zset = get_zset(db, &parts[1])?;
+    let hashes = parts[2..]
+        .iter()
+        .map(|member| match zset.as_ref().and_then(|zset| zset.score(member)) {
+            Some(score) => Reply::Bulk(geo::to_base32(score as u64).into_bytes()),
+            None => Reply::Nil,
+        })
+        .collect();
+    Ok(Reply::Array(hashes))
+}
+
+enum Origin<'a> {
+    Member(&'a [u8]),
+    Position(f64, f64),
+}
+
+/// GEOSEARCH key <FROMMEMBER member | FROMLONLAT longitude latitude>
+/// <BYRADIUS radius unit | BYBOX width height unit> [ASC | DESC] [COUNT count [ANY]]
+/// [WITHCOORD] [WITHDIST] [WITHHASH]
+///
+/// Avec COUNT sans ANY, les résultats sont triés par distance croissante par défaut.
+/// Avec ANY, la recherche s'arrête dès que `count` membres ont été trouvés.
+pub(super) fn geosearch(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 7 {
+        return Err(wrong_arity(parts));
+    }
+    let (mut origin, mut shape, mut unit) = (None, None, 1.0);
+    let (mut descending, mut sort, mut count, mut any) = (false, false, None, false);
+    let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+    let arg = |i: usize| parts.get(i).ok_or(CommandError::Syntax);
+    let mut i = 2;
+    while i < parts.len() {
+        match parts[i].to_ascii_uppercase().as_slice() {
+            b"FROMMEMBER" if origin.is_none() => {
+                origin = Some(Origin::Member(arg(i + 1)?));
+                i += 1;
+            },
+            b"FROMLONLAT" if origin.is_none() => {
+                let (lon, lat) = parse_position(arg(i + 1)?, arg(i + 2)?)?;
+                origin = Some(Origin::Position(lon, lat));
+                i += 2;
+            },
+            b"BYRADIUS" if shape.is_none() => {
+                let radius = parse_length(arg(i + 1)?)?;
+                unit = parse_unit(arg(i + 2)?)?;
+                shape = Some(Shape::Radius(radius * unit));
+                i += 2;
+            },
+            b"BYBOX" if shape.is_none() => {
+                let (width, height) = (parse_length(arg(i + 1)?)?, parse_length(arg(i + 2)?)?);
+                unit = parse_unit(arg(i + 3)?)?;
+                shape = Some(Shape::Box { width: width * unit, height: height * unit });
+                i += 3;
+            },
+            b"FROMMEMBER" | b"FROMLONLAT" => {
+                return Err(CommandError::IncompatibleOptions(
+                    "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+                ));
+            },
+            b"BYRADIUS" | b"BYBOX" => {
+                return Err(CommandError::IncompatibleOptions(
+                    "exactly one of BYRADIUS and BYBOX arguments must be provided for GEOSEARCH",
+                ));
+            },
+            b"ASC" => (sort, descending) = (true, false),
+            b"DESC" => (sort, descending) = (true, true),
+            b"COUNT" => {
+                let n = parse_arg::<i64>(arg(i + 1)?).ok_or(CommandError::NotInteger)?;
+                count = Some(usize::try_from(n).ok().filter(|n| *n > 0).ok_or(CommandError::CountNotPositive)?);
+                i += 1;
+            },
+            b"ANY" => any = true,
+            b"WITHCOORD" => with_coord = true,
+            b"WITHDIST" => with_dist = true,
+            b"WITHHASH" => with_hash = true,
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+    }
+    let Some(origin) = origin else {
+        return Err(CommandError::IncompatibleOptions(
+            "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+        ));
+    };
+    let Some(shape) = shape else {
+        return Err(CommandError::IncompatibleOptions(
+            "exactly one of BYRADIUS and BYBOX arguments must be provided for GEOSEARCH",
+        ));
+    };
+    if any && count.is_none() {
+        return Err(CommandError::IncompatibleOptions("the ANY argument requires COUNT argument"));
+    }
+    sort |= count.is_some() && !any;
+
+    let Some(zset) = get_zset(db, &parts[1])? else {
+        return Ok(Reply::Array(Vec::new()));
+    };
+    let (lon, lat) = match origin {
+        Origin::Position(lon, lat) => (lon, lat),
+        Origin::Member(member) => geo::decode(zset.score(member).ok_or(CommandError::GeoMemberMissing)? as u64),
+    };
+
+    let mut found = Vec::new();
+    'ranges: for (min, max) in shape.score_ranges(lon, lat) {
+        let range = ScoreRange { min: min as f64, min_exclusive: false, max: max as f64, max_exclusive: true };
+        for (member, score) in zset.range_by_score(range, false) {
+            let (point_lon, point_lat) = geo::decode(score as u64);
+            if let Some(distance) = shape.distance_if_inside(lon, lat, point_lon, point_lat) {
+                found.push((member, score as u64, distance, (point_lon, point_lat)));
+                if any && count.is_some_and(|count| found.len() >= count) {
+                    break 'ranges;
+                }
+            }
+        }
+    }
+    if sort {
+        found.sort_by(|a, b| a.2.total_cmp(&b.2));
+        if descending {
+            found.reverse();
+        }
+    }
+    found.truncate(count.unwrap_or(usize::MAX));
+
+    let replies = found
+        .into_iter()
+        .map(|(member, hash, distance, position)| {
+            if !(with_coord || with_dist || with_hash) {
+                return Reply::Bulk(member.to_vec());
+            }
+            let mut item = vec![Reply::Bulk(member.to_vec())];
+            if with_dist {
+                item.push(distance_reply(distance, unit));
+            }
+            if with_hash {
+                item.push(Reply::Integer(hash as i64));
+            }
+            if with_coord {
+                item.push(position_reply(position));
+            }
+            Reply::Array(item)
+        })
+        .collect();
+    Ok(Reply::Array(replies))
+}
@@ -0,0 +1,200 @@
+// src/commands/hashes.rs
+use super::wrong_arity;
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, format_double, parse_arg, Reply};
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+
+type Hash = HashMap<Vec<u8>, Vec<u8>>;
+
+/// Hash stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+fn get_hash<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut Hash>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::Hash(hash), .. }) => Ok(Some(hash)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+fn get_or_create_hash<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<&'a mut Hash, CommandError> {
+    match &mut get_or_insert(db, key, || Value::Hash(HashMap::new())).value {
+        Value::Hash(hash) => Ok(hash),
+        _ => Err(CommandError::WrongType),
+    }
+}
+
+/// HSET key field value [field value ...] (HMSET est l'ancien nom, qui répond OK)
+pub(super) fn hset(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 || !parts.len().is_multiple_of(2) {
+        return Err(wrong_arity(parts));
+    }
+    let hash = get_or_create_hash(db, &parts[1])?;
+    let mut added = 0;
+    for pair in parts[2..].chunks(2) {
+        if hash.insert(pair[0].clone(), pair[1].clone()).is_none() {
+            added += 1;
+        }
+    }
+    let mut logged: Vec<&[u8]> = vec![b"HSET"];
+    logged.extend(parts[1..].iter().map(Vec::as_slice));
+    aof_tx.send(format_command(&logged)).unwrap();
+
+    if parts[0].eq_ignore_ascii_case(b"HMSET") {
+        Ok(Reply::ok())
+    } else {
+        Ok(Reply::Integer(added))
+    }
+}
+
+/// HSETNX key field value
+pub(super) fn hsetnx(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let exists = get_hash(db, &parts[1])?.is_some_and(|hash| hash.contains_key(&parts[2]));
+    if exists {
+        return Ok(Reply::Integer(0));
+    }
+    get_or_create_hash(db, &parts[1])?.insert(parts[2].clone(), parts[3].clone());
+    aof_tx.send(format_command(&[b"HSET", &parts[1], &parts[2], &parts[3]])).unwrap();
+    Ok(Reply::Integer(1))
+}
+
+/// HGET key field
+pub(super) fn hget(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let value = get_hash(db, &parts[1])?.and_then(|hash| hash.get(&parts[2]).cloned());
+    Ok(value.map_or(Reply::Nil, Reply::Bulk))
+}
+
+/// HMGET key field [field ...]
+pub(super) fn hmget(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let hash = get_hash(db, &parts[1])?;
+    let values = parts[2..]
+        .iter()
+        .map(|field| {
+            hash.as_ref()
+                .and_then(|hash| hash.get(field).cloned())
+                .map_or(Reply::Nil, Reply::Bulk)
+        })
+        .collect();
+    Ok(Reply::Array(values))
+}
+
+/// HDEL key field [field ...]
+pub(super) fn hdel(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let hash = match get_hash(db, key)? {
+        Some(hash) => hash,
+        None => return Ok(Reply::Integer(0)),
+    };
+    let removed = parts[2..].iter().filter(|field| hash.remove(*field).is_some()).count();
+    let now_empty = hash.is_empty();
+    if removed > 0 {
+        super::log_command(aof_tx, parts);
+    }
+    if now_empty {
+        db.remove(key);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// HGETALL key | HKEYS key | HVALS key
+pub(super) fn hgetall(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+    let empty = HashMap::new();
+    let hash = get_hash(db, &parts[1])?.map_or(&empty, |hash| &*hash);
+    Ok(match command.as_str() {
+        "HKEYS" => Reply::Array(hash.keys().cloned().map(Reply::Bulk).collect()),
+        "HVALS" => Reply::Array(hash.values().cloned().map(Reply::Bulk).collect()),
+        _ => Reply::Map(
+            hash.iter()
+                .map(|(field, value)| (Reply::Bulk(field.clone()), Reply::Bulk(value.clone())))
+                .collect(),
+        ),
+    })
+}
+
+/// HINCRBY key field increment
+///
+/// Comme pour INCRBY, c'est la valeur obtenue qui est journalisée (sous forme de HSET).
+pub(super) fn hincrby(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let increment = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+    let current = match get_hash(db, &parts[1])?.and_then(|hash| hash.get(&parts[2])) {
+        Some(value) => parse_arg::<i64>(value).ok_or(CommandError::HashValueNotInteger)?,
+        None => 0,
+    };
+    let result = current.checked_add(increment).ok_or(CommandError::Overflow)?;
+    store_field(db, parts, result.to_string().into_bytes(), aof_tx)?;
+    Ok(Reply::Integer(result))
+}
+
+/// HINCRBYFLOAT key field increment
+pub(super) fn hincrbyfloat(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let increment = parse_arg::<f64>(&parts[3]).filter(|f| !f.is_nan()).ok_or(CommandError::NotFloat)?;
+    let current = match get_hash(db, &parts[1])?.and_then(|hash| hash.get(&parts[2])) {
+        Some(value) => parse_arg::<f64>(value).filter(|f| !f.is_nan()).ok_or(CommandError::HashValueNotFloat)?,
+        None => 0.0,
+    };
+    let result = current + increment;
+    if !result.is_finite() {
+        return Err(CommandError::NanOrInfinity);
+    }
+    let value = format_double(result).into_bytes();
+    store_field(db, parts, value.clone(), aof_tx)?;
+    Ok(Reply::Bulk(value))
+}
+
+/// Écrit le résultat d'un incrément dans le champ `parts[2]` et le journalise sous forme de HSET
+fn store_field(db: &mut Keyspace, parts: &[Vec<u8>], value: Vec<u8>, aof_tx: &Sender<String>) -> Result<(), CommandError> {
+    aof_tx.send(format_command(&[b"HSET", &parts[1], &parts[2], &value])).unwrap();
+    get_or_create_hash(db, &parts[1])?.insert(parts[2].clone(), value);
+    Ok(())
+}
+
+/// HEXISTS key field
+pub(super) fn hexists(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let exists = get_hash(db, &parts[1])?.is_some_and(|hash| hash.contains_key(&parts[2]));
+    Ok(Reply::Integer(exists as i64))
+}
+
+/// HLEN key
+pub(super) fn hlen(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_hash(db, &parts[1])?.map_or(0, |hash| hash.len());
+    Ok(Reply::Integer(len as i64))
+}
+
+/// HSTRLEN key field
+pub(super) fn hstrlen(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_hash(db, &parts[1])?
+        .and_then(|hash| hash.get(&parts[2]))
+        .map_or(0, Vec::len);
+    Ok(Reply::Integer(len as i64))
+}
@@ -0,0 +1,79 @@
+// src/commands/hyperloglogs.rs
+use super::{log_command, wrong_arity};
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::hyperloglog::HyperLogLog;
+use crate::protocol::Reply;
+use std::sync::mpsc::Sender;
+
+/// HyperLogLog stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+fn get_hyperloglog<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut HyperLogLog>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::HyperLogLog(hll), .. }) => Ok(Some(hll)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+/// PFADD key [element ...]
+///
+/// Renvoie 1 si la clé a été créée ou si un registre a changé.
+pub(super) fn pfadd(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let mut changed = get_hyperloglog(db, key)?.is_none();
+    let hll = match &mut get_or_insert(db, key, || Value::HyperLogLog(HyperLogLog::new())).value {
+        Value::HyperLogLog(hll) => hll,
+        _ => return Err(CommandError::WrongType),
+    };
+    for element in &parts[2..] {
+        changed |= hll.add(element);
+    }
+    if changed {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(changed as i64))
+}
+
+/// PFCOUNT key [key ...]
+///
+/// Avec plusieurs clés, estime la cardinalité de leur union.
+pub(super) fn pfcount(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    if parts.len() == 2 {
+        return Ok(Reply::Integer(get_hyperloglog(db, &parts[1])?.map_or(0, |hll| hll.count()) as i64));
+    }
+    let union = union(&parts[1..], db)?;
+    Ok(Reply::Integer(union.count() as i64))
+}
+
+/// PFMERGE destkey [sourcekey ...]
+///
+/// `destkey` fait partie de l'union s'il existe déjà ; son expiration est conservée.
+pub(super) fn pfmerge(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let union = union(&parts[1..], db)?;
+    match &mut get_or_insert(db, &parts[1], || Value::HyperLogLog(HyperLogLog::new())).value {
+        Value::HyperLogLog(hll) => *hll = union,
+        _ => return Err(CommandError::WrongType),
+    }
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// Union des HyperLogLog des clés données (les clés absentes sont ignorées)
+fn union(keys: &[Vec<u8>], db: &mut Keyspace) -> Result<HyperLogLog, CommandError> {
+    let mut union = HyperLogLog::new();
+    for key in keys {
+        if let Some(hll) = get_hyperloglog(db, key)? {
+            union.merge(hll);
+        }
+    }
+    Ok(union)
+}
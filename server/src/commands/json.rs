@@ -0,0 +1,278 @@
+// src/commands/json.rs
+//! Documents JSON. Avec un chemin JSONPath (`$...`), les commandes s'appliquent à toutes les
+//! correspondances et renvoient un résultat par correspondance ; avec l'ancienne syntaxe (`.a.b`),
+//! seule la première correspondance compte et un chemin absent est une erreur.
+
+use super::{log_command, wrong_arity};
+use crate::db::{get_live, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::json_path::{self, Location, Path};
+use crate::protocol::{format_command, Reply};
+use serde_json::{Number, Value as Json};
+use std::sync::mpsc::Sender;
+
+/// Document stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+fn get_json<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut Json>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::Json(document), .. }) => Ok(Some(document)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+fn parse_path(arg: &[u8]) -> Result<Path, CommandError> {
+    let path = String::from_utf8_lossy(arg);
+    Path::parse(&path).ok_or_else(|| CommandError::InvalidJsonPath(path.into_owned()))
+}
+
+fn parse_json(arg: &[u8]) -> Result<Json, CommandError> {
+    serde_json::from_slice(arg).map_err(|e| CommandError::InvalidJson(e.to_string()))
+}
+
+/// Positions désignées par `path` ; l'ancienne syntaxe n'en garde qu'une et exige qu'elle existe
+fn locate(path: &Path, arg: &[u8], document: &Json) -> Result<Vec<Vec<Location>>, CommandError> {
+    let mut locations = path.locate(document);
+    if !path.json_path {
+        locations.truncate(1);
+        if locations.is_empty() {
+            return Err(CommandError::JsonPathMissing(String::from_utf8_lossy(arg).into_owned()));
+        }
+    }
+    Ok(locations)
+}
+
+fn json_type(value: &Json) -> &'static str {
+    match value {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(n) if n.is_f64() => "number",
+        Json::Number(_) => "integer",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+fn json_reply(value: &Json) -> Reply {
+    Reply::Bulk(value.to_string().into_bytes())
+}
+
+/// JSON.SET key path value [NX | XX]
+///
+/// Remplace les valeurs désignées par `path`. Si la dernière étape est un nom de membre absent,
+/// le membre est ajouté aux objets désignés par le reste du chemin. Un nouveau document ne peut
+/// être créé qu'à la racine.
+pub(super) fn json_set(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 && parts.len() != 5 {
+        return Err(wrong_arity(parts));
+    }
+    let (nx, xx) = match parts.get(4).map(|option| option.to_ascii_uppercase()).as_deref() {
+        None => (false, false),
+        Some(b"NX") => (true, false),
+        Some(b"XX") => (false, true),
+        Some(_) => return Err(CommandError::Syntax),
+    };
+    let key = &parts[1];
+    let path = parse_path(&parts[2])?;
+    let value = parse_json(&parts[3])?;
+
+    let Some(document) = get_json(db, key)? else {
+        if !path.is_root() {
+            return Err(CommandError::JsonNewObjectAtRoot);
+        }
+        if xx {
+            return Ok(Reply::Nil);
+        }
+        db.insert(key.clone(), Entry::new(Value::Json(value)));
+        log_command(aof_tx, parts);
+        return Ok(Reply::ok());
+    };
+    if path.is_root() {
+        if nx {
+            return Ok(Reply::Nil);
+        }
+        *document = value;
+        log_command(aof_tx, parts);
+        return Ok(Reply::ok());
+    }
+
+    let mut changed = false;
+    if !nx {
+        let mut targets = path.locate(document);
+        if !path.json_path {
+            targets.truncate(1);
+        }
+        for location in targets {
+            if let Some(target) = json_path::get_mut(document, &location) {
+                *target = value.clone();
+                changed = true;
+            }
+        }
+    }
+    if let (Some(name), false) = (path.last_key(), xx) {
+        let mut parents = path.parent().locate(document);
+        if !path.json_path {
+            parents.truncate(1);
+        }
+        for location in parents {
+            if let Some(Json::Object(object)) = json_path::get_mut(document, &location) {
+                if !object.contains_key(name) {
+                    object.insert(name.to_string(), value.clone());
+                    changed = true;
+                }
+            }
+        }
+    }
+    if !changed {
+        return Ok(Reply::Nil);
+    }
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// JSON.GET key [path ...]
+///
+/// Sans chemin, renvoie le document entier. Avec plusieurs chemins, renvoie un objet indexé par chemin.
+pub(super) fn json_get(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let paths = parts[2..].iter().map(|arg| parse_path(arg)).collect::<Result<Vec<_>, _>>()?;
+    let Some(document) = get_json(db, &parts[1])? else {
+        return Ok(Reply::Nil);
+    };
+    // Dès qu'un chemin est en JSONPath, tous les résultats prennent la forme JSONPath (tableaux)
+    let json_path = paths.iter().any(|path| path.json_path);
+    let mut results = Vec::with_capacity(paths.len());
+    for (path, arg) in paths.iter().zip(&parts[2..]) {
+        let locations = if json_path { path.locate(document) } else { locate(path, arg, document)? };
+        let mut values = locations.iter().filter_map(|location| json_path::get(document, location).cloned());
+        results.push(if json_path { Json::Array(values.collect()) } else { values.next().unwrap_or(Json::Null) });
+    }
+    Ok(match results.len() {
+        0 => json_reply(document),
+        1 => json_reply(&results[0]),
+        _ => {
+            let names = parts[2..].iter().map(|arg| String::from_utf8_lossy(arg).into_owned());
+            json_reply(&Json::Object(names.zip(results).collect()))
+        },
+    })
+}
+
+/// JSON.DEL key [path] (et son alias JSON.FORGET)
+///
+/// Renvoie le nombre de valeurs supprimées. Supprimer la racine supprime la clé.
+pub(super) fn json_del(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 2 && parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let path = match parts.get(2) {
+        Some(arg) => parse_path(arg)?,
+        None => Path::parse("$").unwrap(),
+    };
+    let Some(document) = get_json(db, key)? else {
+        return Ok(Reply::Integer(0));
+    };
+    if path.is_root() {
+        db.remove(key);
+        log_command(aof_tx, parts);
+        return Ok(Reply::Integer(1));
+    }
+    let mut locations = path.locate(document);
+    if !path.json_path {
+        locations.truncate(1);
+    }
+    // Les derniers indices d'abord, pour que la suppression n'en décale pas d'autres
+    locations.sort();
+    locations.dedup();
+    let deleted = locations.iter().rev().filter(|location| json_path::remove(document, location)).count();
+    if deleted > 0 {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(deleted as i64))
+}
+
+/// Somme de deux nombres JSON : entière tant que possible, flottante sinon (`None` si non finie)
+fn add_numbers(a: &Number, b: &Number) -> Option<Number> {
+    if let Some(sum) = a.as_i64().zip(b.as_i64()).and_then(|(a, b)| a.checked_add(b)) {
+        return Some(sum.into());
+    }
+    Number::from_f64(a.as_f64()? + b.as_f64()?)
+}
+
+/// JSON.NUMINCRBY key path value
+///
+/// Chaque résultat est journalisé sous la forme `JSON.SET key <position> <valeur>`, comme
+/// les autres incréments : seule la valeur modifiée passe dans l'AOF.
+pub(super) fn json_numincrby(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let path = parse_path(&parts[2])?;
+    let increment = match parse_json(&parts[3])? {
+        Json::Number(n) => n,
+        other => return Err(CommandError::JsonWrongPathType("number", json_type(&other))),
+    };
+    let document = get_json(db, key)?.ok_or(CommandError::NoSuchKey)?;
+    let locations = locate(&path, &parts[2], document)?;
+
+    // Tous les résultats sont calculés avant de modifier le document
+    let mut results = Vec::with_capacity(locations.len());
+    for location in &locations {
+        results.push(match json_path::get(document, location) {
+            Some(Json::Number(n)) => Json::Number(add_numbers(n, &increment).ok_or(CommandError::NanOrInfinity)?),
+            Some(other) if !path.json_path => {
+                return Err(CommandError::JsonWrongPathType("number", json_type(other)));
+            },
+            _ => Json::Null,
+        });
+    }
+    for (location, result) in locations.iter().zip(&results) {
+        if result.is_null() {
+            continue;
+        }
+        if let Some(target) = json_path::get_mut(document, location) {
+            *target = result.clone();
+        }
+        let position = json_path::to_path(location);
+        let value = result.to_string();
+        aof_tx.send(format_command(&[b"JSON.SET", key, position.as_bytes(), value.as_bytes()])).unwrap();
+    }
+    Ok(if path.json_path { json_reply(&Json::Array(results)) } else { json_reply(&results[0]) })
+}
+
+/// JSON.ARRAPPEND key path value [value ...]
+///
+/// Renvoie la nouvelle longueur de chaque tableau (nil pour une correspondance qui n'en est pas un).
+/// Chaque tableau modifié est journalisé en entier sous la forme `JSON.SET key <position> <tableau>`,
+/// pour qu'un rejeu ne répète pas l'ajout.
+pub(super) fn json_arrappend(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let path = parse_path(&parts[2])?;
+    let values = parts[3..].iter().map(|arg| parse_json(arg)).collect::<Result<Vec<_>, _>>()?;
+    let document = get_json(db, key)?.ok_or(CommandError::NoSuchKey)?;
+    let locations = locate(&path, &parts[2], document)?;
+
+    let mut lengths = Vec::with_capacity(locations.len());
+    for location in &locations {
+        lengths.push(match json_path::get_mut(document, location) {
+            Some(Json::Array(array)) => {
+                array.extend(values.iter().cloned());
+                let position = json_path::to_path(location);
+                let value = Json::Array(array.clone()).to_string();
+                aof_tx.send(format_command(&[b"JSON.SET", key, position.as_bytes(), value.as_bytes()])).unwrap();
+                Reply::Integer(array.len() as i64)
+            },
+            Some(other) if !path.json_path => {
+                return Err(CommandError::JsonWrongPathType("array", json_type(other)));
+            },
+            _ => Reply::Nil,
+        });
+    }
+    Ok(if path.json_path { Reply::Array(lengths) } else { lengths.remove(0) })
+}
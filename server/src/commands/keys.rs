@@ -0,0 +1,279 @@
+// src/commands/keys.rs
+//! Commandes qui portent sur les clés elles-mêmes, quel que soit leur type.
+
+use super::{log_command, parse_db_index, wrong_arity};
+use crate::db::{peek_live, Databases, Entry, Keyspace, DATABASES};
+use crate::error::CommandError;
+use crate::glob;
+use crate::protocol::{parse_arg, Reply};
+use std::sync::mpsc::Sender;
+
+/// EXISTS key [key ...]
+///
+/// Une clé répétée est comptée autant de fois qu'elle apparaît.
+pub(super) fn exists(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let count = parts[1..].iter().filter(|key| peek_live(db, key).is_some()).count();
+    Ok(Reply::Integer(count as i64))
+}
+
+/// TYPE key
+pub(super) fn key_type(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let name = peek_live(db, &parts[1]).map_or("none", |entry| entry.value.type_name());
+    Ok(Reply::Simple(name.to_string()))
+}
+
+/// KEYS pattern
+///
+/// Parcourt toute la base sous le verrou : SCAN est préférable sur une grosse base.
+pub(super) fn keys(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let keys = db
+        .iter()
+        .filter(|(key, entry)| !entry.is_expired() && glob::matches(&parts[1], key))
+        .map(|(key, _)| Reply::Bulk(key.clone()))
+        .collect();
+    Ok(Reply::Array(keys))
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+///
+/// Chaque appel examine environ `count` clés (10 par défaut) avant de filtrer par motif et par type,
+/// et ne garde le verrou que le temps de cette page.
+pub(super) fn scan(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let cursor = parse_arg::<u64>(&parts[1]).ok_or(CommandError::InvalidCursor)?;
+    let (mut pattern, mut count, mut type_name) = (None, 10, None);
+    let mut i = 2;
+    while i < parts.len() {
+        let value = parts.get(i + 1).ok_or(CommandError::Syntax)?;
+        match parts[i].to_ascii_uppercase().as_slice() {
+            b"MATCH" => pattern = Some(value),
+            b"COUNT" => {
+                let n = parse_arg::<i64>(value).ok_or(CommandError::NotInteger)?;
+                count = usize::try_from(n).ok().filter(|n| *n > 0).ok_or(CommandError::Syntax)?;
+            },
+            b"TYPE" => type_name = Some(value),
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 2;
+    }
+
+    let (next, examined) = db.scan(cursor, count);
+    let keys = examined
+        .into_iter()
+        .filter(|key| {
+            let entry = &db[*key];
+            !entry.is_expired()
+                && pattern.is_none_or(|pattern| glob::matches(pattern, key))
+                && type_name.is_none_or(|name| name.eq_ignore_ascii_case(entry.value.type_name().as_bytes()))
+        })
+        .map(|key| Reply::Bulk(key.to_vec()))
+        .collect();
+    Ok(Reply::Array(vec![Reply::Bulk(next.to_string().into_bytes()), Reply::Array(keys)]))
+}
+
+/// DBSIZE
+///
+/// Comme dans Redis, les clés expirées que personne n'a encore supprimées sont comptées.
+pub(super) fn dbsize(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 1 {
+        return Err(wrong_arity(parts));
+    }
+    Ok(Reply::Integer(db.len() as i64))
+}
+
+/// RANDOMKEY
+///
+/// Une clé expirée tirée au sort est supprimée, puis un autre tirage est fait.
+pub(super) fn randomkey(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 1 {
+        return Err(wrong_arity(parts));
+    }
+    loop {
+        let Some(key) = db.random_key().map(<[u8]>::to_vec) else {
+            return Ok(Reply::Nil);
+        };
+        if peek_live(db, &key).is_some() {
+            return Ok(Reply::Bulk(key));
+        }
+    }
+}
+
+/// RENAME key newkey et RENAMENX key newkey
+///
+/// La valeur garde son expiration et ses métadonnées d'accès. RENAMENX ne fait rien (et renvoie 0)
+/// si `newkey` existe déjà.
+pub(super) fn rename(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let nx = parts[0].eq_ignore_ascii_case(b"RENAMENX");
+    let (key, new_key) = (&parts[1], &parts[2]);
+    if peek_live(db, key).is_none() {
+        return Err(CommandError::NoSuchKey);
+    }
+    if key == new_key || (nx && peek_live(db, new_key).is_some()) {
+        return Ok(if nx { Reply::Integer(0) } else { Reply::ok() });
+    }
+    let entry = db.remove(key).unwrap();
+    db.insert(new_key.clone(), entry);
+    log_command(aof_tx, parts);
+    Ok(if nx { Reply::Integer(1) } else { Reply::ok() })
+}
+
+/// COPY source destination [DB destination-db] [REPLACE]
+///
+/// La copie garde l'expiration de la source. Renvoie 0 si la source est absente ou si la
+/// destination existe déjà sans REPLACE.
+pub(super) fn copy(
+    parts: &[Vec<u8>],
+    dbs: &mut Databases,
+    index: usize,
+    aof_tx: &Sender<String>,
+) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let (mut replace, mut target) = (false, index);
+    let mut i = 3;
+    while i < parts.len() {
+        match parts[i].to_ascii_uppercase().as_slice() {
+            b"REPLACE" => replace = true,
+            b"DB" => {
+                target = parse_db_index(parts.get(i + 1).ok_or(CommandError::Syntax)?)?;
+                i += 1;
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+    }
+    let (source, destination) = (&parts[1], &parts[2]);
+    if source == destination && target == index {
+        return Err(CommandError::SameObject);
+    }
+    let Some(entry) = peek_live(dbs.keyspace_mut(index), source) else {
+        return Ok(Reply::Integer(0));
+    };
+    let copy = Entry::with_expiry(entry.value.clone(), entry.expire_at);
+    let db = dbs.keyspace_mut(target);
+    if !replace && peek_live(db, destination).is_some() {
+        return Ok(Reply::Integer(0));
+    }
+    db.insert(destination.clone(), copy);
+    dbs.touch(target, destination);
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(1))
+}
+
+/// MOVE key db
+///
+/// La clé garde son expiration et ses métadonnées d'accès. Renvoie 0 si elle est absente de la base
+/// courante ou existe déjà dans la base de destination.
+pub(super) fn move_key(
+    parts: &[Vec<u8>],
+    dbs: &mut Databases,
+    index: usize,
+    aof_tx: &Sender<String>,
+) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let target = parse_db_index(&parts[2])?;
+    if target == index {
+        return Err(CommandError::SameObject);
+    }
+    let key = &parts[1];
+    if peek_live(dbs.keyspace_mut(index), key).is_none() || peek_live(dbs.keyspace_mut(target), key).is_some() {
+        return Ok(Reply::Integer(0));
+    }
+    let entry = dbs.keyspace_mut(index).remove(key).unwrap();
+    dbs.keyspace_mut(target).insert(key.clone(), entry);
+    dbs.touch(index, key);
+    dbs.touch(target, key);
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(1))
+}
+
+/// Option ASYNC ou SYNC de FLUSHDB et FLUSHALL : la base est toujours vidée immédiatement
+fn check_flush_mode(parts: &[Vec<u8>]) -> Result<(), CommandError> {
+    match parts.len() {
+        1 => Ok(()),
+        2 if parts[1].eq_ignore_ascii_case(b"ASYNC") || parts[1].eq_ignore_ascii_case(b"SYNC") => Ok(()),
+        2 => Err(CommandError::Syntax),
+        _ => Err(wrong_arity(parts)),
+    }
+}
+
+/// FLUSHDB [ASYNC | SYNC]
+pub(super) fn flushdb(
+    parts: &[Vec<u8>],
+    dbs: &mut Databases,
+    index: usize,
+    aof_tx: &Sender<String>,
+) -> Result<Reply, CommandError> {
+    check_flush_mode(parts)?;
+    dbs.keyspace_mut(index).clear();
+    dbs.touch_all(index);
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// FLUSHALL [ASYNC | SYNC]
+pub(super) fn flushall(parts: &[Vec<u8>], dbs: &mut Databases, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    check_flush_mode(parts)?;
+    dbs.flush_all();
+    (0..DATABASES).for_each(|index| dbs.touch_all(index));
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// SWAPDB index1 index2
+///
+/// Les connexions qui ont sélectionné l'une des deux bases voient aussitôt le contenu de l'autre.
+pub(super) fn swapdb(parts: &[Vec<u8>], dbs: &mut Databases, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let (a, b) = (parse_db_index(&parts[1])?, parse_db_index(&parts[2])?);
+    dbs.swap(a, b);
+    dbs.touch_all(a);
+    dbs.touch_all(b);
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// OBJECT ENCODING | IDLETIME | FREQ | REFCOUNT key
+///
+/// Ne compte pas comme un accès à la clé. Renvoie nil si la clé est absente.
+pub(super) fn object(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let subcommand = String::from_utf8_lossy(&parts[1]).to_uppercase();
+    if !matches!(subcommand.as_str(), "ENCODING" | "IDLETIME" | "FREQ" | "REFCOUNT") {
+        let subcommand = String::from_utf8_lossy(&parts[1]).into_owned();
+        return Err(CommandError::UnknownSubcommand(subcommand, "OBJECT".to_string()));
+    }
+    if parts.len() != 3 {
+        return Err(CommandError::WrongArity(format!("object|{}", subcommand.to_lowercase())));
+    }
+    let Some(entry) = peek_live(db, &parts[2]) else {
+        return Ok(Reply::Nil);
+    };
+    Ok(match subcommand.as_str() {
+        "ENCODING" => Reply::Bulk(entry.value.encoding().as_bytes().to_vec()),
+        "IDLETIME" => Reply::Integer(entry.access.idle().as_secs() as i64),
+        "FREQ" => Reply::Integer(entry.access.freq() as i64),
+        _ => Reply::Integer(1),
+    })
+}
@@ -0,0 +1,199 @@
+// src/commands/lists.rs
+use super::{log_command, normalize_range, wrong_arity};
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{parse_arg, Reply};
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+
+/// Liste stockée sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+fn get_list<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut VecDeque<Vec<u8>>>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::List(list), .. }) => Ok(Some(list)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+/// Supprime la clé si la liste est devenue vide, comme Redis
+fn remove_if_empty(db: &mut Keyspace, key: &[u8]) {
+    if matches!(db.get(key), Some(Entry { value: Value::List(list), .. }) if list.is_empty()) {
+        db.remove(key);
+    }
+}
+
+/// LPUSH key element [element ...] | RPUSH key element [element ...]
+pub(super) fn push(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let left = parts[0].eq_ignore_ascii_case(b"LPUSH");
+    let list = match &mut get_or_insert(db, &parts[1], || Value::List(VecDeque::new())).value {
+        Value::List(list) => list,
+        _ => return Err(CommandError::WrongType),
+    };
+    for element in &parts[2..] {
+        if left {
+            list.push_front(element.clone());
+        } else {
+            list.push_back(element.clone());
+        }
+    }
+    let len = list.len();
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(len as i64))
+}
+
+/// LPOP key [count] | RPOP key [count]
+pub(super) fn pop(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 2 && parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let left = parts[0].eq_ignore_ascii_case(b"LPOP");
+    let count = match parts.get(2) {
+        Some(count) => Some(parse_arg::<i64>(count).filter(|c| *c >= 0).ok_or(CommandError::OutOfRange)? as usize),
+        None => None,
+    };
+    let key = &parts[1];
+    let list = match get_list(db, key)? {
+        Some(list) => list,
+        None => return Ok(if count.is_some() { Reply::NilArray } else { Reply::Nil }),
+    };
+
+    let popped: Vec<Vec<u8>> = (0..count.unwrap_or(1))
+        .map_while(|_| if left { list.pop_front() } else { list.pop_back() })
+        .collect();
+    if !popped.is_empty() {
+        log_command(aof_tx, parts);
+        remove_if_empty(db, key);
+    }
+    Ok(match count {
+        Some(_) => Reply::Array(popped.into_iter().map(Reply::Bulk).collect()),
+        None => popped.into_iter().next().map_or(Reply::Nil, Reply::Bulk),
+    })
+}
+
+/// LRANGE key start stop
+pub(super) fn lrange(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let start = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let stop = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+    let list = match get_list(db, &parts[1])? {
+        Some(list) => list,
+        None => return Ok(Reply::Array(Vec::new())),
+    };
+    let items = match normalize_range(start, stop, list.len()) {
+        Some((start, stop)) => list.range(start..=stop).cloned().map(Reply::Bulk).collect(),
+        None => Vec::new(),
+    };
+    Ok(Reply::Array(items))
+}
+
+/// LLEN key
+pub(super) fn llen(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_list(db, &parts[1])?.map_or(0, |list| list.len());
+    Ok(Reply::Integer(len as i64))
+}
+
+/// Position réelle d'un index Redis (négatif = depuis la fin)
+fn list_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+/// LINDEX key index
+pub(super) fn lindex(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let index = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let element = get_list(db, &parts[1])?
+        .and_then(|list| list_index(index, list.len()).map(|i| list[i].clone()));
+    Ok(element.map_or(Reply::Nil, Reply::Bulk))
+}
+
+/// LSET key index element
+pub(super) fn lset(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let index = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let list = get_list(db, &parts[1])?.ok_or(CommandError::NoSuchKey)?;
+    let i = list_index(index, list.len()).ok_or(CommandError::IndexOutOfRange)?;
+    list[i] = parts[3].clone();
+    log_command(aof_tx, parts);
+    Ok(Reply::ok())
+}
+
+/// LREM key count element
+///
+/// `count > 0` supprime depuis la tête, `count < 0` depuis la queue, `0` supprime toutes les occurrences.
+pub(super) fn lrem(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let count = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let key = &parts[1];
+    let element = &parts[3];
+    let list = match get_list(db, key)? {
+        Some(list) => list,
+        None => return Ok(Reply::Integer(0)),
+    };
+
+    let limit = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+    let mut removed = 0;
+    let mut kept = VecDeque::with_capacity(list.len());
+    if count >= 0 {
+        for item in list.drain(..) {
+            if removed < limit && item == *element {
+                removed += 1;
+            } else {
+                kept.push_back(item);
+            }
+        }
+    } else {
+        for item in list.drain(..).rev() {
+            if removed < limit && item == *element {
+                removed += 1;
+            } else {
+                kept.push_front(item);
+            }
+        }
+    }
+    *list = kept;
+
+    if removed > 0 {
+        log_command(aof_tx, parts);
+        remove_if_empty(db, key);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// LTRIM key start stop
+pub(super) fn ltrim(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let start = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let stop = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+    let key = &parts[1];
+    let list = match get_list(db, key)? {
+        Some(list) => list,
+        None => return Ok(Reply::ok()),
+    };
+    match normalize_range(start, stop, list.len()) {
+        Some((start, stop)) => {
+            list.truncate(stop + 1);
+            list.drain(..start);
+        },
+        None => list.clear(),
+    }
+    log_command(aof_tx, parts);
+    remove_if_empty(db, key);
+    Ok(Reply::ok())
+}
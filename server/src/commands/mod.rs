@@ -0,0 +1,268 @@
+// src/commands/mod.rs
+//! Exécution des commandes sur l'espace de clés, une famille de commandes par module.
+
+mod bitmaps;
+mod expire;
+mod geo;
+mod hashes;
+mod hyperloglogs;
+mod json;
+mod keys;
+mod lists;
+mod sets;
+mod stream_groups;
+mod streams;
+mod strings;
+mod zsets;
+
+use crate::db::{Databases, Db, Keyspace, DATABASES};
+use crate::error::CommandError;
+use crate::protocol::{format_command, parse_arg, Reply};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Sender};
+
+/// Exécute une commande dans la base `index` (les bases sont déjà verrouillées) et journalise dans
+/// l'AOF les modifications effectuées.
+///
+/// Les lignes journalisées sont précédées de `SELECT index` quand l'AOF est positionné sur une autre base.
+/// Les clés supprimées à l'accès parce qu'elles avaient expiré sont journalisées en premier, sous
+/// forme de `DELETE`. Une commande qui a journalisé quelque chose a modifié ses clés : leur version
+/// est incrémentée pour les connexions qui les surveillent (WATCH). Un XADD réveille en plus les
+/// lectures bloquantes.
+pub(crate) fn process_command_parts(
+    parts: &[Vec<u8>],
+    dbs: &mut Databases,
+    index: usize,
+    aof_tx: &Sender<String>,
+) -> Reply {
+    let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+    let (log_tx, log_rx) = mpsc::channel();
+    let result = match command.as_str() {
+        "COPY" => keys::copy(parts, dbs, index, &log_tx),
+        "MOVE" => keys::move_key(parts, dbs, index, &log_tx),
+        "SWAPDB" => keys::swapdb(parts, dbs, &log_tx),
+        "FLUSHDB" => keys::flushdb(parts, dbs, index, &log_tx),
+        "FLUSHALL" => keys::flushall(parts, dbs, &log_tx),
+        _ => execute(parts, dbs.keyspace_mut(index), &log_tx),
+    };
+    dbs.log_expired(aof_tx);
+    let mut modified = false;
+    for line in log_rx.try_iter() {
+        dbs.log(index, line, aof_tx);
+        modified = true;
+    }
+    if modified {
+        for key in written_keys(&command, parts) {
+            dbs.touch(index, key);
+        }
+        if command == "XADD" {
+            dbs.notify_stream_added();
+        }
+    }
+    result.unwrap_or_else(Reply::from)
+}
+
+/// Clés qu'une commande d'écriture peut modifier dans la base courante
+fn written_keys<'a>(command: &str, parts: &'a [Vec<u8>]) -> Vec<&'a [u8]> {
+    let args = &parts[1..];
+    let keys: Vec<&Vec<u8>> = match command {
+        // Ces commandes signalent elles-mêmes leurs modifications, qui peuvent toucher d'autres bases
+        "COPY" | "MOVE" | "SWAPDB" | "FLUSHDB" | "FLUSHALL" => Vec::new(),
+        "MSET" | "MSETNX" => args.iter().step_by(2).collect(),
+        "RENAME" | "RENAMENX" => args.iter().take(2).collect(),
+        "BITOP" | "XGROUP" => args.iter().skip(1).take(1).collect(),
+        "XREADGROUP" => {
+            let streams = args.iter().position(|arg| arg.eq_ignore_ascii_case(b"STREAMS")).map_or(args.len(), |i| i + 1);
+            let streams = &args[streams..];
+            streams.iter().take(streams.len() / 2).collect()
+        },
+        _ => args.iter().take(1).collect(),
+    };
+    keys.into_iter().map(Vec::as_slice).collect()
+}
+
+/// Commandes qui ne voient que la base sélectionnée
+fn execute(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    match String::from_utf8_lossy(&parts[0]).to_uppercase().as_str() {
+        "SET" | "UPDATE" => strings::set(parts, db, aof_tx),
+        "GET" => strings::get(parts, db),
+        "DELETE" => strings::delete(parts, db, aof_tx),
+        "MGET" => strings::mget(parts, db),
+        "MSET" | "MSETNX" => strings::mset(parts, db, aof_tx),
+        "APPEND" => strings::append(parts, db, aof_tx),
+        "STRLEN" => strings::strlen(parts, db),
+        "GETRANGE" | "SUBSTR" => strings::getrange(parts, db),
+        "SETRANGE" => strings::setrange(parts, db, aof_tx),
+        "GETDEL" => strings::getdel(parts, db, aof_tx),
+        "GETEX" => strings::getex(parts, db, aof_tx),
+        "EXISTS" => keys::exists(parts, db),
+        "TYPE" => keys::key_type(parts, db),
+        "KEYS" => keys::keys(parts, db),
+        "SCAN" => keys::scan(parts, db),
+        "DBSIZE" => keys::dbsize(parts, db),
+        "RANDOMKEY" => keys::randomkey(parts, db),
+        "RENAME" | "RENAMENX" => keys::rename(parts, db, aof_tx),
+        "OBJECT" => keys::object(parts, db),
+        "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" => expire::expire(parts, db, aof_tx),
+        "TTL" | "PTTL" | "EXPIRETIME" | "PEXPIRETIME" => expire::ttl(parts, db),
+        "PERSIST" => expire::persist(parts, db, aof_tx),
+        "INCR" | "DECR" | "INCRBY" | "DECRBY" => strings::incr_by(parts, db, aof_tx),
+        "INCRBYFLOAT" => strings::incr_by_float(parts, db, aof_tx),
+        "SETBIT" => bitmaps::setbit(parts, db, aof_tx),
+        "GETBIT" => bitmaps::getbit(parts, db),
+        "BITCOUNT" => bitmaps::bitcount(parts, db),
+        "BITPOS" => bitmaps::bitpos(parts, db),
+        "BITOP" => bitmaps::bitop(parts, db, aof_tx),
+        "BITFIELD" | "BITFIELD_RO" => bitmaps::bitfield(parts, db, aof_tx),
+        "LPUSH" | "RPUSH" => lists::push(parts, db, aof_tx),
+        "LPOP" | "RPOP" => lists::pop(parts, db, aof_tx),
+        "LRANGE" => lists::lrange(parts, db),
+        "LLEN" => lists::llen(parts, db),
+        "LINDEX" => lists::lindex(parts, db),
+        "LSET" => lists::lset(parts, db, aof_tx),
+        "LREM" => lists::lrem(parts, db, aof_tx),
+        "LTRIM" => lists::ltrim(parts, db, aof_tx),
+        "HSET" | "HMSET" => hashes::hset(parts, db, aof_tx),
+        "HSETNX" => hashes::hsetnx(parts, db, aof_tx),
+        "HGET" => hashes::hget(parts, db),
+        "HMGET" => hashes::hmget(parts, db),
+        "HDEL" => hashes::hdel(parts, db, aof_tx),
+        "HGETALL" | "HKEYS" | "HVALS" => hashes::hgetall(parts, db),
+        "HINCRBY" => hashes::hincrby(parts, db, aof_tx),
+        "HINCRBYFLOAT" => hashes::hincrbyfloat(parts, db, aof_tx),
+        "HEXISTS" => hashes::hexists(parts, db),
+        "HLEN" => hashes::hlen(parts, db),
+        "HSTRLEN" => hashes::hstrlen(parts, db),
+        "SADD" => sets::sadd(parts, db, aof_tx),
+        "SREM" => sets::srem(parts, db, aof_tx),
+        "SMEMBERS" => sets::smembers(parts, db),
+        "SISMEMBER" => sets::sismember(parts, db),
+        "SMISMEMBER" => sets::smismember(parts, db),
+        "SCARD" => sets::scard(parts, db),
+        "SINTER" | "SUNION" | "SDIFF" => sets::combine(parts, db),
+        "SINTERSTORE" | "SUNIONSTORE" | "SDIFFSTORE" => sets::combine_store(parts, db, aof_tx),
+        "ZADD" => zsets::zadd(parts, db, aof_tx),
+        "ZINCRBY" => zsets::zincrby(parts, db, aof_tx),
+        "ZREM" => zsets::zrem(parts, db, aof_tx),
+        "ZCARD" => zsets::zcard(parts, db),
+        "ZSCORE" => zsets::zscore(parts, db),
+        "ZCOUNT" => zsets::zcount(parts, db),
+        "ZRANK" | "ZREVRANK" => zsets::zrank(parts, db),
+        "ZRANGE" | "ZREVRANGE" | "ZRANGEBYSCORE" | "ZREVRANGEBYSCORE" => zsets::zrange(parts, db),
+        "GEOADD" => geo::geoadd(parts, db, aof_tx),
+        "GEODIST" => geo::geodist(parts, db),
+        "GEOPOS" => geo::geopos(parts, db),
+        "GEOHASH" => geo::geohash(parts, db),
+        "GEOSEARCH" => geo::geosearch(parts, db),
+        "PFADD" => hyperloglogs::pfadd(parts, db, aof_tx),
+        "PFCOUNT" => hyperloglogs::pfcount(parts, db),
+        "PFMERGE" => hyperloglogs::pfmerge(parts, db, aof_tx),
+        "JSON.SET" => json::json_set(parts, db, aof_tx),
+        "JSON.GET" => json::json_get(parts, db),
+        "JSON.DEL" | "JSON.FORGET" => json::json_del(parts, db, aof_tx),
+        "JSON.NUMINCRBY" => json::json_numincrby(parts, db, aof_tx),
+        "JSON.ARRAPPEND" => json::json_arrappend(parts, db, aof_tx),
+        "XADD" => streams::xadd(parts, db, aof_tx),
+        "XTRIM" => streams::xtrim(parts, db, aof_tx),
+        "XDEL" => streams::xdel(parts, db, aof_tx),
+        "XLEN" => streams::xlen(parts, db),
+        "XRANGE" | "XREVRANGE" => streams::xrange(parts, db),
+        "XREAD" => streams::xread(parts, db),
+        "XGROUP" => stream_groups::xgroup(parts, db, aof_tx),
+        "XREADGROUP" => stream_groups::xreadgroup(parts, db, aof_tx),
+        "XACK" => stream_groups::xack(parts, db, aof_tx),
+        "XPENDING" => stream_groups::xpending(parts, db),
+        "XCLAIM" => stream_groups::xclaim(parts, db, aof_tx),
+        "QUIT" => Ok(Reply::Simple("BYE".to_string())),
+        _ => Err(CommandError::UnknownCommand),
+    }
+}
+
+/// Vérifie, sans l'exécuter, qu'une commande existe et reçoit un nombre d'arguments acceptable
+/// (commandes mises en file par MULTI).
+///
+/// Les autres erreurs (syntaxe des options, type des valeurs...) n'apparaissent qu'à l'exécution.
+pub(crate) fn check_command(parts: &[Vec<u8>]) -> Result<(), CommandError> {
+    let arity = arity(&String::from_utf8_lossy(&parts[0]).to_uppercase()).ok_or(CommandError::UnknownCommand)?;
+    let len = parts.len() as i32;
+    if (arity >= 0 && len != arity) || len < arity.abs() {
+        return Err(wrong_arity(parts));
+    }
+    Ok(())
+}
+
+/// Nombre d'arguments (nom de la commande compris) selon la convention de Redis : `n` exactement,
+/// ou au moins `-n`. Quand l'implémentation accepte plus de formes que Redis, c'est elle qui compte.
+fn arity(command: &str) -> Option<i32> {
+    Some(match command {
+        "DBSIZE" | "RANDOMKEY" | "MULTI" | "EXEC" | "DISCARD" | "UNWATCH" => 1,
+        "FLUSHDB" | "FLUSHALL" | "HELLO" | "QUIT" => -1,
+        "STRLEN" | "GETDEL" | "TYPE" | "KEYS" | "TTL" | "PTTL" | "EXPIRETIME" | "PEXPIRETIME" | "PERSIST"
+        | "INCR" | "DECR" | "LLEN" | "HGETALL" | "HKEYS" | "HVALS" | "HLEN" | "SMEMBERS" | "SCARD" | "ZCARD"
+        | "XLEN" | "SELECT" => 2,
+        "GET" | "DELETE" | "MGET" | "GETEX" | "EXISTS" | "SCAN" | "OBJECT" | "BITCOUNT" | "BITFIELD"
+        | "BITFIELD_RO" | "LPOP" | "RPOP" | "SINTER" | "SUNION" | "SDIFF" | "GEOPOS" | "GEOHASH" | "PFADD"
+        | "PFCOUNT" | "PFMERGE" | "JSON.GET" | "JSON.DEL" | "JSON.FORGET" | "XGROUP" | "WATCH" => -2,
+        "APPEND" | "RENAME" | "RENAMENX" | "MOVE" | "SWAPDB" | "INCRBY" | "DECRBY" | "INCRBYFLOAT" | "GETBIT"
+        | "LINDEX" | "HGET" | "HEXISTS" | "HSTRLEN" | "SISMEMBER" | "ZSCORE" => 3,
+        "SET" | "UPDATE" | "MSET" | "MSETNX" | "COPY" | "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT"
+        | "BITPOS" | "LPUSH" | "RPUSH" | "HMGET" | "HDEL" | "SADD" | "SREM" | "SMISMEMBER" | "SINTERSTORE"
+        | "SUNIONSTORE" | "SDIFFSTORE" | "ZREM" | "ZRANK" | "ZREVRANK" | "XDEL" | "XPENDING" => -3,
+        "GETRANGE" | "SUBSTR" | "SETRANGE" | "SETBIT" | "LRANGE" | "LSET" | "LREM" | "LTRIM" | "HSETNX"
+        | "HINCRBY" | "HINCRBYFLOAT" | "ZINCRBY" | "ZCOUNT" | "JSON.NUMINCRBY" => 4,
+        "BITOP" | "HSET" | "HMSET" | "ZADD" | "ZRANGE" | "ZREVRANGE" | "ZRANGEBYSCORE" | "ZREVRANGEBYSCORE"
+        | "GEODIST" | "JSON.SET" | "JSON.ARRAPPEND" | "XTRIM" | "XRANGE" | "XREVRANGE" | "XREAD" | "XACK" => -4,
+        "GEOADD" | "XADD" => -5,
+        "XCLAIM" => -6,
+        "GEOSEARCH" | "XREADGROUP" => -7,
+        _ => return None,
+    })
+}
+
+/// Exécute une commande bloquante (XREAD ou XREADGROUP avec BLOCK), en attendant les XADD sans garder
+/// la base verrouillée. `peer` est la connexion du client : l'attente cesse s'il se déconnecte.
+///
+/// Renvoie `None` si la commande ne bloque pas : elle passe alors par `process_command_parts`.
+/// Dans une transaction, les commandes ne bloquent jamais et ne passent pas par ici.
+pub(crate) fn process_blocking_command(
+    parts: &[Vec<u8>],
+    db: &Db,
+    index: usize,
+    aof_tx: &Sender<String>,
+    peer: &TcpStream,
+) -> Option<Reply> {
+    match String::from_utf8_lossy(&parts[0]).to_uppercase().as_str() {
+        "XREAD" | "XREADGROUP" => streams::read_blocking(parts, db, index, aof_tx, peer),
+        _ => None,
+    }
+}
+
+/// Numéro de base valide (SELECT, MOVE, COPY ... DB, SWAPDB)
+pub(crate) fn parse_db_index(arg: &[u8]) -> Result<usize, CommandError> {
+    let index = parse_arg::<i64>(arg).ok_or(CommandError::NotInteger)?;
+    usize::try_from(index).ok().filter(|index| *index < DATABASES).ok_or(CommandError::DbIndexOutOfRange)
+}
+
+/// Erreur d'arité portant le nom de la commande reçue
+fn wrong_arity(parts: &[Vec<u8>]) -> CommandError {
+    CommandError::WrongArity(String::from_utf8_lossy(&parts[0]).to_lowercase())
+}
+
+/// Journalise la commande telle qu'elle a été reçue, lorsqu'elle est déjà déterministe
+fn log_command(aof_tx: &Sender<String>, parts: &[Vec<u8>]) {
+    let args: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+    aof_tx.send(format_command(&args)).unwrap();
+}
+
+/// Convertit des bornes Redis inclusives (négatives = depuis la fin) en indices valides pour `len` éléments.
+///
+/// Renvoie `None` si l'intervalle est vide.
+fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
@@ -0,0 +1,149 @@
+// src/commands/sets.rs
+use super::{log_command, wrong_arity};
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::Reply;
+use std::collections::HashSet;
+use std::sync::mpsc::Sender;
+
+type Set = HashSet<Vec<u8>>;
+
+/// Ensemble stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+fn get_set<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut Set>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::Set(set), .. }) => Ok(Some(set)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+fn to_reply(members: impl IntoIterator<Item = Vec<u8>>) -> Reply {
+    Reply::Set(members.into_iter().map(Reply::Bulk).collect())
+}
+
+/// SADD key member [member ...]
+pub(super) fn sadd(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let set = match &mut get_or_insert(db, &parts[1], || Value::Set(HashSet::new())).value {
+        Value::Set(set) => set,
+        _ => return Err(CommandError::WrongType),
+    };
+    let added = parts[2..].iter().filter(|member| set.insert(member.to_vec())).count();
+    if added > 0 {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(added as i64))
+}
+
+/// SREM key member [member ...]
+pub(super) fn srem(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let set = match get_set(db, key)? {
+        Some(set) => set,
+        None => return Ok(Reply::Integer(0)),
+    };
+    let removed = parts[2..].iter().filter(|member| set.remove(*member)).count();
+    let now_empty = set.is_empty();
+    if removed > 0 {
+        log_command(aof_tx, parts);
+    }
+    if now_empty {
+        db.remove(key);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// SMEMBERS key
+pub(super) fn smembers(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let members = get_set(db, &parts[1])?.map(|set| set.clone()).unwrap_or_default();
+    Ok(to_reply(members))
+}
+
+/// SISMEMBER key member
+pub(super) fn sismember(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let is_member = get_set(db, &parts[1])?.is_some_and(|set| set.contains(&parts[2]));
+    Ok(Reply::Integer(is_member as i64))
+}
+
+/// SMISMEMBER key member [member ...]
+pub(super) fn smismember(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let set = get_set(db, &parts[1])?;
+    let flags = parts[2..]
+        .iter()
+        .map(|member| Reply::Integer(set.as_ref().is_some_and(|set| set.contains(member)) as i64))
+        .collect();
+    Ok(Reply::Array(flags))
+}
+
+/// SCARD key
+pub(super) fn scard(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_set(db, &parts[1])?.map_or(0, |set| set.len());
+    Ok(Reply::Integer(len as i64))
+}
+
+/// Calcule l'intersection, l'union ou la différence (selon `op` : "INTER", "UNION", "DIFF") des ensembles `keys`.
+///
+/// Une clé absente compte comme un ensemble vide ; toutes les clés sont vérifiées pour `WRONGTYPE`.
+fn compute(op: &str, keys: &[Vec<u8>], db: &mut Keyspace) -> Result<Set, CommandError> {
+    let mut sets = Vec::with_capacity(keys.len());
+    for key in keys {
+        sets.push(get_set(db, key)?.map(|set| set.clone()).unwrap_or_default());
+    }
+    let mut sets = sets.into_iter();
+    let first = sets.next().unwrap_or_default();
+    Ok(match op {
+        "INTER" => sets.fold(first, |acc, set| acc.into_iter().filter(|m| set.contains(m)).collect()),
+        "UNION" => sets.fold(first, |mut acc, set| {
+            acc.extend(set);
+            acc
+        }),
+        _ => sets.fold(first, |acc, set| acc.into_iter().filter(|m| !set.contains(m)).collect()),
+    })
+}
+
+/// SINTER key [key ...] | SUNION key [key ...] | SDIFF key [key ...]
+pub(super) fn combine(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+    Ok(to_reply(compute(&command[1..], &parts[1..], db)?))
+}
+
+/// SINTERSTORE destination key [key ...] | SUNIONSTORE ... | SDIFFSTORE ...
+///
+/// La destination est remplacée quel que soit son type, et supprimée si le résultat est vide.
+pub(super) fn combine_store(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+    let op = &command[1..command.len() - "STORE".len()];
+    let result = compute(op, &parts[2..], db)?;
+    let len = result.len();
+    let destination = parts[1].clone();
+    if result.is_empty() {
+        db.remove(&destination);
+    } else {
+        db.insert(destination, Entry::new(Value::Set(result)));
+    }
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(len as i64))
+}
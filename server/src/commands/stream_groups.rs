@@ -0,0 +1,408 @@
+// src/commands/stream_groups.rs
+//! Groupes de consommateurs des streams.
+//!
+//! Les livraisons dépendent de l'horloge : elles sont journalisées sous la forme de XCLAIM explicites
+//! (`XCLAIM key group consumer 0 id TIME ms RETRYCOUNT n FORCE JUSTID`), comme le fait Redis,
+//! pour que le rejeu de l'AOF reconstruise exactement les entrées en attente.
+
+use super::streams::{entry_reply, get_stream, now_ms, parse_bound, parse_id, parse_read_options};
+use super::{log_command, wrong_arity};
+use crate::db::{get_or_insert, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, parse_arg, Reply};
+use crate::stream::{ConsumerGroup, Stream, StreamId};
+use std::collections::BTreeMap;
+use std::sync::mpsc::Sender;
+
+type Entries = BTreeMap<StreamId, Vec<Vec<u8>>>;
+
+fn no_group(key: &[u8], group: &[u8]) -> CommandError {
+    CommandError::NoGroup(String::from_utf8_lossy(key).into_owned(), String::from_utf8_lossy(group).into_owned())
+}
+
+/// Entrées du stream `key` et son groupe `group`, ou `NOGROUP` si l'un des deux n'existe pas
+fn get_group<'a>(db: &'a mut Keyspace, key: &[u8], group: &[u8]) -> Result<(&'a Entries, &'a mut ConsumerGroup), CommandError> {
+    let Stream { entries, groups, .. } = get_stream(db, key)?.ok_or_else(|| no_group(key, group))?;
+    let consumer_group = groups.get_mut(group).ok_or_else(|| no_group(key, group))?;
+    Ok((entries, consumer_group))
+}
+
+/// Journalise l'attribution de `id` à `consumer` avec son heure de livraison et son compteur
+fn log_claim(aof_tx: &Sender<String>, key: &[u8], group: &[u8], consumer: &[u8], id: StreamId, time: u64, count: u64) {
+    let (id, time, count) = (id.to_string(), time.to_string(), count.to_string());
+    aof_tx
+        .send(format_command(&[
+            b"XCLAIM",
+            key,
+            group,
+            consumer,
+            b"0",
+            id.as_bytes(),
+            b"TIME",
+            time.as_bytes(),
+            b"RETRYCOUNT",
+            count.as_bytes(),
+            b"FORCE",
+            b"JUSTID",
+        ]))
+        .unwrap();
+}
+
+fn log_setid(aof_tx: &Sender<String>, key: &[u8], group: &[u8], id: StreamId) {
+    aof_tx.send(format_command(&[b"XGROUP", b"SETID", key, group, id.to_string().as_bytes()])).unwrap();
+}
+
+/// Marque `consumer` comme vu à `now`, en le créant au besoin : une création est journalisée sous la
+/// forme `XGROUP CREATECONSUMER`, même si la lecture qui l'a provoquée ne délivre rien
+fn touch_consumer(aof_tx: &Sender<String>, key: &[u8], group_name: &[u8], group: &mut ConsumerGroup, consumer: &[u8], now: u64) {
+    if !group.consumers.contains_key(consumer) {
+        aof_tx.send(format_command(&[b"XGROUP", b"CREATECONSUMER", key, group_name, consumer])).unwrap();
+    }
+    group.consumer(consumer).seen_at = now;
+}
+
+/// Identifiant de XGROUP CREATE / SETID : `$` désigne le dernier identifiant du stream
+fn group_id(arg: &[u8], stream: Option<&Stream>) -> Result<StreamId, CommandError> {
+    match arg {
+        b"$" => Ok(stream.map_or(StreamId::MIN, |stream| stream.last_id)),
+        _ => parse_id(arg, 0),
+    }
+}
+
+/// XGROUP CREATE key group <id | $> [MKSTREAM] | SETID key group <id | $> | DESTROY key group
+/// | CREATECONSUMER key group consumer | DELCONSUMER key group consumer
+pub(super) fn xgroup(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let subcommand = String::from_utf8_lossy(&parts[1]).to_uppercase();
+    let arity_ok = match subcommand.as_str() {
+        "CREATE" | "SETID" => parts.len() >= 5,
+        "DESTROY" => parts.len() == 4,
+        "CREATECONSUMER" | "DELCONSUMER" => parts.len() == 5,
+        _ => {
+            let subcommand = String::from_utf8_lossy(&parts[1]).into_owned();
+            return Err(CommandError::UnknownSubcommand(subcommand, "XGROUP".to_string()));
+        },
+    };
+    if !arity_ok {
+        return Err(CommandError::WrongArity(format!("xgroup|{}", subcommand.to_lowercase())));
+    }
+    let (key, group) = (&parts[2], &parts[3]);
+
+    match subcommand.as_str() {
+        "CREATE" => {
+            let mut mkstream = false;
+            let mut i = 5;
+            while i < parts.len() {
+                match parts[i].to_ascii_uppercase().as_slice() {
+                    b"MKSTREAM" => mkstream = true,
+                    b"ENTRIESREAD" if i + 1 < parts.len() => {
+                        parse_arg::<i64>(&parts[i + 1]).ok_or(CommandError::NotInteger)?;
+                        i += 1;
+                    },
+                    _ => return Err(CommandError::Syntax),
+                }
+                i += 1;
+            }
+            let stream = get_stream(db, key)?;
+            if stream.is_none() && !mkstream {
+                return Err(CommandError::XGroupKeyMissing);
+            }
+            let id = group_id(&parts[4], stream.as_deref())?;
+            if stream.is_some_and(|stream| stream.groups.contains_key(group)) {
+                return Err(CommandError::BusyGroup);
+            }
+            let stream = match &mut get_or_insert(db, key, || Value::Stream(Stream::default())).value {
+                Value::Stream(stream) => stream,
+                _ => return Err(CommandError::WrongType),
+            };
+            stream.groups.insert(group.clone(), ConsumerGroup::new(id));
+            aof_tx
+                .send(format_command(&[b"XGROUP", b"CREATE", key, group, id.to_string().as_bytes(), b"MKSTREAM"]))
+                .unwrap();
+            Ok(Reply::ok())
+        },
+        "SETID" => {
+            let stream = get_stream(db, key)?.ok_or(CommandError::XGroupKeyMissing)?;
+            let id = group_id(&parts[4], Some(stream))?;
+            stream.groups.get_mut(group).ok_or_else(|| no_group(key, group))?.last_delivered = id;
+            log_setid(aof_tx, key, group, id);
+            Ok(Reply::ok())
+        },
+        "DESTROY" => {
+            let stream = get_stream(db, key)?.ok_or(CommandError::XGroupKeyMissing)?;
+            let destroyed = stream.groups.remove(group.as_slice()).is_some();
+            if destroyed {
+                log_command(aof_tx, parts);
+            }
+            Ok(Reply::Integer(destroyed as i64))
+        },
+        "CREATECONSUMER" => {
+            get_stream(db, key)?.ok_or(CommandError::XGroupKeyMissing)?;
+            let (_, consumer_group) = get_group(db, key, group)?;
+            let consumer = &parts[4];
+            if consumer_group.consumers.contains_key(consumer) {
+                return Ok(Reply::Integer(0));
+            }
+            consumer_group.consumer(consumer).seen_at = now_ms();
+            log_command(aof_tx, parts);
+            Ok(Reply::Integer(1))
+        },
+        _ => {
+            get_stream(db, key)?.ok_or(CommandError::XGroupKeyMissing)?;
+            let (_, consumer_group) = get_group(db, key, group)?;
+            match consumer_group.remove_consumer(&parts[4]) {
+                Some(pending) => {
+                    log_command(aof_tx, parts);
+                    Ok(Reply::Integer(pending as i64))
+                },
+                None => Ok(Reply::Integer(0)),
+            }
+        },
+    }
+}
+
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]
+///
+/// Avec `>`, délivre les entrées jamais délivrées au groupe et les ajoute aux entrées en attente du
+/// consommateur. Avec un identifiant, relit l'historique des entrées en attente du consommateur.
+pub(super) fn xreadgroup(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 7 {
+        return Err(wrong_arity(parts));
+    }
+    if !parts[1].eq_ignore_ascii_case(b"GROUP") {
+        return Err(CommandError::Syntax);
+    }
+    let (group_name, consumer) = (&parts[2], &parts[3]);
+    let options = parse_read_options(parts, 4)?;
+
+    // Tout est vérifié avant de délivrer quoi que ce soit
+    for (key, id) in options.keys.iter().zip(options.ids) {
+        match id.as_slice() {
+            b"$" => return Err(CommandError::XReadGroupDollar),
+            b">" => {},
+            _ => {
+                parse_id(id, 0)?;
+            },
+        }
+        get_group(db, key, group_name)?;
+    }
+
+    let now = now_ms();
+    let mut results = Vec::new();
+    for (key, id) in options.keys.iter().zip(options.ids) {
+        let (entries, group) = get_group(db, key, group_name)?;
+        touch_consumer(aof_tx, key, group_name, group, consumer, now);
+
+        if id == b">" {
+            let delivered: Vec<(StreamId, &Vec<Vec<u8>>)> = match group.last_delivered.next() {
+                Some(start) => entries.range(start..).take(options.count).map(|(id, fields)| (*id, fields)).collect(),
+                None => Vec::new(),
+            };
+            let Some(&(last, _)) = delivered.last() else { continue };
+            group.last_delivered = last;
+            if !options.noack {
+                for (id, _) in &delivered {
+                    group.deliver(*id, consumer, now, 1);
+                    log_claim(aof_tx, key, group_name, consumer, *id, now, 1);
+                }
+            }
+            log_setid(aof_tx, key, group_name, last);
+            let replies = delivered.iter().map(|(id, fields)| entry_reply(id, fields)).collect();
+            results.push(Reply::Array(vec![Reply::Bulk(key.clone()), Reply::Array(replies)]));
+        } else {
+            let after = parse_id(id, 0)?;
+            let history: Vec<StreamId> = match (group.consumers.get(consumer.as_slice()), after.next()) {
+                (Some(owner), Some(start)) => owner.pending.range(start..).take(options.count).copied().collect(),
+                _ => Vec::new(),
+            };
+            let mut replies = Vec::with_capacity(history.len());
+            for id in history {
+                match entries.get(&id) {
+                    Some(fields) => {
+                        let count = group.pending.get(&id).map_or(0, |entry| entry.delivery_count) + 1;
+                        group.deliver(id, consumer, now, count);
+                        log_claim(aof_tx, key, group_name, consumer, id, now, count);
+                        replies.push(entry_reply(&id, fields));
+                    },
+                    // Entrée supprimée du stream depuis sa livraison
+                    None => replies.push(Reply::Array(vec![Reply::Bulk(id.to_string().into_bytes()), Reply::NilArray])),
+                }
+            }
+            results.push(Reply::Array(vec![Reply::Bulk(key.clone()), Reply::Array(replies)]));
+        }
+    }
+    Ok(if results.is_empty() { Reply::NilArray } else { Reply::Array(results) })
+}
+
+/// XACK key group id [id ...]
+pub(super) fn xack(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let ids = parts[3..].iter().map(|arg| parse_id(arg, 0)).collect::<Result<Vec<_>, _>>()?;
+    let acked = match get_group(db, &parts[1], &parts[2]) {
+        Ok((_, group)) => ids.into_iter().filter(|id| group.ack(*id)).count(),
+        Err(CommandError::NoGroup(..)) => 0,
+        Err(e) => return Err(e),
+    };
+    if acked > 0 {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(acked as i64))
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+///
+/// Sans intervalle, renvoie le résumé : nombre d'entrées en attente, plus petit et plus grand
+/// identifiant, et nombre d'entrées par consommateur.
+pub(super) fn xpending(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let (key, group_name) = (&parts[1], &parts[2]);
+    if parts.len() == 3 {
+        let (_, group) = get_group(db, key, group_name)?;
+        let (Some((first, _)), Some((last, _))) = (group.pending.first_key_value(), group.pending.last_key_value()) else {
+            return Ok(Reply::Array(vec![Reply::Integer(0), Reply::Nil, Reply::Nil, Reply::NilArray]));
+        };
+        let mut consumers: Vec<(&Vec<u8>, usize)> = group
+            .consumers
+            .iter()
+            .filter(|(_, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| (name, consumer.pending.len()))
+            .collect();
+        consumers.sort();
+        return Ok(Reply::Array(vec![
+            Reply::Integer(group.pending.len() as i64),
+            Reply::Bulk(first.to_string().into_bytes()),
+            Reply::Bulk(last.to_string().into_bytes()),
+            Reply::Array(
+                consumers
+                    .into_iter()
+                    .map(|(name, count)| Reply::Array(vec![Reply::Bulk(name.clone()), Reply::Bulk(count.to_string().into_bytes())]))
+                    .collect(),
+            ),
+        ]));
+    }
+
+    let mut i = 3;
+    let mut min_idle = 0;
+    if parts[i].eq_ignore_ascii_case(b"IDLE") {
+        let idle = parse_arg::<i64>(parts.get(i + 1).ok_or(CommandError::Syntax)?).ok_or(CommandError::NotInteger)?;
+        min_idle = u64::try_from(idle).unwrap_or(0);
+        i += 2;
+    }
+    if parts.len() != i + 3 && parts.len() != i + 4 {
+        return Err(CommandError::Syntax);
+    }
+    let start = parse_bound(&parts[i], true)?;
+    let end = parse_bound(&parts[i + 1], false)?;
+    let count = parse_arg::<i64>(&parts[i + 2]).ok_or(CommandError::NotInteger)?;
+    let count = usize::try_from(count).unwrap_or(0);
+    let consumer = parts.get(i + 3);
+
+    let (_, group) = get_group(db, key, group_name)?;
+    let now = now_ms();
+    let entries = match (start, end) {
+        (Some(start), Some(end)) if start <= end => group
+            .pending
+            .range(start..=end)
+            .filter(|(_, entry)| consumer.is_none_or(|consumer| entry.consumer == *consumer))
+            .map(|(id, entry)| (id, entry, now.saturating_sub(entry.delivered_at)))
+            .filter(|(_, _, idle)| *idle >= min_idle)
+            .take(count)
+            .map(|(id, entry, idle)| {
+                Reply::Array(vec![
+                    Reply::Bulk(id.to_string().into_bytes()),
+                    Reply::Bulk(entry.consumer.clone()),
+                    Reply::Integer(idle as i64),
+                    Reply::Integer(entry.delivery_count as i64),
+                ])
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(Reply::Array(entries))
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+/// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+///
+/// Transfère à `consumer` les entrées en attente inactives depuis au moins `min-idle-time` ms.
+pub(super) fn xclaim(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 6 {
+        return Err(wrong_arity(parts));
+    }
+    let (key, group_name, consumer) = (&parts[1], &parts[2], &parts[3]);
+    let min_idle = parse_arg::<i64>(&parts[4]).ok_or(CommandError::NotInteger)?;
+    let min_idle = u64::try_from(min_idle).unwrap_or(0);
+
+    let mut ids = Vec::new();
+    let mut i = 5;
+    while let Some(id) = parts.get(i).and_then(|arg| parse_id(arg, 0).ok()) {
+        ids.push(id);
+        i += 1;
+    }
+    if ids.is_empty() {
+        return Err(CommandError::InvalidStreamId);
+    }
+    let now = now_ms();
+    let (mut delivered_at, mut retry_count, mut force, mut just_id, mut last_id) = (now, None, false, false, None);
+    while i < parts.len() {
+        let option = parts[i].to_ascii_uppercase();
+        match option.as_slice() {
+            b"FORCE" => force = true,
+            b"JUSTID" => just_id = true,
+            b"IDLE" | b"TIME" | b"RETRYCOUNT" | b"LASTID" => {
+                let value = parts.get(i + 1).ok_or(CommandError::Syntax)?;
+                match option.as_slice() {
+                    b"LASTID" => last_id = Some(parse_id(value, 0)?),
+                    _ => {
+                        let n = parse_arg::<i64>(value).ok_or(CommandError::NotInteger)?;
+                        let n = u64::try_from(n).unwrap_or(0);
+                        match option.as_slice() {
+                            b"IDLE" => delivered_at = now.saturating_sub(n),
+                            b"TIME" => delivered_at = n,
+                            _ => retry_count = Some(n),
+                        }
+                    },
+                }
+                i += 1;
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+    }
+
+    let (entries, group) = get_group(db, key, group_name)?;
+    if let Some(last_id) = last_id.filter(|id| *id > group.last_delivered) {
+        group.last_delivered = last_id;
+        log_setid(aof_tx, key, group_name, last_id);
+    }
+    touch_consumer(aof_tx, key, group_name, group, consumer, now);
+
+    let mut claimed = Vec::new();
+    for id in ids {
+        let pending = group.pending.get(&id);
+        if pending.is_none() && !force {
+            continue;
+        }
+        let Some(fields) = entries.get(&id) else {
+            // L'entrée a été supprimée du stream : elle n'a plus lieu d'être en attente
+            if group.ack(id) {
+                aof_tx.send(format_command(&[b"XACK", key, group_name, id.to_string().as_bytes()])).unwrap();
+            }
+            continue;
+        };
+        if pending.is_some_and(|entry| now.saturating_sub(entry.delivered_at) < min_idle) {
+            continue;
+        }
+        let count = retry_count.unwrap_or_else(|| pending.map_or(0, |entry| entry.delivery_count) + !just_id as u64);
+        group.deliver(id, consumer, delivered_at, count);
+        log_claim(aof_tx, key, group_name, consumer, id, delivered_at, count);
+        claimed.push(if just_id { Reply::Bulk(id.to_string().into_bytes()) } else { entry_reply(&id, fields) });
+    }
+    Ok(Reply::Array(claimed))
+}
@@ -0,0 +1,399 @@
+// src/commands/streams.rs
+use super::{log_command, process_command_parts, wrong_arity};
+use crate::db::{get_live, get_or_insert, Db, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, parse_arg, Reply};
+use crate::stream::{Stream, StreamId};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Intervalle maximal entre deux vérifications de la connexion pendant un XREAD bloquant
+const PEER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stream stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+pub(super) fn get_stream<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut Stream>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::Stream(stream), .. }) => Ok(Some(stream)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+/// Identifiant complet ou abrégé (`ms`, la séquence valant alors `default_seq`)
+pub(super) fn parse_id(arg: &[u8], default_seq: u64) -> Result<StreamId, CommandError> {
+    let s = std::str::from_utf8(arg).map_err(|_| CommandError::InvalidStreamId)?;
+    match s.split_once('-') {
+        Some(_) => s.parse().map_err(|_| CommandError::InvalidStreamId),
+        None => s.parse().map(|ms| StreamId::new(ms, default_seq)).map_err(|_| CommandError::InvalidStreamId),
+    }
+}
+
+/// Borne de XRANGE : `-`, `+`, identifiant éventuellement abrégé, `(` pour une borne exclusive.
+///
+/// Renvoie `None` quand une borne exclusive ne laisse aucun identifiant possible.
+pub(super) fn parse_bound(arg: &[u8], start: bool) -> Result<Option<StreamId>, CommandError> {
+    match arg {
+        b"-" => Ok(Some(StreamId::MIN)),
+        b"+" => Ok(Some(StreamId::MAX)),
+        _ => {
+            let (exclusive, arg) = match arg.strip_prefix(b"(") {
+                Some(rest) => (true, rest),
+                None => (false, arg),
+            };
+            let id = parse_id(arg, if start { 0 } else { u64::MAX })?;
+            Ok(match (exclusive, start) {
+                (false, _) => Some(id),
+                (true, true) => id.next(),
+                (true, false) => id.prev(),
+            })
+        },
+    }
+}
+
+/// Option de réduction commune à XADD et XTRIM : `MAXLEN|MINID [=|~] seuil [LIMIT n]`
+enum Trim {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+/// Lit une option de réduction à partir de `parts[*i]` (qui vaut MAXLEN ou MINID) et avance `i`
+fn parse_trim(parts: &[Vec<u8>], i: &mut usize) -> Result<Trim, CommandError> {
+    let strategy = parts[*i].to_ascii_uppercase();
+    *i += 1;
+    if matches!(parts.get(*i).map(Vec::as_slice), Some(b"=") | Some(b"~")) {
+        *i += 1;
+    }
+    let threshold = parts.get(*i).ok_or(CommandError::Syntax)?;
+    *i += 1;
+    if parts.get(*i).is_some_and(|arg| arg.eq_ignore_ascii_case(b"LIMIT")) {
+        // Le découpage se fait toujours exactement : la limite n'a pas d'effet
+        parse_arg::<u64>(parts.get(*i + 1).ok_or(CommandError::Syntax)?).ok_or(CommandError::NotInteger)?;
+        *i += 2;
+    }
+    if strategy == b"MAXLEN" {
+        let max_len = parse_arg::<i64>(threshold).ok_or(CommandError::NotInteger)?;
+        let max_len = usize::try_from(max_len).map_err(|_| CommandError::OutOfRange)?;
+        Ok(Trim::MaxLen(max_len))
+    } else {
+        Ok(Trim::MinId(parse_id(threshold, 0)?))
+    }
+}
+
+fn apply_trim(stream: &mut Stream, trim: &Trim) -> usize {
+    match trim {
+        Trim::MaxLen(max_len) => stream.trim_max_len(*max_len),
+        Trim::MinId(min_id) => stream.trim_min_id(*min_id),
+    }
+}
+
+pub(super) fn entry_reply(id: &StreamId, fields: &[Vec<u8>]) -> Reply {
+    Reply::Array(vec![
+        Reply::Bulk(id.to_string().into_bytes()),
+        Reply::Array(fields.iter().cloned().map(Reply::Bulk).collect()),
+    ])
+}
+
+pub(super) fn now_ms() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] <* | id> field value [field value ...]
+///
+/// L'identifiant effectivement attribué remplace `*` dans la commande journalisée.
+pub(super) fn xadd(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 5 {
+        return Err(wrong_arity(parts));
+    }
+    let mut nomkstream = false;
+    let mut trim = None;
+    let mut i = 2;
+    loop {
+        match parts[i].to_ascii_uppercase().as_slice() {
+            b"NOMKSTREAM" => {
+                nomkstream = true;
+                i += 1;
+            },
+            b"MAXLEN" | b"MINID" => trim = Some(parse_trim(parts, &mut i)?),
+            _ => break,
+        }
+        if i >= parts.len() {
+            return Err(CommandError::Syntax);
+        }
+    }
+    let fields = &parts[i + 1..];
+    if fields.is_empty() || !fields.len().is_multiple_of(2) {
+        return Err(wrong_arity(parts));
+    }
+
+    let key = &parts[1];
+    let (last, next_auto) = match get_stream(db, key)? {
+        Some(stream) => (stream.last_id, stream.next_id(now_ms())),
+        None if nomkstream => return Ok(Reply::Nil),
+        None => (StreamId::MIN, Stream::default().next_id(now_ms())),
+    };
+    let id = match parts[i].as_slice() {
+        b"*" => next_auto.ok_or(CommandError::StreamIdTooSmall)?,
+        arg => match arg.strip_suffix(b"-*") {
+            Some(ms) => {
+                let ms = parse_arg::<u64>(ms).ok_or(CommandError::InvalidStreamId)?;
+                match ms.cmp(&last.ms) {
+                    std::cmp::Ordering::Greater => StreamId::new(ms, 0),
+                    std::cmp::Ordering::Equal => last.next().filter(|id| id.ms == ms).ok_or(CommandError::StreamIdTooSmall)?,
+                    std::cmp::Ordering::Less => return Err(CommandError::StreamIdTooSmall),
+                }
+            },
+            None => parse_id(arg, 0)?,
+        },
+    };
+    if id == StreamId::MIN {
+        return Err(CommandError::StreamIdZero);
+    }
+    if id <= last {
+        return Err(CommandError::StreamIdTooSmall);
+    }
+
+    let stream = match &mut get_or_insert(db, key, || Value::Stream(Stream::default())).value {
+        Value::Stream(stream) => stream,
+        _ => return Err(CommandError::WrongType),
+    };
+    stream.add(id, fields.to_vec());
+    if let Some(trim) = &trim {
+        apply_trim(stream, trim);
+    }
+
+    let id = id.to_string();
+    let mut logged: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+    logged[i] = id.as_bytes();
+    aof_tx.send(format_command(&logged)).unwrap();
+    Ok(Reply::Bulk(id.into_bytes()))
+}
+
+/// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+pub(super) fn xtrim(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    if !parts[2].eq_ignore_ascii_case(b"MAXLEN") && !parts[2].eq_ignore_ascii_case(b"MINID") {
+        return Err(CommandError::Syntax);
+    }
+    let mut i = 2;
+    let trim = parse_trim(parts, &mut i)?;
+    if i != parts.len() {
+        return Err(CommandError::Syntax);
+    }
+    let removed = match get_stream(db, &parts[1])? {
+        Some(stream) => apply_trim(stream, &trim),
+        None => 0,
+    };
+    if removed > 0 {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// XDEL key id [id ...]
+pub(super) fn xdel(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let ids = parts[2..].iter().map(|arg| parse_id(arg, 0)).collect::<Result<Vec<_>, _>>()?;
+    let removed = match get_stream(db, &parts[1])? {
+        Some(stream) => ids.iter().filter(|id| stream.entries.remove(id).is_some()).count(),
+        None => 0,
+    };
+    if removed > 0 {
+        log_command(aof_tx, parts);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// XLEN key
+pub(super) fn xlen(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_stream(db, &parts[1])?.map_or(0, |stream| stream.len());
+    Ok(Reply::Integer(len as i64))
+}
+
+/// XRANGE key start end [COUNT count] | XREVRANGE key end start [COUNT count]
+pub(super) fn xrange(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 4 && parts.len() != 6 {
+        return Err(wrong_arity(parts));
+    }
+    let rev = parts[0].eq_ignore_ascii_case(b"XREVRANGE");
+    let (start, end) = if rev { (&parts[3], &parts[2]) } else { (&parts[2], &parts[3]) };
+    let start = parse_bound(start, true)?;
+    let end = parse_bound(end, false)?;
+    let count = match parts.get(4) {
+        Some(option) if option.eq_ignore_ascii_case(b"COUNT") => {
+            let count = parse_arg::<i64>(&parts[5]).ok_or(CommandError::NotInteger)?;
+            usize::try_from(count).unwrap_or(0)
+        },
+        Some(_) => return Err(CommandError::Syntax),
+        None => usize::MAX,
+    };
+
+    let stream = match get_stream(db, &parts[1])? {
+        Some(stream) => stream,
+        None => return Ok(Reply::Array(Vec::new())),
+    };
+    let items = match (start, end) {
+        (Some(start), Some(end)) if start <= end => {
+            let range = stream.entries.range(start..=end);
+            if rev {
+                range.rev().take(count).map(|(id, fields)| entry_reply(id, fields)).collect()
+            } else {
+                range.take(count).map(|(id, fields)| entry_reply(id, fields)).collect()
+            }
+        },
+        _ => Vec::new(),
+    };
+    Ok(Reply::Array(items))
+}
+
+/// Options de XREAD et XREADGROUP : `[COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]`
+pub(super) struct ReadOptions<'a> {
+    pub(super) count: usize,
+    pub(super) block: Option<u64>,
+    /// Uniquement pour XREADGROUP : les entrées délivrées ne sont pas ajoutées aux entrées en attente
+    pub(super) noack: bool,
+    pub(super) keys: &'a [Vec<u8>],
+    pub(super) ids: &'a [Vec<u8>],
+}
+
+/// Lit les options à partir de `parts[start]` (après `GROUP group consumer` pour XREADGROUP)
+pub(super) fn parse_read_options(parts: &[Vec<u8>], start: usize) -> Result<ReadOptions<'_>, CommandError> {
+    let group = parts[0].eq_ignore_ascii_case(b"XREADGROUP");
+    let mut count = usize::MAX;
+    let mut block = None;
+    let mut noack = false;
+    let mut i = start;
+    while i < parts.len() {
+        let option = parts[i].to_ascii_uppercase();
+        if option == b"STREAMS" {
+            let streams = &parts[i + 1..];
+            if streams.is_empty() || !streams.len().is_multiple_of(2) {
+                return Err(CommandError::UnbalancedStreams(String::from_utf8_lossy(&parts[0]).to_lowercase()));
+            }
+            let (keys, ids) = streams.split_at(streams.len() / 2);
+            return Ok(ReadOptions { count, block, noack, keys, ids });
+        }
+        if group && option == b"NOACK" {
+            noack = true;
+            i += 1;
+            continue;
+        }
+        let value = parts.get(i + 1).ok_or(CommandError::Syntax)?;
+        match option.as_slice() {
+            b"COUNT" => {
+                let n = parse_arg::<i64>(value).ok_or(CommandError::NotInteger)?;
+                count = if n <= 0 { usize::MAX } else { n as usize };
+            },
+            b"BLOCK" => {
+                let ms = parse_arg::<i64>(value).ok_or(CommandError::NotInteger)?;
+                block = Some(u64::try_from(ms).map_err(|_| CommandError::NegativeTimeout)?);
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 2;
+    }
+    Err(CommandError::Syntax)
+}
+
+/// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+///
+/// Renvoie les entrées d'identifiant strictement supérieur à celui donné pour chaque stream,
+/// ou nil si aucune n'est disponible. Ici `BLOCK` est ignoré : l'attente est gérée par `xread_blocking`.
+pub(super) fn xread(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let options = parse_read_options(parts, 1)?;
+    let mut results = Vec::new();
+    for (key, id) in options.keys.iter().zip(options.ids) {
+        let stream = get_stream(db, key)?;
+        let after = match id.as_slice() {
+            b"$" => stream.as_ref().map_or(StreamId::MIN, |stream| stream.last_id),
+            _ => parse_id(id, 0)?,
+        };
+        let Some(stream) = stream else { continue };
+        let Some(start) = after.next() else { continue };
+        let entries: Vec<Reply> = stream
+            .entries
+            .range(start..)
+            .take(options.count)
+            .map(|(id, fields)| entry_reply(id, fields))
+            .collect();
+        if !entries.is_empty() {
+            results.push(Reply::Array(vec![Reply::Bulk(key.clone()), Reply::Array(entries)]));
+        }
+    }
+    Ok(if results.is_empty() { Reply::NilArray } else { Reply::Array(results) })
+}
+
+/// XREAD ou XREADGROUP avec l'option BLOCK, hors transaction : réessaie à chaque XADD jusqu'à l'arrivée
+/// d'une entrée ou l'expiration du délai (0 = sans limite). La base est libérée pendant l'attente, et
+/// celle-ci s'arrête si le client se déconnecte.
+///
+/// Renvoie `None` si la commande ne demande pas à bloquer.
+pub(super) fn read_blocking(
+    parts: &[Vec<u8>],
+    db: &Db,
+    index: usize,
+    aof_tx: &Sender<String>,
+    peer: &TcpStream,
+) -> Option<Reply> {
+    let group = parts[0].eq_ignore_ascii_case(b"XREADGROUP");
+    let options = match parse_read_options(parts, if group { 4 } else { 1 }) {
+        Ok(options) => options,
+        Err(_) => return None,
+    };
+    let timeout = options.block?;
+    let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_millis(timeout));
+
+    // Pour XREAD, `$` désigne le dernier identifiant au moment de l'appel : on le fige avant d'attendre
+    let mut parts = parts.to_vec();
+    let mut guard = db.lock().unwrap();
+    if !group {
+        let first_id = parts.len() - options.ids.len();
+        for i in first_id..parts.len() {
+            if parts[i] == b"$" {
+                let key = &parts[i - options.keys.len()];
+                let last_id = match get_stream(guard.keyspace_mut(index), key) {
+                    Ok(stream) => stream.map_or(StreamId::MIN, |stream| stream.last_id),
+                    Err(e) => return Some(Reply::from(e)),
+                };
+                parts[i] = last_id.to_string().into_bytes();
+            }
+        }
+    }
+
+    let stream_added = guard.stream_added();
+    loop {
+        let reply = process_command_parts(&parts, &mut guard, index, aof_tx);
+        let now = Instant::now();
+        if reply != Reply::NilArray || deadline.is_some_and(|deadline| now >= deadline) || peer_closed(peer) {
+            return Some(reply);
+        }
+        // Réveil par XADD, ou périodique pour remarquer une déconnexion du client
+        let wait = deadline.map_or(PEER_CHECK_INTERVAL, |deadline| (deadline - now).min(PEER_CHECK_INTERVAL));
+        guard = stream_added.wait_timeout(guard, wait).unwrap().0;
+    }
+}
+
+/// Vrai si le client a fermé la connexion, sans consommer les requêtes qu'il aurait déjà envoyées
+fn peer_closed(peer: &TcpStream) -> bool {
+    if peer.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let closed = match peer.peek(&mut [0; 1]) {
+        Ok(0) => true,
+        Ok(_) => false,
+        Err(e) => e.kind() != ErrorKind::WouldBlock,
+    };
+    let _ = peer.set_nonblocking(false);
+    closed
+}
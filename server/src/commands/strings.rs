@@ -0,0 +1,358 @@
+// src/commands/strings.rs
+use super::expire::unix_millis;
+use super::{log_command, wrong_arity};
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, format_double, parse_arg, Reply};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
+
+/// Taille maximale d'une chaîne, comme dans Redis (512 Mo)
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT timestamp | PXAT timestamp-ms | KEEPTTL]
+///
+/// `UPDATE` est conservé comme alias de `SET ... XX`, et `TTL seconds` comme synonyme de `EX seconds`.
+pub(super) fn set(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    let command = String::from_utf8_lossy(&parts[0]).to_lowercase();
+    if parts.len() < 3 {
+        return Err(CommandError::WrongArity(command));
+    }
+    let update = command == "update";
+    let mut nx = false;
+    let mut xx = update;
+    let mut get = false;
+    let mut keep_ttl = false;
+    let mut expire_at: Option<SystemTime> = None;
+
+    let mut i = 3;
+    while i < parts.len() {
+        let option = String::from_utf8_lossy(&parts[i]).to_uppercase();
+        let has_expiry = keep_ttl || expire_at.is_some();
+        match option.as_str() {
+            "NX" if !xx => nx = true,
+            "XX" if !nx => xx = true,
+            "GET" => get = true,
+            "KEEPTTL" if !has_expiry => keep_ttl = true,
+            "EX" | "PX" | "EXAT" | "PXAT" | "TTL" if !has_expiry && i + 1 < parts.len() => {
+                i += 1;
+                let amount = parse_arg::<i64>(&parts[i]).ok_or(CommandError::NotInteger)?;
+                expire_at = Some(expire_time(&option, amount, &command)?);
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+    }
+
+    let key = &parts[1];
+    let old = get_live(db, key).map(|entry| entry.clone());
+    let old_value = match &old {
+        Some(Entry { value: Value::String(value), .. }) => Some(value.clone()),
+        Some(_) if get => return Err(CommandError::WrongType),
+        _ => None,
+    };
+    let previous = || old_value.clone().map_or(Reply::Nil, Reply::Bulk);
+    if (nx && old.is_some()) || (xx && old.is_none()) {
+        if update && !get {
+            return Ok(Reply::error("ERR La clé n'existe pas."));
+        }
+        return Ok(if get { previous() } else { Reply::Nil });
+    }
+
+    if keep_ttl {
+        expire_at = old.as_ref().and_then(|entry| entry.expire_at);
+    }
+    let value = parts[2].clone();
+    log_set(aof_tx, key, &value, expire_at);
+    db.insert(key.clone(), Entry::with_expiry(Value::String(value), expire_at));
+
+    Ok(if get { previous() } else { Reply::ok() })
+}
+
+/// Convertit une option d'expiration (`EX`, `PX`, `EXAT`, `PXAT`, `TTL`) en date absolue
+fn expire_time(option: &str, amount: i64, command: &str) -> Result<SystemTime, CommandError> {
+    let invalid = || CommandError::InvalidExpireTime(command.to_string());
+    if amount <= 0 {
+        return Err(invalid());
+    }
+    let millis = match option {
+        "EX" | "EXAT" | "TTL" => amount.checked_mul(1000).ok_or_else(invalid)? as u64,
+        _ => amount as u64,
+    };
+    let base = match option {
+        "EXAT" | "PXAT" => SystemTime::UNIX_EPOCH,
+        _ => SystemTime::now(),
+    };
+    base.checked_add(Duration::from_millis(millis)).ok_or_else(invalid)
+}
+
+/// GET key
+pub(super) fn get(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Ok(Reply::error("ERR Usage: GET key"));
+    }
+    match get_string(db, &parts[1])? {
+        Some(value) => Ok(Reply::Bulk(value.clone())),
+        None => Ok(Reply::Nil),
+    }
+}
+
+/// MGET key [key ...]
+///
+/// Une clé absente ou d'un autre type donne nil.
+pub(super) fn mget(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let values = parts[1..]
+        .iter()
+        .map(|key| match get_live(db, key) {
+            Some(Entry { value: Value::String(value), .. }) => Reply::Bulk(value.clone()),
+            _ => Reply::Nil,
+        })
+        .collect();
+    Ok(Reply::Array(values))
+}
+
+/// MSET key value [key value ...] | MSETNX key value [key value ...]
+///
+/// Comme SET, chaque écriture supprime l'expiration. MSETNX n'écrit rien si l'une des clés existe.
+pub(super) fn mset(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 || parts.len().is_multiple_of(2) {
+        return Err(wrong_arity(parts));
+    }
+    let nx = parts[0].eq_ignore_ascii_case(b"MSETNX");
+    if nx && parts[1..].iter().step_by(2).any(|key| get_live(db, key).is_some()) {
+        return Ok(Reply::Integer(0));
+    }
+    for pair in parts[1..].chunks(2) {
+        db.insert(pair[0].clone(), Entry::new(Value::String(pair[1].clone())));
+    }
+    log_command(aof_tx, parts);
+    Ok(if nx { Reply::Integer(1) } else { Reply::ok() })
+}
+
+/// APPEND key value
+///
+/// Journalisé comme un SET de la valeur complète : rejouer la ligne deux fois ne double pas l'ajout.
+pub(super) fn append(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let entry = get_or_insert(db, &parts[1], || Value::String(Vec::new()));
+    let value = match &mut entry.value {
+        Value::String(value) => value,
+        _ => return Err(CommandError::WrongType),
+    };
+    value.extend_from_slice(&parts[2]);
+    log_set(aof_tx, &parts[1], value, entry.expire_at);
+    Ok(Reply::Integer(value.len() as i64))
+}
+
+/// STRLEN key
+pub(super) fn strlen(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    Ok(Reply::Integer(get_string(db, &parts[1])?.map_or(0, |value| value.len()) as i64))
+}
+
+/// GETRANGE key start end (et l'ancien SUBSTR)
+///
+/// Bornes inclusives, négatives à partir de la fin, ramenées dans la chaîne.
+pub(super) fn getrange(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let start = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let end = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+    let value = get_string(db, &parts[1])?.map_or(&[][..], |value| value.as_slice());
+    let len = value.len() as i64;
+    if start < 0 && end < 0 && start > end {
+        return Ok(Reply::Bulk(Vec::new()));
+    }
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { (len + end).max(0) } else { end.min(len - 1) };
+    if len == 0 || start > end {
+        return Ok(Reply::Bulk(Vec::new()));
+    }
+    Ok(Reply::Bulk(value[start as usize..=end as usize].to_vec()))
+}
+
+/// SETRANGE key offset value
+///
+/// Écrit `value` à partir de `offset`, en complétant par des octets nuls si la chaîne est trop courte.
+pub(super) fn setrange(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let offset = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+    let offset = usize::try_from(offset).map_err(|_| CommandError::OffsetOutOfRange)?;
+    let (key, patch) = (&parts[1], &parts[3]);
+    if patch.is_empty() {
+        // Rien à écrire : la clé n'est pas créée
+        return Ok(Reply::Integer(get_string(db, key)?.map_or(0, |value| value.len()) as i64));
+    }
+    if offset + patch.len() > MAX_STRING_LEN {
+        return Err(CommandError::StringTooLong);
+    }
+    let value = match &mut get_or_insert(db, key, || Value::String(Vec::new())).value {
+        Value::String(value) => value,
+        _ => return Err(CommandError::WrongType),
+    };
+    if value.len() < offset + patch.len() {
+        value.resize(offset + patch.len(), 0);
+    }
+    value[offset..offset + patch.len()].copy_from_slice(patch);
+    let len = value.len();
+    log_command(aof_tx, parts);
+    Ok(Reply::Integer(len as i64))
+}
+
+/// GETDEL key
+pub(super) fn getdel(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let Some(value) = get_string(db, key)?.cloned() else {
+        return Ok(Reply::Nil);
+    };
+    db.remove(key);
+    aof_tx.send(format_command(&[b"DELETE", key])).unwrap();
+    Ok(Reply::Bulk(value))
+}
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT timestamp | PXAT timestamp-ms | PERSIST]
+///
+/// La nouvelle expiration est journalisée sous forme absolue, comme pour SET.
+pub(super) fn getex(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Err(wrong_arity(parts));
+    }
+    let option = parts.get(2).map(|option| String::from_utf8_lossy(option).to_uppercase());
+    let expire_at = match (option.as_deref(), parts.len()) {
+        (None, _) => None,
+        (Some("PERSIST"), 3) => Some(None),
+        (Some("EX" | "PX" | "EXAT" | "PXAT"), 4) => {
+            let amount = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+            Some(Some(expire_time(option.as_deref().unwrap(), amount, "getex")?))
+        },
+        _ => return Err(CommandError::Syntax),
+    };
+
+    let key = &parts[1];
+    let Some(value) = get_string(db, key)?.cloned() else {
+        return Ok(Reply::Nil);
+    };
+    if let Some(expire_at) = expire_at {
+        db.set_expire(key, expire_at);
+        log_set(aof_tx, key, &value, expire_at);
+    }
+    Ok(Reply::Bulk(value))
+}
+
+/// DELETE key (quel que soit le type de la valeur)
+pub(super) fn delete(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 2 {
+        return Ok(Reply::error("ERR Usage: DELETE key"));
+    }
+    let key = &parts[1];
+    if get_live(db, key).is_some() {
+        db.remove(key);
+        aof_tx.send(format_command(&[b"DELETE", key])).unwrap();
+        Ok(Reply::ok())
+    } else {
+        Ok(Reply::error("ERR La clé n'existe pas."))
+    }
+}
+
+/// INCR key | DECR key | INCRBY key increment | DECRBY key decrement
+///
+/// Une clé absente vaut 0. Le résultat est journalisé tel quel dans l'AOF.
+pub(super) fn incr_by(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    let command = String::from_utf8_lossy(&parts[0]).to_lowercase();
+    let with_amount = command.ends_with("by");
+    if parts.len() != if with_amount { 3 } else { 2 } {
+        return Err(wrong_arity(parts));
+    }
+    let amount = if with_amount {
+        parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?
+    } else {
+        1
+    };
+    let delta = if command.starts_with("decr") {
+        amount.checked_neg().ok_or(CommandError::Overflow)?
+    } else {
+        amount
+    };
+
+    let key = &parts[1];
+    let current = match get_string(db, key)? {
+        Some(value) => parse_arg::<i64>(value).ok_or(CommandError::NotInteger)?,
+        None => 0,
+    };
+    let result = current.checked_add(delta).ok_or(CommandError::Overflow)?;
+    store_number(db, key, result.to_string().into_bytes(), aof_tx);
+    Ok(Reply::Integer(result))
+}
+
+/// INCRBYFLOAT key increment
+pub(super) fn incr_by_float(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let increment = parse_float(&parts[2])?;
+    let key = &parts[1];
+    let current = match get_string(db, key)? {
+        Some(value) => parse_float(value)?,
+        None => 0.0,
+    };
+    let result = current + increment;
+    if !result.is_finite() {
+        return Err(CommandError::NanOrInfinity);
+    }
+    let value = format_double(result).into_bytes();
+    store_number(db, key, value.clone(), aof_tx);
+    Ok(Reply::Bulk(value))
+}
+
+fn parse_float(arg: &[u8]) -> Result<f64, CommandError> {
+    parse_arg::<f64>(arg).filter(|f| !f.is_nan()).ok_or(CommandError::NotFloat)
+}
+
+/// Chaîne stockée sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+pub(super) fn get_string<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut Vec<u8>>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::String(value), .. }) => Ok(Some(value)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+/// Remplace la valeur d'un compteur en conservant son expiration, et journalise le résultat
+/// (et non l'incrément) pour que le rejeu de l'AOF soit exact.
+fn store_number(db: &mut Keyspace, key: &[u8], value: Vec<u8>, aof_tx: &Sender<String>) {
+    let expire_at = match db.get_mut(key) {
+        Some(entry) => {
+            entry.value = Value::String(value.clone());
+            entry.expire_at
+        },
+        None => {
+            db.insert(key.to_vec(), Entry::new(Value::String(value.clone())));
+            None
+        },
+    };
+    log_set(aof_tx, key, &value, expire_at);
+}
+
+/// Journalise l'écriture d'une chaîne sous la forme `SET key value [PXAT timestamp-ms]`
+fn log_set(aof_tx: &Sender<String>, key: &[u8], value: &[u8], expire_at: Option<SystemTime>) {
+    let cmd = if let Some(exp) = expire_at {
+        let ts = unix_millis(exp).to_string();
+        format_command(&[b"SET", key, value, b"PXAT", ts.as_bytes()])
+    } else {
+        format_command(&[b"SET", key, value])
+    };
+    aof_tx.send(cmd).unwrap();
+}
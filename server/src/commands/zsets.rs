@@ -0,0 +1,298 @@
+// src/commands/zsets.rs
+use super::{log_command, normalize_range, wrong_arity};
+use crate::db::{get_live, get_or_insert, Entry, Keyspace, Value};
+use crate::error::CommandError;
+use crate::protocol::{format_command, format_double, parse_arg, Reply};
+use crate::sorted_set::{ScoreRange, SortedSet};
+use std::sync::mpsc::Sender;
+
+/// Ensemble trié stocké sous `key`, ou `WRONGTYPE` si la clé contient un autre type
+pub(super) fn get_zset<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<Option<&'a mut SortedSet>, CommandError> {
+    match get_live(db, key) {
+        None => Ok(None),
+        Some(Entry { value: Value::SortedSet(zset), .. }) => Ok(Some(zset)),
+        Some(_) => Err(CommandError::WrongType),
+    }
+}
+
+pub(super) fn get_or_create_zset<'a>(db: &'a mut Keyspace, key: &[u8]) -> Result<&'a mut SortedSet, CommandError> {
+    match &mut get_or_insert(db, key, || Value::SortedSet(SortedSet::new())).value {
+        Value::SortedSet(zset) => Ok(zset),
+        _ => Err(CommandError::WrongType),
+    }
+}
+
+/// Supprime la clé si l'ensemble trié est vide (par exemple après un ZADD XX sur une clé absente)
+pub(super) fn remove_if_empty(db: &mut Keyspace, key: &[u8]) {
+    if matches!(db.get(key), Some(Entry { value: Value::SortedSet(zset), .. }) if zset.is_empty()) {
+        db.remove(key);
+    }
+}
+
+/// Score d'un argument (`inf`, `-inf` et `+inf` acceptés, `nan` refusé)
+fn parse_score(arg: &[u8]) -> Option<f64> {
+    parse_arg::<f64>(arg).filter(|score| !score.is_nan())
+}
+
+/// Intervalle de scores Redis : `1.5`, `(1.5` pour une borne exclusive, `-inf`, `+inf`
+fn parse_range(min: &[u8], max: &[u8]) -> Result<ScoreRange, CommandError> {
+    let bound = |arg: &[u8]| match arg.strip_prefix(b"(") {
+        Some(value) => parse_score(value).map(|score| (score, true)),
+        None => parse_score(arg).map(|score| (score, false)),
+    };
+    let (min, min_exclusive) = bound(min).ok_or(CommandError::MinMaxNotFloat)?;
+    let (max, max_exclusive) = bound(max).ok_or(CommandError::MinMaxNotFloat)?;
+    Ok(ScoreRange { min, min_exclusive, max, max_exclusive })
+}
+
+/// Journalise le score obtenu sous la forme `ZADD key score member`
+fn log_score(aof_tx: &Sender<String>, key: &[u8], member: &[u8], score: f64) {
+    aof_tx.send(format_command(&[b"ZADD", key, format_double(score).as_bytes(), member])).unwrap();
+}
+
+/// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+pub(super) fn zadd(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) = (false, false, false, false, false, false);
+    let mut i = 2;
+    while i < parts.len() {
+        match String::from_utf8_lossy(&parts[i]).to_uppercase().as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "GT" => gt = true,
+            "LT" => lt = true,
+            "CH" => ch = true,
+            "INCR" => incr = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    let pairs = &parts[i..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(CommandError::Syntax);
+    }
+    if nx && xx {
+        return Err(CommandError::IncompatibleOptions("XX and NX options at the same time are not compatible"));
+    }
+    if (gt && lt) || (nx && (gt || lt)) {
+        return Err(CommandError::IncompatibleOptions("GT, LT, and/or NX options at the same time are not compatible"));
+    }
+    if incr && pairs.len() > 2 {
+        return Err(CommandError::IncompatibleOptions("INCR option supports a single increment-element pair"));
+    }
+    let scores = pairs
+        .chunks(2)
+        .map(|pair| parse_score(&pair[0]).ok_or(CommandError::NotFloat))
+        .collect::<Result<Vec<f64>, _>>()?;
+
+    let key = &parts[1];
+    if get_zset(db, key)?.is_none() && xx {
+        return Ok(if incr { Reply::Nil } else { Reply::Integer(0) });
+    }
+    let zset = get_or_create_zset(db, key)?;
+    let (mut added, mut updated) = (0, 0);
+    let mut incr_result = None;
+    for (pair, &score) in pairs.chunks(2).zip(&scores) {
+        let member = &pair[1];
+        let old = zset.score(member);
+        if (nx && old.is_some()) || (xx && old.is_none()) {
+            continue;
+        }
+        let new = match (incr, old) {
+            (true, Some(old)) => old + score,
+            _ => score,
+        };
+        if new.is_nan() {
+            remove_if_empty(db, key);
+            return Err(CommandError::ScoreNaN);
+        }
+        if let Some(old) = old {
+            if (gt && new <= old) || (lt && new >= old) {
+                continue;
+            }
+            if new != old {
+                updated += 1;
+            }
+        } else {
+            added += 1;
+        }
+        zset.insert(member.clone(), new);
+        incr_result = Some(new);
+    }
+
+    if incr {
+        if let Some(score) = incr_result {
+            log_score(aof_tx, key, &pairs[1], score);
+        }
+        remove_if_empty(db, key);
+        return Ok(incr_result.map_or(Reply::Nil, Reply::Double));
+    }
+    if added + updated > 0 {
+        log_command(aof_tx, parts);
+    }
+    remove_if_empty(db, key);
+    Ok(Reply::Integer(if ch { added + updated } else { added }))
+}
+
+/// ZINCRBY key increment member
+pub(super) fn zincrby(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let increment = parse_score(&parts[2]).ok_or(CommandError::NotFloat)?;
+    let (key, member) = (&parts[1], &parts[3]);
+    let current = get_zset(db, key)?.and_then(|zset| zset.score(member)).unwrap_or(0.0);
+    let score = current + increment;
+    if score.is_nan() {
+        return Err(CommandError::ScoreNaN);
+    }
+    get_or_create_zset(db, key)?.insert(member.clone(), score);
+    log_score(aof_tx, key, member, score);
+    Ok(Reply::Double(score))
+}
+
+/// ZREM key member [member ...]
+pub(super) fn zrem(parts: &[Vec<u8>], db: &mut Keyspace, aof_tx: &Sender<String>) -> Result<Reply, CommandError> {
+    if parts.len() < 3 {
+        return Err(wrong_arity(parts));
+    }
+    let key = &parts[1];
+    let zset = match get_zset(db, key)? {
+        Some(zset) => zset,
+        None => return Ok(Reply::Integer(0)),
+    };
+    let removed = parts[2..].iter().filter(|member| zset.remove(member)).count();
+    if removed > 0 {
+        log_command(aof_tx, parts);
+        remove_if_empty(db, key);
+    }
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// ZCARD key
+pub(super) fn zcard(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 2 {
+        return Err(wrong_arity(parts));
+    }
+    let len = get_zset(db, &parts[1])?.map_or(0, |zset| zset.len());
+    Ok(Reply::Integer(len as i64))
+}
+
+/// ZSCORE key member
+pub(super) fn zscore(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 {
+        return Err(wrong_arity(parts));
+    }
+    let score = get_zset(db, &parts[1])?.and_then(|zset| zset.score(&parts[2]));
+    Ok(score.map_or(Reply::Nil, Reply::Double))
+}
+
+/// ZCOUNT key min max
+pub(super) fn zcount(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let range = parse_range(&parts[2], &parts[3])?;
+    let count = get_zset(db, &parts[1])?.map_or(0, |zset| zset.count(range));
+    Ok(Reply::Integer(count as i64))
+}
+
+/// ZRANK key member [WITHSCORE] | ZREVRANK key member [WITHSCORE]
+pub(super) fn zrank(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(wrong_arity(parts));
+    }
+    let with_score = match parts.get(3) {
+        Some(option) if option.eq_ignore_ascii_case(b"WITHSCORE") => true,
+        Some(_) => return Err(CommandError::Syntax),
+        None => false,
+    };
+    let rev = parts[0].eq_ignore_ascii_case(b"ZREVRANK");
+    let found = get_zset(db, &parts[1])?.and_then(|zset| {
+        let rank = zset.rank(&parts[2])?;
+        let rank = if rev { zset.len() - 1 - rank } else { rank };
+        Some((rank as i64, zset.score(&parts[2])?))
+    });
+    Ok(match found {
+        Some((rank, score)) if with_score => Reply::Array(vec![Reply::Integer(rank), Reply::Double(score)]),
+        Some((rank, _)) => Reply::Integer(rank),
+        None if with_score => Reply::NilArray,
+        None => Reply::Nil,
+    })
+}
+
+/// ZRANGE key start stop [BYSCORE] [REV] [LIMIT offset count] [WITHSCORES]
+///
+/// Sert aussi aux anciennes formes ZREVRANGE, ZRANGEBYSCORE et ZREVRANGEBYSCORE. Avec `REV`,
+/// les bornes de score s'écrivent du maximum vers le minimum, comme dans Redis.
+pub(super) fn zrange(parts: &[Vec<u8>], db: &mut Keyspace) -> Result<Reply, CommandError> {
+    if parts.len() < 4 {
+        return Err(wrong_arity(parts));
+    }
+    let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+    let mut by_score = command.ends_with("BYSCORE");
+    let mut rev = command.starts_with("ZREV");
+    let mut limit = None;
+    let mut with_scores = false;
+    let mut i = 4;
+    while i < parts.len() {
+        match String::from_utf8_lossy(&parts[i]).to_uppercase().as_str() {
+            "WITHSCORES" => with_scores = true,
+            "BYSCORE" if command == "ZRANGE" => by_score = true,
+            "REV" if command == "ZRANGE" => rev = true,
+            "LIMIT" if command != "ZREVRANGE" && i + 2 < parts.len() => {
+                let offset = parse_arg::<i64>(&parts[i + 1]).ok_or(CommandError::NotInteger)?;
+                let count = parse_arg::<i64>(&parts[i + 2]).ok_or(CommandError::NotInteger)?;
+                limit = Some((offset, count));
+                i += 2;
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+    }
+    if limit.is_some() && !by_score {
+        return Err(CommandError::IncompatibleOptions(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+        ));
+    }
+
+    let items: Vec<(Vec<u8>, f64)> = if by_score {
+        let (min, max) = if rev { (&parts[3], &parts[2]) } else { (&parts[2], &parts[3]) };
+        let range = parse_range(min, max)?;
+        let (offset, count) = limit.unwrap_or((0, -1));
+        let count = if count < 0 { usize::MAX } else { count as usize };
+        match get_zset(db, &parts[1])? {
+            Some(zset) if offset >= 0 => zset
+                .range_by_score(range, rev)
+                .skip(offset as usize)
+                .take(count)
+                .map(|(member, score)| (member.to_vec(), score))
+                .collect(),
+            _ => Vec::new(),
+        }
+    } else {
+        let start = parse_arg::<i64>(&parts[2]).ok_or(CommandError::NotInteger)?;
+        let stop = parse_arg::<i64>(&parts[3]).ok_or(CommandError::NotInteger)?;
+        match get_zset(db, &parts[1])? {
+            Some(zset) => match normalize_range(start, stop, zset.len()) {
+                Some((start, stop)) => {
+                    let first = if rev { zset.len() - 1 - start } else { start };
+                    zset.iter_from_rank(first, rev)
+                        .take(stop - start + 1)
+                        .map(|(member, score)| (member.to_vec(), score))
+                        .collect()
+                },
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        }
+    };
+
+    Ok(if with_scores {
+        Reply::Pairs(items.into_iter().map(|(member, score)| (Reply::Bulk(member), Reply::Double(score))).collect())
+    } else {
+        Reply::Array(items.into_iter().map(|(member, _)| Reply::Bulk(member)).collect())
+    })
+}
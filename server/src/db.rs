@@ -1,13 +1,654 @@
 // src/db.rs
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
-use serde::{Serialize, Deserialize};
+use crate::expiry::ExpiryIndex;
+use crate::hyperloglog::HyperLogLog;
+use crate::protocol::format_command;
+use crate::sorted_set::SortedSet;
+use crate::stream::Stream;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::{BuildHasher, RandomState};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Nombre de bases numérotées (SELECT, MOVE, COPY ... DB, SWAPDB)
+pub const DATABASES: usize = 16;
+
+/// Valeur initiale du compteur de fréquence d'une nouvelle entrée, comme dans Redis
+const LFU_INIT_VAL: u8 = 5;
+/// Plus il est grand, plus le compteur de fréquence monte lentement (`lfu-log-factor` de Redis)
+const LFU_LOG_FACTOR: f64 = 10.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
-    pub value: String,
+    #[serde(deserialize_with = "value_compat")]
+    pub value: Value,
     pub expire_at: Option<SystemTime>,
+    /// Métadonnées d'accès, qui ne sont pas enregistrées dans le snapshot
+    #[serde(skip)]
+    pub access: Access,
+}
+
+/// Dernier accès et fréquence d'accès d'une entrée (OBJECT IDLETIME et OBJECT FREQ)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Access {
+    last: SystemTime,
+    /// Compteur logarithmique de Redis : il faut environ 100 accès pour atteindre 10 et un
+    /// million pour atteindre 255. Il perd 1 par minute sans accès.
+    counter: u8,
+}
+
+impl Default for Access {
+    fn default() -> Access {
+        Access { last: SystemTime::now(), counter: LFU_INIT_VAL }
+    }
+}
+
+impl Access {
+    /// Enregistre un accès
+    pub fn touch(&mut self) {
+        let mut counter = self.freq();
+        if counter < u8::MAX {
+            let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+            let draw = RandomState::new().hash_one(()) as f64 / u64::MAX as f64;
+            if draw < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+                counter += 1;
+            }
+        }
+        *self = Access { last: SystemTime::now(), counter };
+    }
+
+    /// Temps écoulé depuis le dernier accès
+    pub fn idle(&self) -> Duration {
+        self.last.elapsed().unwrap_or_default()
+    }
+
+    /// Compteur de fréquence, diminué d'une unité par minute écoulée depuis le dernier accès
+    pub fn freq(&self) -> u8 {
+        let minutes = (self.idle().as_secs() / 60).min(u8::MAX as u64) as u8;
+        self.counter.saturating_sub(minutes)
+    }
+}
+
+/// Valeur stockée sous une clé, selon son type Redis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Value {
+    String(#[serde(with = "escaped")] Vec<u8>),
+    List(#[serde(with = "escaped_seq")] VecDeque<Vec<u8>>),
+    Hash(#[serde(with = "escaped_map")] HashMap<Vec<u8>, Vec<u8>>),
+    Set(#[serde(with = "escaped_seq")] HashSet<Vec<u8>>),
+    #[serde(rename = "zset")]
+    SortedSet(SortedSet),
+    Stream(Stream),
+    HyperLogLog(HyperLogLog),
+    /// Document JSON, enregistré tel quel dans le snapshot
+    Json(serde_json::Value),
+}
+
+impl Value {
+    /// Nom du type tel que renvoyé par la commande TYPE
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+            // Contrairement à Redis, un HyperLogLog n'est pas une chaîne : les commandes de chaînes
+            // le refusent (WRONGTYPE), TYPE et SCAN TYPE ne doivent donc pas le présenter comme tel
+            Value::HyperLogLog(_) => "hyperloglog",
+            Value::Json(_) => "ReJSON-RL",
+        }
+    }
+
+    /// Nom de l'encodage renvoyé par OBJECT ENCODING.
+    ///
+    /// Chaque type n'a qu'une représentation ici : ce sont les noms qu'emploierait Redis, avec ses
+    /// seuils par défaut, pour que les outils qui s'y fient gardent le même comportement.
+    pub fn encoding(&self) -> &'static str {
+        let small = |item: &[u8]| item.len() <= 64;
+        match self {
+            Value::String(value) if is_canonical_integer(value) => "int",
+            Value::String(value) if value.len() <= 44 => "embstr",
+            Value::String(_) | Value::HyperLogLog(_) | Value::Json(_) => "raw",
+            Value::List(list) if list.iter().map(|item| item.len() + 11).sum::<usize>() <= 8192 => "listpack",
+            Value::List(_) => "quicklist",
+            Value::Hash(hash) if hash.len() <= 128 && hash.iter().all(|(field, value)| small(field) && small(value)) => {
+                "listpack"
+            },
+            Value::Hash(_) => "hashtable",
+            Value::Set(set) if set.len() <= 512 && set.iter().all(|member| is_canonical_integer(member)) => "intset",
+            Value::Set(set) if set.len() <= 128 && set.iter().all(|member| small(member)) => "listpack",
+            Value::Set(_) => "hashtable",
+            Value::SortedSet(zset) if zset.len() <= 128 && zset.iter().all(|(member, _)| small(member)) => "listpack",
+            Value::SortedSet(_) => "skiplist",
+            Value::Stream(_) => "stream",
+        }
+    }
+}
+
+/// Vrai si `value` est l'écriture décimale exacte d'un entier 64 bits (sans zéro ni `+` superflu)
+fn is_canonical_integer(value: &[u8]) -> bool {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|text| text.parse::<i64>().ok())
+        .is_some_and(|n| n.to_string().as_bytes() == value)
+}
+
+impl Entry {
+    pub fn new(value: Value) -> Entry {
+        Entry::with_expiry(value, None)
+    }
+
+    pub fn with_expiry(value: Value, expire_at: Option<SystemTime>) -> Entry {
+        Entry { value, expire_at, access: Access::default() }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expire_at.is_some_and(|exp| SystemTime::now() > exp)
+    }
+}
+
+/// Espace de clés complet, avec ses index.
+///
+/// Se lit comme la `HashMap` qu'il contient ; les modifications passent par `insert`, `remove`,
+/// `get_mut` et `set_expire`, qui tiennent à jour :
+/// - l'index des dates d'expiration de l'expiration active (voir le module `expiry`) ;
+/// - l'ordre de parcours de SCAN : les clés triées par leur hachage, qui ne dépend pas de la
+///   capacité de la table et reste donc stable quand elle est redimensionnée.
+#[derive(Debug, Default)]
+pub struct Keyspace {
+    entries: HashMap<Vec<u8>, Entry>,
+    expiry: ExpiryIndex,
+    order: BTreeSet<(u64, Vec<u8>)>,
+    hasher: RandomState,
+    /// Pendant le rejeu de l'AOF, une clé expirée n'est pas supprimée quand on y accède : ses
+    /// commandes suivantes s'appliquent à elle comme avant l'arrêt, et l'expiration active la
+    /// supprime une fois le serveur démarré
+    loading: bool,
+    /// Clés supprimées à l'accès parce qu'elles avaient expiré, pas encore journalisées
+    expired: Vec<Vec<u8>>,
+}
+
+impl Keyspace {
+    pub fn insert(&mut self, key: Vec<u8>, entry: Entry) -> Option<Entry> {
+        if let Some(expire_at) = entry.expire_at {
+            self.index_expiry(&key, expire_at);
+        }
+        if !self.entries.contains_key(&key) {
+            self.order.insert((self.hasher.hash_one(key.as_slice()), key.clone()));
+        }
+        self.entries.insert(key, entry)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&(self.hasher.hash_one(key), key.to_vec()));
+        Some(entry)
+    }
+
+    /// Supprime `key` si elle a expiré (expiration passive), sauf pendant le rejeu de l'AOF
+    fn expire_if_due(&mut self, key: &[u8]) {
+        if !self.loading && self.entries.get(key).is_some_and(Entry::is_expired) {
+            self.remove(key);
+            self.expired.push(key.to_vec());
+        }
+    }
+
+    /// Vrai pendant le rejeu de l'AOF
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    /// Vide l'espace de clés (FLUSHDB, FLUSHALL)
+    pub fn clear(&mut self) {
+        *self = Keyspace { loading: self.loading, ..Keyspace::default() };
+    }
+
+    /// Accès en écriture à une entrée ; son expiration se modifie par `set_expire`
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        self.entries.get_mut(key)
+    }
+
+    /// Remplace l'expiration de `key` si la clé existe
+    pub fn set_expire(&mut self, key: &[u8], expire_at: Option<SystemTime>) {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return;
+        };
+        entry.expire_at = expire_at;
+        if let Some(expire_at) = expire_at {
+            self.index_expiry(key, expire_at);
+        }
+    }
+
+    fn index_expiry(&mut self, key: &[u8], expire_at: SystemTime) {
+        self.expiry.push(expire_at, key);
+        if self.expiry.needs_compaction() {
+            let entries = &self.entries;
+            self.expiry.retain(|expire_at, key| entries.get(key).is_some_and(|entry| entry.expire_at == Some(expire_at)));
+        }
+    }
+
+    /// Examine au plus `limit` échéances dépassées à `now` et supprime les clés correspondantes.
+    ///
+    /// Renvoie le nombre d'échéances examinées et les clés supprimées.
+    pub fn expire_due(&mut self, now: SystemTime, limit: usize) -> (usize, Vec<Vec<u8>>) {
+        let mut examined = 0;
+        let mut expired = Vec::new();
+        while examined < limit {
+            let Some((expire_at, key)) = self.expiry.pop_due(now) else {
+                break;
+            };
+            examined += 1;
+            // Échéance périmée : la clé a été supprimée ou son expiration a changé depuis
+            if self.entries.get(&key).is_some_and(|entry| entry.expire_at == Some(expire_at)) {
+                self.remove(&key);
+                expired.push(key);
+            }
+        }
+        (examined, expired)
+    }
+
+    /// Au moins `count` clés dans l'ordre de parcours, à partir du curseur `cursor`.
+    ///
+    /// Renvoie aussi le curseur suivant (0 à la fin du parcours). Une page ne s'arrête jamais entre
+    /// deux clés de même hachage, si bien qu'une clé présente pendant tout le parcours est renvoyée
+    /// exactement une fois.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&[u8]>) {
+        let mut keys = Vec::new();
+        let mut last = None;
+        for (hash, key) in self.order.range((cursor, Vec::new())..) {
+            if keys.len() >= count && last != Some(*hash) {
+                return (*hash, keys);
+            }
+            keys.push(key.as_slice());
+            last = Some(*hash);
+        }
+        (0, keys)
+    }
+
+    /// Clé tirée au hasard (expirée ou non), `None` si la base est vide
+    pub fn random_key(&self) -> Option<&[u8]> {
+        let start = RandomState::new().hash_one(());
+        let mut after = self.order.range((start, Vec::new())..);
+        after.next().or_else(|| self.order.first()).map(|(_, key)| key.as_slice())
+    }
+}
+
+impl From<HashMap<Vec<u8>, Entry>> for Keyspace {
+    fn from(entries: HashMap<Vec<u8>, Entry>) -> Keyspace {
+        let mut keyspace = Keyspace::default();
+        for (key, entry) in entries {
+            keyspace.insert(key, entry);
+        }
+        keyspace
+    }
+}
+
+impl Deref for Keyspace {
+    type Target = HashMap<Vec<u8>, Entry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+/// Les bases numérotées du serveur.
+///
+/// `Deref` et `DerefMut` donnent accès à la base 0, celle des connexions qui n'ont pas fait SELECT.
+#[derive(Debug)]
+pub struct Databases {
+    keyspaces: Vec<Keyspace>,
+    /// Base à laquelle s'appliquent les lignes de l'AOF jusqu'au prochain `SELECT`
+    aof_index: usize,
+    /// Base par laquelle commence le prochain cycle d'expiration active
+    pub(crate) expiry_index: usize,
+    /// Clés surveillées par WATCH, par base
+    watched: HashMap<(usize, Vec<u8>), WatchedKey>,
+    /// Réveille les lectures bloquantes de streams (XREAD, XREADGROUP) à chaque XADD ;
+    /// s'utilise avec le verrou qui protège ces bases
+    stream_added: Arc<Condvar>,
+}
+
+/// Clé surveillée par au moins une connexion
+#[derive(Debug, Default)]
+struct WatchedKey {
+    watchers: usize,
+    /// Incrémentée à chaque modification de la clé tant qu'elle est surveillée
+    version: u64,
+}
+
+impl Default for Databases {
+    fn default() -> Databases {
+        Databases {
+            keyspaces: (0..DATABASES).map(|_| Keyspace::default()).collect(),
+            aof_index: 0,
+            expiry_index: 0,
+            watched: HashMap::new(),
+            stream_added: Arc::new(Condvar::new()),
+        }
+    }
+}
+
+impl From<Keyspace> for Databases {
+    fn from(keyspace: Keyspace) -> Databases {
+        let mut databases = Databases::default();
+        databases.keyspaces[0] = keyspace;
+        databases
+    }
+}
+
+impl Databases {
+    pub fn keyspace(&self, index: usize) -> &Keyspace {
+        &self.keyspaces[index]
+    }
+
+    pub fn keyspace_mut(&mut self, index: usize) -> &mut Keyspace {
+        &mut self.keyspaces[index]
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.keyspaces.swap(a, b);
+    }
+
+    /// Vide toutes les bases (FLUSHALL)
+    pub fn flush_all(&mut self) {
+        self.keyspaces.iter_mut().for_each(Keyspace::clear);
+    }
+
+    /// Journalise sous forme de `DELETE` les clés supprimées à l'accès parce qu'elles avaient
+    /// expiré, et les signale aux connexions qui les surveillent
+    pub fn log_expired(&mut self, aof_tx: &Sender<String>) {
+        for index in 0..DATABASES {
+            for key in mem::take(&mut self.keyspaces[index].expired) {
+                self.log(index, format_command(&[b"DELETE", &key]), aof_tx);
+                self.touch(index, &key);
+            }
+        }
+    }
+
+    /// Suspend l'expiration passive pendant le rejeu de l'AOF
+    pub fn set_loading(&mut self, loading: bool) {
+        self.keyspaces.iter_mut().for_each(|keyspace| keyspace.loading = loading);
+    }
+
+    /// Commence à surveiller `key` pour une connexion et renvoie sa version courante
+    pub fn watch(&mut self, index: usize, key: &[u8]) -> u64 {
+        let watched = self.watched.entry((index, key.to_vec())).or_default();
+        watched.watchers += 1;
+        watched.version
+    }
+
+    /// Arrête de surveiller `key` pour une connexion ; la version est oubliée quand plus personne ne la surveille
+    pub fn unwatch(&mut self, index: usize, key: &[u8]) {
+        let id = (index, key.to_vec());
+        if let Some(watched) = self.watched.get_mut(&id) {
+            watched.watchers -= 1;
+            if watched.watchers == 0 {
+                self.watched.remove(&id);
+            }
+        }
+    }
+
+    /// Version d'une clé surveillée (0 si personne ne la surveille)
+    pub fn version(&self, index: usize, key: &[u8]) -> u64 {
+        self.watched.get(&(index, key.to_vec())).map_or(0, |watched| watched.version)
+    }
+
+    /// Signale la modification de `key` aux connexions qui la surveillent
+    pub fn touch(&mut self, index: usize, key: &[u8]) {
+        if self.watched.is_empty() {
+            return;
+        }
+        if let Some(watched) = self.watched.get_mut(&(index, key.to_vec())) {
+            watched.version += 1;
+        }
+    }
+
+    /// Signale la modification de toutes les clés de la base `index` (FLUSHDB, SWAPDB...)
+    pub fn touch_all(&mut self, index: usize) {
+        for (_, watched) in self.watched.iter_mut().filter(|((watched_index, _), _)| *watched_index == index) {
+            watched.version += 1;
+        }
+    }
+
+    /// Condition sur laquelle attendent les lectures bloquantes de streams
+    pub fn stream_added(&self) -> Arc<Condvar> {
+        self.stream_added.clone()
+    }
+
+    /// Réveille les lectures bloquantes après l'ajout d'une entrée à un stream
+    pub fn notify_stream_added(&self) {
+        self.stream_added.notify_all();
+    }
+
+    pub fn aof_index(&self) -> usize {
+        self.aof_index
+    }
+
+    /// Change la base courante de l'AOF (ligne `SELECT` rejouée au démarrage)
+    pub fn set_aof_index(&mut self, index: usize) {
+        self.aof_index = index;
+    }
+
+    /// Journalise une ligne produite par une commande exécutée dans la base `index`, précédée
+    /// de `SELECT index` si l'AOF est positionné sur une autre base
+    pub fn log(&mut self, index: usize, line: String, aof_tx: &Sender<String>) {
+        if self.aof_index != index {
+            aof_tx.send(format!("SELECT {}", index)).unwrap();
+            self.aof_index = index;
+        }
+        aof_tx.send(line).unwrap();
+    }
+
+    /// Lit un snapshot : un tableau avec une table par base, ou une table unique (snapshot
+    /// antérieur aux bases numérotées) chargée dans la base 0
+    pub fn from_snapshot(data: &[u8]) -> serde_json::Result<Databases> {
+        #[derive(Deserialize)]
+        struct SnapshotKeyspace(#[serde(with = "escaped_keys")] HashMap<Vec<u8>, Entry>);
+
+        let mut databases = Databases::default();
+        match serde_json::from_slice::<Vec<SnapshotKeyspace>>(data) {
+            Ok(keyspaces) => {
+                for (index, SnapshotKeyspace(entries)) in keyspaces.into_iter().enumerate().take(DATABASES) {
+                    databases.keyspaces[index] = Keyspace::from(entries);
+                }
+            },
+            Err(_) => {
+                // Une entrée dont la valeur est une simple chaîne date d'avant l'échappement : comme sa
+                // valeur (voir `value_compat`), sa clé est reprise telle quelle
+                let raw: HashMap<String, serde_json::Value> = serde_json::from_slice(data)?;
+                let mut entries = HashMap::with_capacity(raw.len());
+                for (key, entry) in raw {
+                    let key = match entry.get("value") {
+                        Some(serde_json::Value::String(_)) => key.into_bytes(),
+                        _ => unescape_bytes(&key),
+                    };
+                    entries.insert(key, Entry::deserialize(entry)?);
+                }
+                databases.keyspaces[0] = Keyspace::from(entries);
+            },
+        }
+        Ok(databases)
+    }
+}
+
+/// Le snapshot est un tableau avec une table (clés échappées) par base
+impl Serialize for Databases {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Escaped<'a>(&'a Keyspace);
+
+        impl Serialize for Escaped<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                escaped_keys::serialize(self.0, serializer)
+            }
+        }
+
+        serializer.collect_seq(self.keyspaces.iter().map(Escaped))
+    }
+}
+
+impl Deref for Databases {
+    type Target = Keyspace;
+
+    fn deref(&self) -> &Keyspace {
+        &self.keyspaces[0]
+    }
+}
+
+impl DerefMut for Databases {
+    fn deref_mut(&mut self) -> &mut Keyspace {
+        &mut self.keyspaces[0]
+    }
+}
+
+pub type Db = Arc<Mutex<Databases>>;
+
+/// Renvoie l'entrée associée à `key` si elle n'a pas expiré (une entrée expirée est supprimée au passage)
+/// et enregistre l'accès
+pub fn get_live<'a>(db: &'a mut Keyspace, key: &[u8]) -> Option<&'a mut Entry> {
+    peek_live(db, key)?;
+    let entry = db.get_mut(key)?;
+    entry.access.touch();
+    Some(entry)
+}
+
+/// Comme `get_live`, mais sans compter d'accès (EXISTS, TYPE, TTL, OBJECT...)
+pub fn peek_live<'a>(db: &'a mut Keyspace, key: &[u8]) -> Option<&'a Entry> {
+    db.expire_if_due(key);
+    db.get(key)
+}
+
+/// Renvoie l'entrée associée à `key`, en la créant avec `default` si elle est absente ou expirée
+pub fn get_or_insert<'a>(db: &'a mut Keyspace, key: &[u8], default: impl FnOnce() -> Value) -> &'a mut Entry {
+    db.expire_if_due(key);
+    if !db.contains_key(key) {
+        db.insert(key.to_vec(), Entry::new(default()));
+    }
+    db.get_mut(key).unwrap()
+}
+
+/// Les snapshots antérieurs aux types de valeurs stockaient directement la chaîne dans `value`,
+/// sans échappement : elle est reprise telle quelle.
+fn value_compat<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Compat {
+        Legacy(String),
+        Typed(Value),
+    }
+    Ok(match Compat::deserialize(deserializer)? {
+        Compat::Legacy(s) => Value::String(s.into_bytes()),
+        Compat::Typed(value) => value,
+    })
+}
+
+/// Représente des octets quelconques sous forme de chaîne pour le snapshot JSON :
+/// l'UTF-8 valide est conservé, `\` devient `\\` et les autres octets deviennent `\xHH`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '\\' {
+                escaped.push_str("\\\\");
+            } else {
+                escaped.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+/// Inverse de `escape_bytes`
+pub fn unescape_bytes(escaped: &str) -> Vec<u8> {
+    let bytes = escaped.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) == Some(&b'\\') {
+                out.push(b'\\');
+                i += 2;
+                continue;
+            }
+            if bytes.get(i + 1) == Some(&b'x') {
+                if let Some(byte) = escaped.get(i + 2..i + 4).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    out.push(byte);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Sérialisation serde d'un `Vec<u8>` via `escape_bytes`
+pub mod escaped {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::escape_bytes(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(super::unescape_bytes(&s))
+    }
 }
 
-pub type Db = Arc<Mutex<HashMap<String, Entry>>>;
+/// Sérialisation serde d'une `HashMap` dont les clés sont des octets (les clés JSON doivent être des chaînes)
+pub mod escaped_keys {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<V: Serialize, S: Serializer>(map: &HashMap<Vec<u8>, V>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (super::escape_bytes(k), v)))
+    }
+
+    pub fn deserialize<'de, V: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Vec<u8>, V>, D::Error> {
+        let map = HashMap::<String, V>::deserialize(deserializer)?;
+        Ok(map.into_iter().map(|(k, v)| (super::unescape_bytes(&k), v)).collect())
+    }
+}
+
+/// Sérialisation serde d'une collection d'octets (`VecDeque`, `HashSet`...) via `escape_bytes`
+pub mod escaped_seq {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S: Serializer>(items: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        for<'a> &'a T: IntoIterator<Item = &'a Vec<u8>>,
+    {
+        serializer.collect_seq(items.into_iter().map(|item| super::escape_bytes(item)))
+    }
+
+    pub fn deserialize<'de, T: FromIterator<Vec<u8>>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let items = Vec::<String>::deserialize(deserializer)?;
+        Ok(items.iter().map(|item| super::unescape_bytes(item)).collect())
+    }
+}
+
+/// Sérialisation serde d'une `HashMap` d'octets vers octets (champs d'un hash)
+pub mod escaped_map {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(map: &HashMap<Vec<u8>, Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (super::escape_bytes(k), super::escape_bytes(v))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Vec<u8>, Vec<u8>>, D::Error> {
+        let map = HashMap::<String, String>::deserialize(deserializer)?;
+        Ok(map.iter().map(|(k, v)| (super::unescape_bytes(k), super::unescape_bytes(v))).collect())
+    }
+}
@@ -0,0 +1,190 @@
+// src/error.rs
+use crate::protocol::Reply;
+use std::fmt;
+
+/// Erreur d'exécution d'une commande, renvoyée au client sous forme de réponse d'erreur
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    /// Commande que le serveur ne connaît pas
+    UnknownCommand,
+    /// Nombre d'arguments incorrect pour la commande (nom en minuscules)
+    WrongArity(String),
+    /// Option ou combinaison d'options invalide
+    Syntax,
+    /// La clé contient une valeur d'un autre type que celui attendu par la commande
+    WrongType,
+    NoSuchKey,
+    IndexOutOfRange,
+    /// Valeur négative là où un nombre positif est attendu
+    OutOfRange,
+    NotInteger,
+    NotFloat,
+    /// Champ de hash non numérique pour HINCRBY / HINCRBYFLOAT
+    HashValueNotInteger,
+    HashValueNotFloat,
+    /// Bornes de ZRANGEBYSCORE / ZCOUNT invalides
+    MinMaxNotFloat,
+    /// Score NaN obtenu par ZINCRBY ou ZADD INCR (`inf` + `-inf`)
+    ScoreNaN,
+    /// Options incompatibles entre elles (message Redis complet, sans le préfixe ERR)
+    IncompatibleOptions(&'static str),
+    /// Identifiant de stream mal formé
+    InvalidStreamId,
+    /// Identifiant de XADD inférieur ou égal au dernier identifiant du stream
+    StreamIdTooSmall,
+    StreamIdZero,
+    /// XREAD sans autant d'identifiants que de clés (nom de la commande en minuscules)
+    UnbalancedStreams(String),
+    NegativeTimeout,
+    /// Clé ou groupe de consommateurs absent (clé, groupe)
+    NoGroup(String, String),
+    BusyGroup,
+    /// Sous-commande XGROUP sur une clé absente
+    XGroupKeyMissing,
+    /// `$` passé à XREADGROUP, qui n'a pas de sens pour un groupe
+    XReadGroupDollar,
+    /// Sous-commande inconnue (sous-commande telle que reçue, commande en majuscules)
+    UnknownSubcommand(String, String),
+    /// Numéro de base négatif ou trop grand
+    DbIndexOutOfRange,
+    /// Source et destination identiques (COPY, MOVE)
+    SameObject,
+    /// Curseur de SCAN qui n'est pas un entier positif
+    InvalidCursor,
+    /// Option inconnue (telle que reçue)
+    UnsupportedOption(String),
+    /// Durée d'expiration négative, nulle ou trop grande (nom de la commande en minuscules)
+    InvalidExpireTime(String),
+    Overflow,
+    NanOrInfinity,
+    /// Décalage négatif pour SETRANGE
+    OffsetOutOfRange,
+    /// Chaîne qui dépasserait 512 Mo
+    StringTooLong,
+    /// Position de bit négative, non numérique ou au-delà de la taille maximale d'une chaîne (512 Mo)
+    BitOffset,
+    /// Valeur de SETBIT autre que 0 ou 1
+    BitValue,
+    /// Bit recherché par BITPOS autre que 0 ou 1
+    BitposBit,
+    /// Type BITFIELD invalide (`i1` à `i64`, `u1` à `u63`)
+    BitfieldType,
+    BitopNotSingleSource,
+    /// Sous-commande d'écriture passée à BITFIELD_RO
+    BitfieldReadOnly,
+    /// Position hors des limites indexables (longitude, latitude)
+    InvalidCoordinates(f64, f64),
+    UnsupportedUnit,
+    /// COUNT nul ou négatif (GEOSEARCH)
+    CountNotPositive,
+    /// Membre de départ absent (GEOSEARCH FROMMEMBER)
+    GeoMemberMissing,
+    /// Valeur JSON mal formée (message de l'analyseur)
+    InvalidJson(String),
+    InvalidJsonPath(String),
+    /// Chemin de l'ancienne syntaxe sans correspondance
+    JsonPathMissing(String),
+    /// JSON.SET hors de la racine sur une clé absente
+    JsonNewObjectAtRoot,
+    /// Valeur JSON d'un autre type que celui attendu (attendu, trouvé)
+    JsonWrongPathType(&'static str, &'static str),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::WrongArity(command) => {
+                write!(f, "ERR wrong number of arguments for '{}' command", command)
+            },
+            CommandError::UnknownCommand => write!(f, "ERR Commande inconnue"),
+            CommandError::Syntax => write!(f, "ERR syntax error"),
+            CommandError::WrongType => {
+                write!(f, "WRONGTYPE Operation against a key holding the wrong kind of value")
+            },
+            CommandError::NoSuchKey => write!(f, "ERR no such key"),
+            CommandError::IndexOutOfRange => write!(f, "ERR index out of range"),
+            CommandError::OutOfRange => write!(f, "ERR value is out of range, must be positive"),
+            CommandError::NotInteger => write!(f, "ERR value is not an integer or out of range"),
+            CommandError::NotFloat => write!(f, "ERR value is not a valid float"),
+            CommandError::HashValueNotInteger => write!(f, "ERR hash value is not an integer"),
+            CommandError::HashValueNotFloat => write!(f, "ERR hash value is not a float"),
+            CommandError::MinMaxNotFloat => write!(f, "ERR min or max is not a float"),
+            CommandError::ScoreNaN => write!(f, "ERR resulting score is not a number (NaN)"),
+            CommandError::IncompatibleOptions(message) => write!(f, "ERR {}", message),
+            CommandError::InvalidStreamId => {
+                write!(f, "ERR Invalid stream ID specified as stream command argument")
+            },
+            CommandError::StreamIdTooSmall => {
+                write!(f, "ERR The ID specified in XADD is equal or smaller than the target stream top item")
+            },
+            CommandError::StreamIdZero => write!(f, "ERR The ID specified in XADD must be greater than 0-0"),
+            CommandError::UnbalancedStreams(command) => write!(
+                f,
+                "ERR Unbalanced '{}' list of streams: for each stream key an ID or '$' must be specified.",
+                command
+            ),
+            CommandError::NegativeTimeout => write!(f, "ERR timeout is negative"),
+            CommandError::NoGroup(key, group) => {
+                write!(f, "NOGROUP No such key '{}' or consumer group '{}'", key, group)
+            },
+            CommandError::BusyGroup => write!(f, "BUSYGROUP Consumer Group name already exists"),
+            CommandError::XGroupKeyMissing => write!(
+                f,
+                "ERR The XGROUP subcommand requires the key to exist. \
+                 Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically."
+            ),
+            CommandError::XReadGroupDollar => write!(
+                f,
+                "ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this \
+                 consumer by specifying a proper ID, or use the > ID to get new messages. \
+                 The $ ID would just return an empty result set."
+            ),
+            CommandError::UnknownSubcommand(subcommand, command) => {
+                write!(f, "ERR unknown subcommand '{}'. Try {} HELP.", subcommand, command)
+            },
+            CommandError::DbIndexOutOfRange => write!(f, "ERR DB index is out of range"),
+            CommandError::SameObject => write!(f, "ERR source and destination objects are the same"),
+            CommandError::InvalidCursor => write!(f, "ERR invalid cursor"),
+            CommandError::UnsupportedOption(option) => write!(f, "ERR Unsupported option {}", option),
+            CommandError::InvalidExpireTime(command) => {
+                write!(f, "ERR invalid expire time in '{}' command", command)
+            },
+            CommandError::Overflow => write!(f, "ERR increment or decrement would overflow"),
+            CommandError::NanOrInfinity => write!(f, "ERR increment would produce NaN or Infinity"),
+            CommandError::OffsetOutOfRange => write!(f, "ERR offset is out of range"),
+            CommandError::StringTooLong => {
+                write!(f, "ERR string exceeds maximum allowed size (proto-max-bulk-len)")
+            },
+            CommandError::BitOffset => write!(f, "ERR bit offset is not an integer or out of range"),
+            CommandError::BitValue => write!(f, "ERR bit is not an integer or out of range"),
+            CommandError::BitposBit => write!(f, "ERR The bit argument must be 1 or 0."),
+            CommandError::BitfieldType => write!(
+                f,
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+            ),
+            CommandError::BitopNotSingleSource => {
+                write!(f, "ERR BITOP NOT must be called with a single source key.")
+            },
+            CommandError::BitfieldReadOnly => write!(f, "ERR BITFIELD_RO only supports the GET subcommand"),
+            CommandError::InvalidCoordinates(lon, lat) => {
+                write!(f, "ERR invalid longitude,latitude pair {:.6},{:.6}", lon, lat)
+            },
+            CommandError::UnsupportedUnit => write!(f, "ERR unsupported unit provided. please use M, KM, FT, MI"),
+            CommandError::CountNotPositive => write!(f, "ERR COUNT must be > 0"),
+            CommandError::GeoMemberMissing => write!(f, "ERR could not decode requested zset member"),
+            CommandError::InvalidJson(message) => write!(f, "ERR invalid JSON: {}", message),
+            CommandError::InvalidJsonPath(path) => write!(f, "ERR invalid JSON path '{}'", path),
+            CommandError::JsonPathMissing(path) => write!(f, "ERR Path '{}' does not exist", path),
+            CommandError::JsonNewObjectAtRoot => write!(f, "ERR new objects must be created at the root"),
+            CommandError::JsonWrongPathType(expected, found) => {
+                write!(f, "ERR wrong type of path value - expected {} but found {}", expected, found)
+            },
+        }
+    }
+}
+
+impl From<CommandError> for Reply {
+    fn from(error: CommandError) -> Reply {
+        Reply::Error(error.to_string())
+    }
+}
@@ -0,0 +1,116 @@
+// src/expiry.rs
+//! Expiration active des clés.
+//!
+//! Chaque date d'expiration posée est rangée dans un tas min (`ExpiryIndex`). Les entrées du tas ne
+//! sont pas retirées quand une clé est supprimée ou change d'expiration : elles sont ignorées au
+//! moment où elles sortent, si l'entrée de la base ne porte plus la même date.
+//!
+//! Comme dans Redis, un cycle est lancé dix fois par seconde et traite les clés par lots, en
+//! relâchant le verrou entre deux lots, dans la limite d'un budget de temps. Contrairement à Redis,
+//! les lots ne sont pas des échantillons de clés tirées au hasard, répétés tant que plus de 25 %
+//! d'entre elles avaient expiré : le cycle vide délibérément le tas dans l'ordre des échéances.
+//! Chaque échéance qui en sort est déjà dépassée, si bien que la règle des 25 % serait toujours
+//! vérifiée ; le cycle continue donc tant que le lot précédent était plein.
+
+use crate::db::{Db, DATABASES};
+use crate::protocol::format_command;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Nombre d'échéances examinées par lot
+const BATCH: usize = 20;
+/// Intervalle entre deux cycles
+const CYCLE_INTERVAL: Duration = Duration::from_millis(100);
+/// Durée maximale d'un cycle (25 % de l'intervalle, comme Redis)
+const CYCLE_BUDGET: Duration = Duration::from_millis(25);
+/// Taille en dessous de laquelle le tas n'est jamais nettoyé
+const MIN_COMPACT_LEN: usize = 1024;
+
+/// Tas min des échéances (date, clé)
+#[derive(Debug, Default)]
+pub struct ExpiryIndex {
+    heap: BinaryHeap<Reverse<(SystemTime, Vec<u8>)>>,
+    /// Taille du tas après le dernier nettoyage
+    compacted_len: usize,
+}
+
+impl ExpiryIndex {
+    pub fn push(&mut self, expire_at: SystemTime, key: &[u8]) {
+        self.heap.push(Reverse((expire_at, key.to_vec())));
+    }
+
+    /// Retire et renvoie l'échéance la plus proche si elle est dépassée à `now`
+    pub fn pop_due(&mut self, now: SystemTime) -> Option<(SystemTime, Vec<u8>)> {
+        match self.heap.peek() {
+            Some(Reverse((expire_at, _))) if *expire_at < now => self.heap.pop().map(|Reverse(due)| due),
+            _ => None,
+        }
+    }
+
+    /// Vrai quand le tas a doublé depuis le dernier nettoyage : les entrées périmées y sont
+    /// peut-être nombreuses, et les retirer coûte en moyenne O(1) par insertion.
+    pub fn needs_compaction(&self) -> bool {
+        self.heap.len() > MIN_COMPACT_LEN.max(2 * self.compacted_len)
+    }
+
+    /// Ne garde que les échéances pour lesquelles `keep` renvoie vrai
+    pub fn retain(&mut self, mut keep: impl FnMut(SystemTime, &[u8]) -> bool) {
+        self.heap.retain(|Reverse((expire_at, key))| keep(*expire_at, key));
+        self.compacted_len = self.heap.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+/// Boucle du thread d'expiration active
+pub fn run_active_expiry(db: Db, aof_tx: Sender<String>) {
+    loop {
+        thread::sleep(CYCLE_INTERVAL);
+        expire_cycle(&db, &aof_tx);
+    }
+}
+
+/// Un cycle d'expiration active. Chaque clé supprimée est journalisée sous forme de `DELETE`.
+///
+/// Les bases sont parcourues l'une après l'autre. Dans chacune, les échéances dépassées sont
+/// retirées du tas par lots de `BATCH`, de la plus ancienne à la plus récente, jusqu'à ce qu'il
+/// n'en reste plus : il n'y a pas d'échantillonnage aléatoire. Quand le budget de temps est
+/// épuisé, le cycle suivant reprend à la base où celui-ci s'est arrêté.
+///
+/// Renvoie le nombre de clés supprimées.
+pub fn expire_cycle(db: &Db, aof_tx: &Sender<String>) -> usize {
+    let start = Instant::now();
+    let mut deleted = 0;
+    for _ in 0..DATABASES {
+        loop {
+            let (examined, expired) = {
+                let mut dbs = db.lock().unwrap();
+                let index = dbs.expiry_index;
+                let (examined, expired) = dbs.keyspace_mut(index).expire_due(SystemTime::now(), BATCH);
+                for key in &expired {
+                    dbs.log(index, format_command(&[b"DELETE", key]), aof_tx);
+                }
+                (examined, expired.len())
+            };
+            deleted += expired;
+            if start.elapsed() >= CYCLE_BUDGET {
+                return deleted;
+            }
+            if examined < BATCH {
+                break;
+            }
+        }
+        let mut dbs = db.lock().unwrap();
+        dbs.expiry_index = (dbs.expiry_index + 1) % DATABASES;
+    }
+    deleted
+}
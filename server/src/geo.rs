@@ -0,0 +1,160 @@
+// src/geo.rs
+//! Géohash sur 52 bits et calculs de distance des index géographiques.
+//!
+//! Une position est rangée dans un ensemble trié avec pour score son géohash : 26 bits de latitude et
+//! 26 bits de longitude entrelacés, ce qui tient exactement dans un `f64`. Comme dans Redis, la
+//! latitude est limitée à ±85,05112878° (limites de la projection de Mercator).
+
+pub const LON_MIN: f64 = -180.0;
+pub const LON_MAX: f64 = 180.0;
+pub const LAT_MIN: f64 = -85.05112878;
+pub const LAT_MAX: f64 = 85.05112878;
+
+/// Bits par coordonnée dans les scores
+const STEP: u32 = 26;
+/// Rayon terrestre utilisé par Redis pour la formule de haversine
+const EARTH_RADIUS: f64 = 6372797.560856;
+const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Vrai si la position peut être indexée
+pub fn is_valid(lon: f64, lat: f64) -> bool {
+    (LON_MIN..=LON_MAX).contains(&lon) && (LAT_MIN..=LAT_MAX).contains(&lat)
+}
+
+/// Position de la cellule (entiers sur `step` bits) contenant `value` dans `[min, max]`
+fn cell(value: f64, min: f64, max: f64, step: u32) -> u64 {
+    let cells = (1u64 << step) as f64;
+    (((value - min) / (max - min)) * cells).clamp(0.0, cells - 1.0) as u64
+}
+
+/// Entrelace les bits : la latitude occupe les bits pairs, la longitude les bits impairs
+fn interleave(lat: u64, lon: u64) -> u64 {
+    (0..32).fold(0, |bits, i| bits | ((lat >> i) & 1) << (2 * i) | ((lon >> i) & 1) << (2 * i + 1))
+}
+
+fn deinterleave(bits: u64) -> (u64, u64) {
+    (0..32).fold((0, 0), |(lat, lon), i| (lat | ((bits >> (2 * i)) & 1) << i, lon | ((bits >> (2 * i + 1)) & 1) << i))
+}
+
+fn encode_step(lon: f64, lat: f64, step: u32) -> u64 {
+    interleave(cell(lat, LAT_MIN, LAT_MAX, step), cell(lon, LON_MIN, LON_MAX, step))
+}
+
+/// Géohash sur 52 bits d'une position valide
+pub fn encode(lon: f64, lat: f64) -> u64 {
+    encode_step(lon, lat, STEP)
+}
+
+/// Centre de la cellule désignée par un géohash de 52 bits : (longitude, latitude)
+pub fn decode(hash: u64) -> (f64, f64) {
+    let (lat, lon) = deinterleave(hash);
+    let cells = (1u64 << STEP) as f64;
+    let center = |index: u64, min: f64, max: f64| {
+        let low = min + index as f64 / cells * (max - min);
+        let high = min + (index + 1) as f64 / cells * (max - min);
+        ((low + high) / 2.0).clamp(min, max)
+    };
+    (center(lon, LON_MIN, LON_MAX), center(lat, LAT_MIN, LAT_MAX))
+}
+
+/// Géohash standard en base 32 (11 caractères), calculé sur les latitudes -90 à 90 comme le fait GEOHASH
+pub fn to_base32(hash: u64) -> String {
+    let (lon, lat) = decode(hash);
+    let bits = interleave(cell(lat, -90.0, 90.0, STEP), cell(lon, LON_MIN, LON_MAX, STEP));
+    (0..11)
+        .map(|i| {
+            // 52 bits ne remplissent que 10 caractères et demi : le dernier vaut toujours '0'
+            let index = if i == 10 { 0 } else { (bits >> (52 - (i + 1) * 5)) & 0x1f };
+            BASE32[index as usize] as char
+        })
+        .collect()
+}
+
+/// Distance en mètres entre deux positions (formule de haversine)
+pub fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+    let a = u * u + lat1.cos() * lat2.cos() * v * v;
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Zone de recherche autour d'un centre, en mètres
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    /// Distance du centre `(lon, lat)` au point si celui-ci est dans la zone.
+    ///
+    /// Pour un rectangle, l'écart de latitude se mesure sur le méridien et l'écart de longitude
+    /// le long du parallèle du point, comme dans Redis.
+    pub fn distance_if_inside(&self, lon: f64, lat: f64, point_lon: f64, point_lat: f64) -> Option<f64> {
+        let inside = match *self {
+            Shape::Radius(radius) => return Some(distance(lon, lat, point_lon, point_lat)).filter(|d| *d <= radius),
+            Shape::Box { width, height } => {
+                EARTH_RADIUS * (point_lat - lat).to_radians().abs() <= height / 2.0
+                    && distance(lon, point_lat, point_lon, point_lat) <= width / 2.0
+            },
+        };
+        inside.then(|| distance(lon, lat, point_lon, point_lat))
+    }
+
+    /// Demi-étendues de la zone autour du centre, en degrés de latitude et de longitude
+    fn extent(&self, lat: f64) -> (f64, f64) {
+        let (lat_delta, half_width) = match *self {
+            Shape::Radius(radius) => (radius / EARTH_RADIUS, radius),
+            Shape::Box { width, height } => (height / 2.0 / EARTH_RADIUS, width / 2.0),
+        };
+        // Longitude : au parallèle le plus proche du pôle, là où un degré est le plus court
+        let extreme_lat = (lat.to_radians().abs() + lat_delta).min(std::f64::consts::FRAC_PI_2);
+        let ratio = match *self {
+            Shape::Radius(_) => lat_delta.sin() / extreme_lat.cos(),
+            Shape::Box { .. } => (half_width / 2.0 / EARTH_RADIUS).sin() / extreme_lat.cos(),
+        };
+        let lon_delta = match *self {
+            _ if ratio >= 1.0 => std::f64::consts::PI,
+            Shape::Radius(_) => ratio.asin(),
+            Shape::Box { .. } => 2.0 * ratio.asin(),
+        };
+        (lat_delta.to_degrees(), lon_delta.to_degrees())
+    }
+
+    /// Intervalles de scores `[min, max)` qui couvrent la zone centrée sur `(lon, lat)`.
+    ///
+    /// On prend la plus fine précision dont les cellules sont au moins aussi grandes que la zone :
+    /// la cellule du centre et ses huit voisines la contiennent alors entièrement.
+    pub fn score_ranges(&self, lon: f64, lat: f64) -> Vec<(u64, u64)> {
+        let (lat_delta, lon_delta) = self.extent(lat);
+        let step = (1..=STEP)
+            .rev()
+            .find(|step| {
+                let cells = (1u64 << step) as f64;
+                (LAT_MAX - LAT_MIN) / cells >= lat_delta && (LON_MAX - LON_MIN) / cells >= lon_delta
+            })
+            .unwrap_or(1);
+
+        let cells = 1i64 << step;
+        let (center_lat, center_lon) = deinterleave(encode_step(lon, lat, step));
+        let shift = 2 * (STEP - step);
+        let mut ranges = Vec::with_capacity(9);
+        for lat_offset in -1..=1 {
+            let cell_lat = center_lat as i64 + lat_offset;
+            if !(0..cells).contains(&cell_lat) {
+                continue;
+            }
+            for lon_offset in -1..=1 {
+                // La longitude fait le tour du globe
+                let cell_lon = (center_lon as i64 + lon_offset).rem_euclid(cells);
+                let hash = interleave(cell_lat as u64, cell_lon as u64);
+                let range = (hash << shift, (hash + 1) << shift);
+                if !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+        }
+        ranges
+    }
+}
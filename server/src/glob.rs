@@ -0,0 +1,74 @@
+// src/glob.rs
+//! Motifs de style glob de KEYS et SCAN MATCH, avec la syntaxe de Redis :
+//! `*` (n'importe quelle suite d'octets), `?` (un octet), `[abc]`, `[^abc]`, `[a-z]` et `\x`
+//! pour un caractère littéral.
+
+/// Vrai si `string` correspond entièrement à `pattern`
+pub fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Dernière étoile rencontrée : position après l'étoile et position dans la chaîne où reprendre
+    let mut star: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, s));
+            continue;
+        }
+        if let Some(next) = match_one(pattern, p, string[s]) {
+            p = next;
+            s += 1;
+            continue;
+        }
+        // Échec : l'étoile précédente absorbe un octet de plus
+        match star {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                star = Some((star_p, s));
+            },
+            None => return false,
+        }
+    }
+    pattern[p.min(pattern.len())..].iter().all(|c| *c == b'*')
+}
+
+/// Compare l'élément du motif qui commence en `p` à l'octet `c`.
+///
+/// Renvoie la position de l'élément suivant si l'octet correspond.
+fn match_one(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match *pattern.get(p)? {
+        b'?' => Some(p + 1),
+        b'[' => match_class(pattern, p + 1, c),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+/// Classe `[...]` dont le contenu commence en `p`. Une classe non fermée s'étend jusqu'à la fin du motif.
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> Option<usize> {
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut found = false;
+    while p < pattern.len() {
+        match pattern[p] {
+            b']' => break,
+            b'\\' if p + 1 < pattern.len() => {
+                found |= pattern[p + 1] == c;
+                p += 2;
+            },
+            low if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let high = pattern[p + 2];
+                let (low, high) = if low <= high { (low, high) } else { (high, low) };
+                found |= (low..=high).contains(&c);
+                p += 3;
+            },
+            literal => {
+                found |= literal == c;
+                p += 1;
+            },
+        }
+    }
+    (found != negate).then_some(p + 1)
+}
@@ -0,0 +1,235 @@
+// src/hyperloglog.rs
+//! HyperLogLog : estimation du nombre d'éléments distincts avec 16384 registres (erreur type de 0,81 %).
+//!
+//! Mêmes paramètres, même fonction de hachage (MurmurHash64A) et même estimateur que Redis :
+//! un ensemble d'éléments donne les mêmes registres d'un redémarrage à l'autre.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Nombre de bits de l'empreinte utilisés pour choisir le registre
+const P: u32 = 14;
+/// Nombre de registres
+pub const REGISTERS: usize = 1 << P;
+/// Bits restants, dont on compte les zéros de poids faible
+const Q: u32 = 64 - P;
+/// Au-delà de ce nombre de registres non nuls, la table complète (un octet par registre) est plus compacte
+const SPARSE_MAX: usize = 3000;
+
+/// Registres d'un HyperLogLog. La représentation creuse ne garde que les registres non nuls ;
+/// elle passe en représentation dense quand elle devient trop grande.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HyperLogLog {
+    Sparse(#[serde(with = "sparse")] BTreeMap<u16, u8>),
+    Dense(#[serde(with = "dense")] Vec<u8>),
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog::Sparse(BTreeMap::new())
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> HyperLogLog {
+        HyperLogLog::default()
+    }
+
+    /// Nom de la représentation courante (`sparse` ou `dense`)
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            HyperLogLog::Sparse(_) => "sparse",
+            HyperLogLog::Dense(_) => "dense",
+        }
+    }
+
+    fn register(&self, index: usize) -> u8 {
+        match self {
+            HyperLogLog::Sparse(registers) => registers.get(&(index as u16)).copied().unwrap_or(0),
+            HyperLogLog::Dense(registers) => registers[index],
+        }
+    }
+
+    /// Porte le registre `index` à `value` s'il est plus petit. Renvoie vrai si le registre a changé.
+    fn raise(&mut self, index: usize, value: u8) -> bool {
+        if self.register(index) >= value {
+            return false;
+        }
+        match self {
+            HyperLogLog::Sparse(registers) => {
+                registers.insert(index as u16, value);
+                if registers.len() > SPARSE_MAX {
+                    let mut dense = vec![0; REGISTERS];
+                    for (index, value) in registers.iter() {
+                        dense[*index as usize] = *value;
+                    }
+                    *self = HyperLogLog::Dense(dense);
+                }
+            },
+            HyperLogLog::Dense(registers) => registers[index] = value,
+        }
+        true
+    }
+
+    /// Ajoute un élément. Renvoie vrai si l'estimation a pu changer (un registre a été modifié).
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur_hash64a(element, 0xadc8_3b19);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+        // Le bit sentinelle borne le compte à Q + 1
+        let rest = (hash >> P) | (1 << Q);
+        self.raise(index, rest.trailing_zeros() as u8 + 1)
+    }
+
+    /// Union : chaque registre prend le maximum des deux
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        match other {
+            HyperLogLog::Sparse(registers) => {
+                for (index, value) in registers {
+                    self.raise(*index as usize, *value);
+                }
+            },
+            HyperLogLog::Dense(registers) => {
+                for (index, value) in registers.iter().enumerate().filter(|(_, value)| **value > 0) {
+                    self.raise(index, *value);
+                }
+            },
+        }
+    }
+
+    /// Nombre estimé d'éléments distincts (estimateur d'Otmar Ertl, celui de Redis)
+    pub fn count(&self) -> u64 {
+        let mut histogram = [0u32; Q as usize + 2];
+        match self {
+            HyperLogLog::Sparse(registers) => {
+                histogram[0] = (REGISTERS - registers.len()) as u32;
+                for value in registers.values() {
+                    histogram[*value as usize] += 1;
+                }
+            },
+            HyperLogLog::Dense(registers) => {
+                for value in registers {
+                    histogram[*value as usize] += 1;
+                }
+            },
+        }
+
+        let m = REGISTERS as f64;
+        let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+        for k in (1..=Q as usize).rev() {
+            z += histogram[k] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (0.5 / std::f64::consts::LN_2 * m * m / z).round() as u64
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// MurmurHash64A (Austin Appleby), lu en petit-boutiste comme dans Redis
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// Dans le snapshot, les registres creux sont une liste de paires `[indice, valeur]`
+mod sparse {
+    use super::{Deserialize, Deserializer, Serializer, Q, REGISTERS};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(registers: &BTreeMap<u16, u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(registers.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<u16, u8>, D::Error> {
+        let registers = Vec::<(u16, u8)>::deserialize(deserializer)?;
+        if registers.iter().any(|(index, value)| *index as usize >= REGISTERS || *value as u32 > Q + 1) {
+            return Err(serde::de::Error::custom("registres HyperLogLog invalides"));
+        }
+        Ok(registers.into_iter().collect())
+    }
+}
+
+/// Dans le snapshot, la table dense est une chaîne hexadécimale (deux caractères par registre)
+mod dense {
+    use super::{Deserialize, Deserializer, Serializer, Q, REGISTERS};
+    use std::fmt::Write;
+
+    pub fn serialize<S: Serializer>(registers: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(registers.len() * 2);
+        for value in registers {
+            write!(hex, "{:02x}", value).unwrap();
+        }
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let invalid = || serde::de::Error::custom("registres HyperLogLog invalides");
+        if hex.len() != REGISTERS * 2 {
+            return Err(invalid());
+        }
+        (0..REGISTERS)
+            .map(|i| {
+                let value = hex.get(2 * i..2 * i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok());
+                value.filter(|value| *value as u32 <= Q + 1).ok_or_else(invalid)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,234 @@
+// src/json_path.rs
+//! Chemins des documents JSON, dans les deux syntaxes de RedisJSON.
+//!
+//! - JSONPath : `$`, `$.a.b`, `$["a"][0]`, `$.list[-1]`, `$.*`, `$[*]`, `$..name`. Toutes les
+//!   correspondances sont renvoyées.
+//! - Ancienne syntaxe : `.`, `.a.b`, `a[0]`. Seule la première correspondance compte.
+
+use serde_json::Value;
+
+/// Étape d'un chemin
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    /// `..name` : le membre `name` à n'importe quelle profondeur
+    Descendant(String),
+    /// `..*` : tous les descendants
+    AllDescendants,
+}
+
+/// Position concrète d'une valeur dans un document
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Location {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    segments: Vec<Segment>,
+    /// Vrai pour un chemin JSONPath (`$...`), faux pour l'ancienne syntaxe
+    pub json_path: bool,
+}
+
+impl Path {
+    /// Analyse un chemin ; `None` si sa syntaxe est invalide
+    pub fn parse(path: &str) -> Option<Path> {
+        let (json_path, rest) = match path.strip_prefix('$') {
+            Some(rest) => (true, rest.to_string()),
+            None if path == "." => (false, String::new()),
+            None if path.starts_with('.') || path.starts_with('[') => (false, path.to_string()),
+            None => (false, format!(".{}", path)),
+        };
+        let chars: Vec<char> = rest.chars().collect();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '.' if chars.get(i + 1) == Some(&'.') => {
+                    i += 2;
+                    let (name, next) = read_name(&chars, i)?;
+                    segments.push(if name == "*" { Segment::AllDescendants } else { Segment::Descendant(name) });
+                    i = next;
+                },
+                '.' => {
+                    let (name, next) = read_name(&chars, i + 1)?;
+                    segments.push(if name == "*" { Segment::Wildcard } else { Segment::Key(name) });
+                    i = next;
+                },
+                '[' if matches!(chars.get(i + 1), Some('"' | '\'')) => {
+                    let (key, next) = read_quoted(&chars, i + 1)?;
+                    if chars.get(next) != Some(&']') {
+                        return None;
+                    }
+                    segments.push(Segment::Key(key));
+                    i = next + 1;
+                },
+                '[' => {
+                    let close = i + chars[i..].iter().position(|c| *c == ']')?;
+                    let inner: String = chars[i + 1..close].iter().collect();
+                    segments.push(match inner.trim() {
+                        "*" => Segment::Wildcard,
+                        index => Segment::Index(index.parse().ok()?),
+                    });
+                    i = close + 1;
+                },
+                _ => return None,
+            }
+        }
+        Some(Path { segments, json_path })
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Dernière étape si c'est un nom de membre, pour créer ce membre quand il manque
+    pub fn last_key(&self) -> Option<&str> {
+        match self.segments.last() {
+            Some(Segment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Chemin sans sa dernière étape
+    pub fn parent(&self) -> Path {
+        let mut parent = self.clone();
+        parent.segments.pop();
+        parent
+    }
+
+    /// Positions de toutes les valeurs désignées par le chemin, dans l'ordre du document
+    pub fn locate(&self, document: &Value) -> Vec<Vec<Location>> {
+        let mut found = vec![(Vec::new(), document)];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for (location, value) in found {
+                match segment {
+                    Segment::Key(key) => {
+                        if let Some(child) = value.as_object().and_then(|object| object.get(key)) {
+                            next.push((child_location(&location, Location::Key(key.clone())), child));
+                        }
+                    },
+                    Segment::Index(index) => {
+                        if let Some(array) = value.as_array() {
+                            let index = if *index < 0 { array.len() as i64 + index } else { *index };
+                            if let Some(child) = usize::try_from(index).ok().and_then(|i| array.get(i)) {
+                                next.push((child_location(&location, Location::Index(index as usize)), child));
+                            }
+                        }
+                    },
+                    Segment::Wildcard => push_children(&location, value, &mut next),
+                    Segment::Descendant(key) => descend(&location, value, &mut |location, value| {
+                        if let Some(child) = value.as_object().and_then(|object| object.get(key)) {
+                            next.push((child_location(location, Location::Key(key.clone())), child));
+                        }
+                    }),
+                    Segment::AllDescendants => {
+                        descend(&location, value, &mut |location, value| push_children(location, value, &mut next))
+                    },
+                }
+            }
+            found = next;
+        }
+        found.into_iter().map(|(location, _)| location).collect()
+    }
+}
+
+/// Nom après un `.` : jusqu'au prochain `.` ou `[`
+fn read_name(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let end = chars[start..].iter().position(|c| *c == '.' || *c == '[').map_or(chars.len(), |n| start + n);
+    (end > start).then(|| (chars[start..end].iter().collect(), end))
+}
+
+/// Chaîne entre guillemets doubles (échappements JSON) ou simples (`\'` et `\\`) commençant à `start`
+fn read_quoted(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let quote = chars[start];
+    let mut end = start + 1;
+    while *chars.get(end)? != quote {
+        end += if chars[end] == '\\' { 2 } else { 1 };
+    }
+    let literal: String = chars[start..=end].iter().collect();
+    let key = if quote == '"' {
+        serde_json::from_str(&literal).ok()?
+    } else {
+        literal[1..literal.len() - 1].replace("\\'", "'").replace("\\\\", "\\")
+    };
+    Some((key, end + 1))
+}
+
+fn child_location(parent: &[Location], step: Location) -> Vec<Location> {
+    let mut location = parent.to_vec();
+    location.push(step);
+    location
+}
+
+fn push_children<'a>(location: &[Location], value: &'a Value, out: &mut Vec<(Vec<Location>, &'a Value)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object {
+                out.push((child_location(location, Location::Key(key.clone())), child));
+            }
+        },
+        Value::Array(array) => {
+            for (index, child) in array.iter().enumerate() {
+                out.push((child_location(location, Location::Index(index)), child));
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Appelle `visit` sur `value` puis sur chacun de ses descendants (parcours en profondeur)
+fn descend<'a>(location: &[Location], value: &'a Value, visit: &mut impl FnMut(&[Location], &'a Value)) {
+    visit(location, value);
+    let mut children = Vec::new();
+    push_children(location, value, &mut children);
+    for (child_location, child) in children {
+        descend(&child_location, child, visit);
+    }
+}
+
+/// Valeur à une position concrète
+pub fn get<'a>(document: &'a Value, location: &[Location]) -> Option<&'a Value> {
+    location.iter().try_fold(document, |value, step| match step {
+        Location::Key(key) => value.as_object()?.get(key),
+        Location::Index(index) => value.as_array()?.get(*index),
+    })
+}
+
+pub fn get_mut<'a>(document: &'a mut Value, location: &[Location]) -> Option<&'a mut Value> {
+    location.iter().try_fold(document, |value, step| match step {
+        Location::Key(key) => value.as_object_mut()?.get_mut(key),
+        Location::Index(index) => value.as_array_mut()?.get_mut(*index),
+    })
+}
+
+/// Supprime la valeur à une position concrète (autre que la racine). Renvoie vrai si elle existait.
+pub fn remove(document: &mut Value, location: &[Location]) -> bool {
+    let Some((last, parent)) = location.split_last() else {
+        return false;
+    };
+    match (get_mut(document, parent), last) {
+        (Some(Value::Object(object)), Location::Key(key)) => object.remove(key).is_some(),
+        (Some(Value::Array(array)), Location::Index(index)) if *index < array.len() => {
+            array.remove(*index);
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Chemin JSONPath normalisé d'une position concrète, par exemple `$["a"][0]`
+pub fn to_path(location: &[Location]) -> String {
+    let mut path = String::from("$");
+    for step in location {
+        match step {
+            Location::Key(key) => path.push_str(&format!("[{}]", Value::String(key.clone()))),
+            Location::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
@@ -1,4 +1,14 @@
 // src/lib.rs
+pub mod commands;
 pub mod db;
+pub mod error;
+pub mod expiry;
+pub mod geo;
+pub mod glob;
+pub mod hyperloglog;
+pub mod json_path;
 pub mod persistence;
+pub mod protocol;
 pub mod server;
+pub mod sorted_set;
+pub mod stream;
@@ -1,11 +1,10 @@
 // src/main.rs
-use redust::db::Db;
+use redust::db::{Databases, Db};
 use redust::server::run_server;
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 fn main() {
-    let db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
 
     redust::persistence::restore_state(&db);
 
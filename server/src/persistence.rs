@@ -1,23 +1,44 @@
 // src/persistence.rs
-use crate::db::{Db, Entry};
+use crate::db::{Databases, Db, Entry, Value};
+use crate::protocol::{parse_arg, split_args};
+use crate::commands::{parse_db_index, process_command_parts};
 use serde_json;
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::sync::mpsc::{Receiver, TryRecvError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufWriter, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::time::{Duration, Instant, SystemTime};
 use std::thread::sleep;
 
+/// Intervalle entre deux snapshots
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Sauvegarde l'état complet des bases, puis vide l'AOF dont toutes les lignes y sont reflétées.
+///
+/// Sans cela, le rejeu de l'AOF après le chargement du snapshot appliquerait une seconde fois les
+/// commandes qui ne sont pas idempotentes (LPUSH, APPEND...). En fonctionnement, c'est le thread
+/// de l'AOF qui l'appelle (voir `run_aof_writer`), pour qu'aucune ligne déjà prise en compte
+/// n'attende encore d'être écrite.
 pub fn snapshot(db: &Db) {
-    let db = db.lock().unwrap();
-    let file = File::create("snapshot.json").unwrap();
-    serde_json::to_writer(file, &*db).unwrap();
+    save(&mut db.lock().unwrap());
+}
+
+/// Écrit le snapshot puis vide l'AOF ; le verrou des bases est tenu, donc aucune ligne ne s'ajoute entre-temps
+fn save(dbs: &mut Databases) {
+    // Fichier temporaire puis renommage : un arrêt brutal laisse l'ancien snapshot intact
+    let mut file = BufWriter::new(File::create("snapshot.json.tmp").unwrap());
+    serde_json::to_writer(&mut file, &*dbs).unwrap();
+    file.into_inner().unwrap().sync_all().unwrap();
+    fs::rename("snapshot.json.tmp", "snapshot.json").unwrap();
+
+    // L'AOF repart de zéro, donc de la base 0 au rejeu
+    OpenOptions::new().create(true).write(true).truncate(true).open("appendonly.aof").unwrap();
+    dbs.set_aof_index(0);
     println!("Snapshot sauvegardé.");
 }
 
 pub fn restore_state(db: &Db) {
-    if let Ok(file) = File::open("snapshot.json") {
-        if let Ok(snapshot_data) = serde_json::from_reader::<_, HashMap<String, Entry>>(file) {
+    if let Ok(data) = fs::read("snapshot.json") {
+        if let Ok(snapshot_data) = Databases::from_snapshot(&data) {
             let mut db_lock = db.lock().unwrap();
             *db_lock = snapshot_data;
             println!("Snapshot chargé avec succès.");
@@ -28,57 +49,121 @@ pub fn restore_state(db: &Db) {
         println!("Aucun snapshot trouvé.");
     }
 
-    if let Ok(file) = File::open("appendonly.aof") {
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            if let Ok(cmd_line) = line {
-                apply_command(&cmd_line, db);
-            }
+    if let Ok(mut data) = fs::read("appendonly.aof") {
+        // La fin incomplète est retirée du fichier avant que `run_aof_writer` ne le rouvre :
+        // sinon les lignes ajoutées après le redémarrage compteraient dans la transaction inachevée
+        let complete = complete_len(&data);
+        if complete < data.len() {
+            eprintln!("AOF tronqué à {} octets (fin incomplète de {} octets).", complete, data.len() - complete);
+            let file = OpenOptions::new().write(true).open("appendonly.aof").unwrap();
+            file.set_len(complete as u64).unwrap();
+            data.truncate(complete);
         }
+        replay(data.lines().map_while(Result::ok), db);
         println!("AOF appliqué avec succès.");
     } else {
         println!("Aucun AOF trouvé.");
     }
 }
 
-fn apply_command(command: &str, db: &Db) {
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    if parts.is_empty() {
-        return;
+/// Longueur de l'AOF sans sa fin incomplète, laissée par un arrêt brutal : une transaction dont le
+/// `EXEC` n'a pas été écrit, ou une dernière ligne sans saut de ligne.
+fn complete_len(data: &[u8]) -> usize {
+    let (mut complete, mut offset) = (0, 0);
+    let mut in_transaction = false;
+    for line in data.split_inclusive(|&byte| byte == b'\n') {
+        offset += line.len();
+        match line.strip_suffix(b"\n") {
+            None => break,
+            Some(b"MULTI") => in_transaction = true,
+            Some(b"EXEC") => {
+                in_transaction = false;
+                complete = offset;
+            },
+            Some(_) if !in_transaction => complete = offset,
+            Some(_) => {},
+        }
+    }
+    complete
+}
+
+/// Rejoue des lignes de l'AOF dans l'ordre.
+///
+/// Les lignes comprises entre `MULTI` et `EXEC` (écritures d'une transaction) ne sont rejouées
+/// qu'une fois leur `EXEC` atteint : une transaction interrompue par un arrêt brutal pendant
+/// l'écriture de l'AOF est ignorée en entier. Renvoie le nombre de lignes ainsi ignorées.
+///
+/// Comme dans Redis, les clés expirées ne sont pas supprimées pendant le rejeu : une commande
+/// journalisée après leur `PEXPIREAT` les retrouve telles qu'elles étaient.
+pub fn replay(lines: impl IntoIterator<Item = String>, db: &Db) -> usize {
+    db.lock().unwrap().set_loading(true);
+    let mut transaction: Option<Vec<String>> = None;
+    let mut skipped = 0;
+    for line in lines {
+        match line.as_str() {
+            "MULTI" => {
+                // Un MULTI avant le EXEC du précédent : la transaction précédente est incomplète
+                skipped += transaction.replace(Vec::new()).map_or(0, |unfinished| unfinished.len());
+            },
+            "EXEC" => {
+                for line in transaction.take().unwrap_or_default() {
+                    apply_command(&line, db);
+                }
+            },
+            _ => match &mut transaction {
+                Some(pending) => pending.push(line),
+                None => apply_command(&line, db),
+            },
+        }
     }
+    skipped += transaction.map_or(0, |pending| pending.len());
+    db.lock().unwrap().set_loading(false);
+    if skipped > 0 {
+        eprintln!("Transaction incomplète ignorée ({} lignes).", skipped);
+    }
+    skipped
+}
 
-    match parts[0].to_uppercase().as_str() {
-        "SET" | "UPDATE" => {
-            if parts.len() >= 3 {
-                let key = parts[1].to_string();
-                let value = parts[2].to_string();
-                let expire_at = if parts.len() >= 5 && parts[3].to_uppercase() == "TTL" {
-                    if let Ok(ts) = parts[4].parse::<u64>() {
-                        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(ts))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-                let entry = Entry { value, expire_at };
-                let mut db_lock = db.lock().unwrap();
-                db_lock.insert(key, entry);
+/// Rejoue une ligne de l'AOF (arguments au format `split_args`, donc éventuellement entre guillemets)
+///
+/// Les commandes sont rejouées par le même code que celles des clients, dans la base désignée par
+/// le dernier `SELECT` rejoué, sauf les anciennes lignes `SET`/`UPDATE` dont le TTL était un
+/// timestamp absolu en secondes.
+pub fn apply_command(command: &str, db: &Db) {
+    let parts = match split_args(command.as_bytes()) {
+        Some(parts) if !parts.is_empty() => parts,
+        _ => return,
+    };
+
+    let mut db_lock = db.lock().unwrap();
+    let index = db_lock.aof_index();
+    match String::from_utf8_lossy(&parts[0]).to_uppercase().as_str() {
+        "SELECT" if parts.len() == 2 => {
+            if let Ok(index) = parse_db_index(&parts[1]) {
+                db_lock.set_aof_index(index);
             }
         },
-        "DELETE" => {
-            if parts.len() >= 2 {
-                let key = parts[1].to_string();
-                let mut db_lock = db.lock().unwrap();
-                db_lock.remove(&key);
-            }
+        "SET" | "UPDATE" if parts.len() == 3 || (parts.len() == 5 && parts[3].eq_ignore_ascii_case(b"TTL")) => {
+            let key = parts[1].clone();
+            let value = parts[2].clone();
+            let expire_at = if parts.len() == 5 {
+                parse_arg::<u64>(&parts[4]).map(|ts| SystemTime::UNIX_EPOCH + Duration::from_secs(ts))
+            } else {
+                None
+            };
+            let entry = Entry::with_expiry(Value::String(value), expire_at);
+            db_lock.keyspace_mut(index).insert(key, entry);
         },
         _ => {
+            // Le canal ne sert qu'à absorber ce que la commande journaliserait à nouveau
+            let (replay_tx, _replay_rx) = mpsc::channel();
+            process_command_parts(&parts, &mut db_lock, index, &replay_tx);
         }
     }
 }
 
-pub fn run_aof_writer(rx: Receiver<String>) {
+/// Boucle du thread de l'AOF, qui prend aussi les snapshots périodiques
+pub fn run_aof_writer(rx: Receiver<String>, db: Db) {
     let mut file = BufWriter::new(
         OpenOptions::new()
             .create(true)
@@ -87,6 +172,7 @@ pub fn run_aof_writer(rx: Receiver<String>) {
             .unwrap(),
     );
 
+    let mut last_snapshot = Instant::now();
     loop {
         let mut buffer = Vec::new();
         let start = Instant::now();
@@ -104,5 +190,13 @@ pub fn run_aof_writer(rx: Receiver<String>) {
             writeln!(file, "{}", cmd).unwrap();
         }
         file.flush().unwrap();
+
+        if last_snapshot.elapsed() >= SNAPSHOT_INTERVAL {
+            let mut dbs = db.lock().unwrap();
+            // Lignes envoyées avant qu'on obtienne le verrou : le snapshot les contient déjà
+            rx.try_iter().for_each(drop);
+            save(&mut dbs);
+            last_snapshot = Instant::now();
+        }
     }
 }
@@ -0,0 +1,478 @@
+// src/protocol.rs
+//! Décodage des requêtes (RESP ou format texte historique) et encodage des réponses RESP2/RESP3.
+
+/// Longueur maximale d'une requête inline ou d'un en-tête RESP sans fin de ligne.
+const MAX_INLINE_SIZE: usize = 64 * 1024;
+/// Taille maximale d'une chaîne bulk reçue (même limite que Redis).
+const MAX_BULK_SIZE: usize = 512 * 1024 * 1024;
+/// Nombre maximal d'arguments dans une requête multibulk.
+const MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+
+/// Réponse typée renvoyée par le serveur.
+///
+/// Les types RESP3 (`Map`, `Set`, `Double`, `Boolean`, `BigNumber`, `Push`) sont convertis
+/// vers leur équivalent RESP2 pour les connexions qui n'ont pas négocié RESP3 via `HELLO`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    Array(Vec<Reply>),
+    /// Tableau absent (`*-1` en RESP2), par exemple LPOP avec un nombre d'éléments sur une clé absente
+    NilArray,
+    Map(Vec<(Reply, Reply)>),
+    /// Paires ordonnées (membre et score de ZRANGE WITHSCORES) : tableau de paires en RESP3, tableau plat en RESP2
+    Pairs(Vec<(Reply, Reply)>),
+    Set(Vec<Reply>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    Push(Vec<Reply>),
+}
+
+impl Reply {
+    pub fn ok() -> Reply {
+        Reply::Simple("OK".to_string())
+    }
+
+    /// Erreur au format Redis : le premier mot est le code (`ERR`, `WRONGTYPE`...)
+    pub fn error(message: impl Into<String>) -> Reply {
+        Reply::Error(message.into())
+    }
+
+    /// Encodage RESP pour la version de protocole négociée (2 ou 3)
+    pub fn encode(&self, protover: u8, out: &mut Vec<u8>) {
+        let resp3 = protover >= 3;
+        match self {
+            Reply::Simple(s) => {
+                out.push(b'+');
+                push_line(out, s);
+            },
+            Reply::Error(e) => {
+                out.push(b'-');
+                push_line(out, e);
+            },
+            Reply::Integer(n) => push_header(out, b':', *n),
+            Reply::Bulk(data) => {
+                push_header(out, b'$', data.len() as i64);
+                out.extend_from_slice(data);
+                out.extend_from_slice(b"\r\n");
+            },
+            Reply::Nil if resp3 => out.extend_from_slice(b"_\r\n"),
+            Reply::Nil => out.extend_from_slice(b"$-1\r\n"),
+            Reply::NilArray if resp3 => out.extend_from_slice(b"_\r\n"),
+            Reply::NilArray => out.extend_from_slice(b"*-1\r\n"),
+            Reply::Array(items) => push_aggregate(out, b'*', items, protover),
+            Reply::Map(pairs) => {
+                if resp3 {
+                    push_header(out, b'%', pairs.len() as i64);
+                } else {
+                    push_header(out, b'*', 2 * pairs.len() as i64);
+                }
+                for (key, value) in pairs {
+                    key.encode(protover, out);
+                    value.encode(protover, out);
+                }
+            },
+            Reply::Pairs(pairs) => {
+                push_header(out, b'*', if resp3 { pairs.len() } else { 2 * pairs.len() } as i64);
+                for (first, second) in pairs {
+                    if resp3 {
+                        push_header(out, b'*', 2);
+                    }
+                    first.encode(protover, out);
+                    second.encode(protover, out);
+                }
+            },
+            Reply::Set(items) => push_aggregate(out, if resp3 { b'~' } else { b'*' }, items, protover),
+            Reply::Push(items) => push_aggregate(out, if resp3 { b'>' } else { b'*' }, items, protover),
+            Reply::Double(d) if resp3 => {
+                out.push(b',');
+                push_line(out, &format_double(*d));
+            },
+            Reply::Double(d) => Reply::Bulk(format_double(*d).into_bytes()).encode(protover, out),
+            Reply::Boolean(b) if resp3 => out.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" }),
+            Reply::Boolean(b) => push_header(out, b':', *b as i64),
+            Reply::BigNumber(n) if resp3 => {
+                out.push(b'(');
+                push_line(out, n);
+            },
+            Reply::BigNumber(n) => Reply::Bulk(n.clone().into_bytes()).encode(protover, out),
+        }
+    }
+
+    /// Encodage texte historique, une valeur par ligne (clients inline)
+    pub fn encode_inline(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Simple(s) | Reply::BigNumber(s) => out.extend_from_slice(s.as_bytes()),
+            Reply::Error(e) => {
+                // "ERR message" devient "ERR: message" comme avant
+                match e.split_once(' ') {
+                    Some((code, message)) => {
+                        out.extend_from_slice(format!("{}: {}", code, message).as_bytes())
+                    },
+                    None => out.extend_from_slice(e.as_bytes()),
+                }
+            },
+            Reply::Integer(n) => out.extend_from_slice(n.to_string().as_bytes()),
+            Reply::Bulk(data) => out.extend_from_slice(quote_arg(data).as_bytes()),
+            Reply::Nil | Reply::NilArray => out.extend_from_slice(b"nil"),
+            Reply::Double(d) => out.extend_from_slice(format_double(*d).as_bytes()),
+            Reply::Boolean(b) => out.extend_from_slice(if *b { b"1" } else { b"0" }),
+            Reply::Array(items) | Reply::Set(items) | Reply::Push(items) => {
+                if items.is_empty() {
+                    out.extend_from_slice(b"(empty array)\n");
+                }
+                for item in items {
+                    item.encode_inline(out);
+                }
+                return;
+            },
+            Reply::Map(pairs) | Reply::Pairs(pairs) => {
+                if pairs.is_empty() {
+                    out.extend_from_slice(b"(empty array)\n");
+                }
+                for (key, value) in pairs {
+                    key.encode_inline(out);
+                    value.encode_inline(out);
+                }
+                return;
+            },
+        }
+        out.push(b'\n');
+    }
+
+    /// Encodage texte d'un tableau en une seule réponse numérotée, comme redis-cli (`1) OK`).
+    ///
+    /// Sert pour EXEC : un client inline peut ainsi retrouver la réponse de chaque commande de la
+    /// transaction, même quand elle est elle-même un tableau.
+    pub fn encode_inline_numbered(&self, out: &mut Vec<u8>) {
+        self.encode_numbered(0, out);
+    }
+
+    fn encode_numbered(&self, indent: usize, out: &mut Vec<u8>) {
+        let items = match self {
+            Reply::Array(items) | Reply::Set(items) | Reply::Push(items) if !items.is_empty() => items,
+            _ => return self.encode_inline(out),
+        };
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.extend(std::iter::repeat_n(b' ', indent));
+            }
+            let label = format!("{}) ", i + 1);
+            out.extend_from_slice(label.as_bytes());
+            item.encode_numbered(indent + label.len(), out);
+        }
+    }
+}
+
+/// Représentation textuelle d'un flottant, comme Redis (`inf`, `-inf`, `nan`).
+pub fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+    } else {
+        d.to_string()
+    }
+}
+
+fn push_header(out: &mut Vec<u8>, prefix: u8, n: i64) {
+    out.push(prefix);
+    out.extend_from_slice(n.to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn push_aggregate(out: &mut Vec<u8>, prefix: u8, items: &[Reply], protover: u8) {
+    push_header(out, prefix, items.len() as i64);
+    for item in items {
+        item.encode(protover, out);
+    }
+}
+
+/// Écrit une ligne de statut en remplaçant les fins de ligne qui casseraient le protocole.
+fn push_line(out: &mut Vec<u8>, line: &str) {
+    out.extend(line.bytes().map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b }));
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Requête décodée depuis le tampon de lecture
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub args: Vec<Vec<u8>>,
+    /// Vrai si la requête a été envoyée au format texte ; la réponse suit alors le même format.
+    pub inline: bool,
+}
+
+/// Décode une requête en tête de `buf`, sans reprendre l'analyse d'un appel précédent (voir `RequestParser`).
+///
+/// Renvoie `Ok(None)` si la requête est incomplète, sinon la requête et le nombre d'octets consommés.
+/// Une requête sans argument (ligne vide, `*0`) est renvoyée telle quelle et doit être ignorée.
+pub fn parse_request(buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+    RequestParser::default().parse(buf)
+}
+
+/// Décodeur de requêtes d'une connexion.
+///
+/// Une requête multibulk incomplète n'est pas relue depuis le début à l'arrivée de la suite :
+/// l'analyse reprend après le dernier argument complet, et rien n'est fait tant que le tampon n'a pas
+/// atteint la fin de l'argument en cours. Entre deux appels, `buf` doit donc commencer à la même
+/// requête, tant qu'elle n'a pas été renvoyée.
+#[derive(Debug, Default)]
+pub struct RequestParser {
+    pending: Option<Multibulk>,
+}
+
+/// Avancement d'une requête multibulk incomplète (positions relatives au début de la requête)
+#[derive(Debug)]
+struct Multibulk {
+    /// Nombre d'arguments annoncé
+    count: usize,
+    /// Arguments complets déjà lus
+    args: Vec<Vec<u8>>,
+    /// Début de l'argument suivant
+    pos: usize,
+    /// Taille de tampon nécessaire pour terminer l'argument en cours
+    needed: usize,
+}
+
+impl RequestParser {
+    /// Comme `parse_request`, en reprenant là où l'appel précédent s'est arrêté
+    pub fn parse(&mut self, buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        if buf[0] != b'*' {
+            return parse_inline(buf);
+        }
+        let result = self.parse_multibulk(buf);
+        if !matches!(result, Ok(None)) {
+            self.pending = None;
+        }
+        result
+    }
+
+    fn parse_multibulk(&mut self, buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+        let state = match &mut self.pending {
+            Some(state) => state,
+            None => {
+                let (count, pos) = match read_header(buf, 0, "multibulk length")? {
+                    Some(header) => header,
+                    None => return Ok(None),
+                };
+                if count > MAX_MULTIBULK_LEN as i64 {
+                    return Err("Protocol error: invalid multibulk length".to_string());
+                }
+                let count = count.max(0) as usize;
+                // Le nombre annoncé vient du client : il ne dimensionne pas la préallocation
+                let args = Vec::with_capacity(count.min(1024));
+                self.pending.insert(Multibulk { count, args, pos, needed: 0 })
+            },
+        };
+        if buf.len() < state.needed {
+            return Ok(None);
+        }
+
+        while state.args.len() < state.count {
+            let pos = state.pos;
+            if pos >= buf.len() {
+                return Ok(None);
+            }
+            if buf[pos] != b'$' {
+                return Err(format!("Protocol error: expected '$', got '{}'", buf[pos] as char));
+            }
+            let (len, start) = match read_header(buf, pos, "bulk length")? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            if len < 0 || len > MAX_BULK_SIZE as i64 {
+                return Err("Protocol error: invalid bulk length".to_string());
+            }
+            let end = start + len as usize;
+            if buf.len() < end + 2 {
+                state.needed = end + 2;
+                return Ok(None);
+            }
+            if &buf[end..end + 2] != b"\r\n" {
+                return Err("Protocol error: bulk string not terminated by CRLF".to_string());
+            }
+            state.args.push(buf[start..end].to_vec());
+            state.pos = end + 2;
+        }
+        let Multibulk { args, pos, .. } = self.pending.take().unwrap();
+        Ok(Some((Request { args, inline: false }, pos)))
+    }
+}
+
+fn parse_inline(buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+    let end = match buf.iter().position(|&b| b == b'\n') {
+        Some(end) => end,
+        None if buf.len() > MAX_INLINE_SIZE => {
+            return Err("Protocol error: too big inline request".to_string())
+        },
+        None => return Ok(None),
+    };
+    let args = split_args(&buf[..end])
+        .ok_or_else(|| "Protocol error: unbalanced quotes in request".to_string())?;
+    Ok(Some((Request { args, inline: true }, end + 1)))
+}
+
+/// Découpe une ligne de texte en arguments, avec la même syntaxe que `redis-cli` :
+/// `"..."` accepte les échappements `\n`, `\r`, `\t`, `\b`, `\a`, `\"`, `\\` et `\xHH`,
+/// `'...'` n'accepte que `\'`.
+///
+/// Renvoie `None` si les guillemets ne sont pas équilibrés.
+pub fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Some(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let c = line.get(i).copied();
+            if in_double {
+                match c {
+                    None => return None,
+                    Some(b'\\') if line.get(i + 1) == Some(&b'x') && hex_byte(line.get(i + 2..i + 4)).is_some() => {
+                        current.push(hex_byte(line.get(i + 2..i + 4)).unwrap());
+                        i += 3;
+                    },
+                    Some(b'\\') if i + 1 < line.len() => {
+                        i += 1;
+                        current.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    },
+                    Some(b'"') => {
+                        // Le guillemet fermant doit être suivi d'un espace ou de la fin de ligne
+                        if line.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    },
+                    Some(other) => current.push(other),
+                }
+            } else if in_single {
+                match c {
+                    None => return None,
+                    Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                        current.push(b'\'');
+                        i += 1;
+                    },
+                    Some(b'\'') => {
+                        if line.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    },
+                    Some(other) => current.push(other),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(b) if b.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double = true,
+                    Some(b'\'') => in_single = true,
+                    Some(other) => current.push(other),
+                }
+            }
+            i += 1;
+        }
+        args.push(current);
+    }
+}
+
+fn hex_byte(digits: Option<&[u8]>) -> Option<u8> {
+    let digits = digits.filter(|d| d.iter().all(u8::is_ascii_hexdigit))?;
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+/// Représentation d'un argument relisible par `split_args`.
+///
+/// L'argument est renvoyé tel quel s'il ne contient ni espace, ni guillemet, ni caractère de
+/// contrôle ; sinon il est entouré de guillemets et échappé (`\xHH` pour les octets non UTF-8).
+pub fn quote_arg(arg: &[u8]) -> String {
+    let needs_quotes = arg.is_empty()
+        || arg.utf8_chunks().any(|chunk| {
+            !chunk.invalid().is_empty()
+                || chunk
+                    .valid()
+                    .chars()
+                    .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '"' | '\'' | '\\'))
+        });
+    if !needs_quotes {
+        return String::from_utf8_lossy(arg).into_owned();
+    }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for chunk in arg.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => quoted.push_str("\\\\"),
+                '"' => quoted.push_str("\\\""),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '\u{07}' => quoted.push_str("\\a"),
+                '\u{08}' => quoted.push_str("\\b"),
+                c if c.is_control() => {
+                    let mut utf8 = [0u8; 4];
+                    for byte in c.encode_utf8(&mut utf8).bytes() {
+                        quoted.push_str(&format!("\\x{:02x}", byte));
+                    }
+                },
+                c => quoted.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            quoted.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Lit un argument numérique (ou tout type implémentant `FromStr`) envoyé sous forme d'octets.
+pub fn parse_arg<T: std::str::FromStr>(arg: &[u8]) -> Option<T> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// Ligne de commande relisible par `split_args`, utilisée notamment pour l'AOF.
+pub fn format_command(args: &[&[u8]]) -> String {
+    args.iter().map(|arg| quote_arg(arg)).collect::<Vec<_>>().join(" ")
+}
+
+/// Lit un en-tête `<préfixe><entier>\r\n` à la position `pos`, et renvoie l'entier et la position suivante.
+fn read_header(buf: &[u8], pos: usize, what: &str) -> Result<Option<(i64, usize)>, String> {
+    let rest = &buf[pos + 1..];
+    let end = match rest.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => end,
+        None if rest.len() > MAX_INLINE_SIZE => {
+            return Err(format!("Protocol error: invalid {}", what))
+        },
+        None => return Ok(None),
+    };
+    let value = std::str::from_utf8(&rest[..end])
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| format!("Protocol error: invalid {}", what))?;
+    Ok(Some((value, pos + 1 + end + 2)))
+}
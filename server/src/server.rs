@@ -1,12 +1,35 @@
 // src/server.rs
-use crate::db::{Db, Entry};
-use crate::persistence::snapshot;
+use crate::commands::{check_command, parse_db_index, process_blocking_command, process_command_parts};
+use crate::db::{peek_live, Databases, Db};
+use crate::expiry::run_active_expiry;
+use crate::protocol::{parse_arg, Reply, RequestParser};
 use std::net::{TcpListener, TcpStream};
-use std::io::{BufRead, BufReader, Write};
-use std::sync::mpsc::Sender;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
-use std::time::{Duration, SystemTime};
-use std::collections::HashMap;
+
+/// Taille du tampon d'écriture au-delà de laquelle les réponses sont envoyées sans attendre
+const MAX_PENDING_OUTPUT: usize = 64 * 1024;
+
+/// Identifiant attribué à chaque connexion (renvoyé par HELLO)
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Clé surveillée par WATCH, avec son état au moment de WATCH
+struct Watched {
+    index: usize,
+    key: Vec<u8>,
+    version: u64,
+    /// Une clé supprimée ou expirée depuis WATCH fait aussi échouer EXEC
+    existed: bool,
+}
+
+impl Watched {
+    fn changed(&self, dbs: &mut Databases) -> bool {
+        dbs.version(self.index, &self.key) != self.version
+            || peek_live(dbs.keyspace_mut(self.index), &self.key).is_some() != self.existed
+    }
+}
 
 pub fn run_server(addr: &str, db: Db) {
     let listener = TcpListener::bind(addr).expect("Binding Error");
@@ -15,29 +38,17 @@ pub fn run_server(addr: &str, db: Db) {
     // Communication channel pour l'AOF writer
     let (aof_tx, aof_rx) = std::sync::mpsc::channel::<String>();
 
-    // Démarrage du thread AOF
-    thread::spawn(move || {
-        crate::persistence::run_aof_writer(aof_rx);
-    });
-
-    // Thread Snapshot (toutes les 5 minutes)
-    let snapshot_db = db.clone();
+    // Démarrage du thread AOF, qui prend aussi un snapshot toutes les 5 minutes
+    let writer_db = db.clone();
     thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(300));
-            snapshot(&snapshot_db);
-        }
+        crate::persistence::run_aof_writer(aof_rx, writer_db);
     });
 
-    // Thread de nettoyage des TTL
-    let ttl_db = db.clone();
+    // Thread d'expiration active
+    let expiry_db = db.clone();
+    let expiry_tx = aof_tx.clone();
     thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(1));
-            let now = SystemTime::now();
-            let mut db = ttl_db.lock().unwrap();
-            db.retain(|_, entry| entry.expire_at.map_or(true, |exp| exp > now));
-        }
+        run_active_expiry(expiry_db, expiry_tx);
     });
 
     // Acceptation des connexions entrantes (gestion parallel des clients)
@@ -55,166 +66,267 @@ pub fn run_server(addr: &str, db: Db) {
     }
 }
 
-/// Gestion des clients avec support de transaction (MULTI/EXEC/DISCARD)
-pub fn handle_client(stream: TcpStream, db: Db, aof_tx: Sender<String>) {
-    let mut reader = BufReader::new(&stream);
-    let mut buffer = String::new();
+/// Gestion des clients avec support de transaction (MULTI/EXEC/DISCARD) et du pipelining
+///
+/// Les requêtes RESP (tableaux de chaînes bulk) reçoivent des réponses RESP, les requêtes
+/// texte historiques reçoivent une réponse texte.
+pub fn handle_client(mut stream: TcpStream, db: Db, aof_tx: Sender<String>) {
+    // Tampon de lecture : plusieurs requêtes peuvent y arriver d'un coup (pipelining).
+    // `pos` marque le début de la première requête non traitée.
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    // Garde l'avancement d'une requête incomplète d'une lecture à l'autre
+    let mut parser = RequestParser::default();
+    let mut chunk = [0u8; 16 * 1024];
+    // Tampon d'écriture : les réponses y sont ajoutées dans l'ordre puis envoyées en une fois
+    let mut out: Vec<u8> = Vec::new();
+
+    // Version du protocole RESP négociée via HELLO (RESP2 par défaut)
+    let mut protover: u8 = 2;
+    let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut client_name: Option<String> = None;
+    // Base sélectionnée par SELECT (0 par défaut)
+    let mut db_index = 0;
 
     // Variables de gestion de transaction
     let mut in_transaction = false;
-    let mut transaction_queue: Vec<String> = Vec::new();
+    let mut transaction_queue: Vec<Vec<Vec<u8>>> = Vec::new();
+    // Vrai si une commande a été refusée à la mise en file : EXEC annule alors la transaction
+    let mut transaction_aborted = false;
+    let mut watched: Vec<Watched> = Vec::new();
 
     loop {
-        buffer.clear();
-        let bytes_read = reader.read_line(&mut buffer).unwrap();
-        if bytes_read == 0 {
-            break; // fin de connexion
-        }
-        let trimmed = buffer.trim();
-        if trimmed.is_empty() {
+        let (request, consumed) = match parser.parse(&buffer[pos..]) {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => {
+                // Plus aucune requête complète : on envoie les réponses accumulées avant d'attendre la suite
+                buffer.drain(..pos);
+                pos = 0;
+                if !out.is_empty() {
+                    if stream.write_all(&out).is_err() {
+                        break;
+                    }
+                    out.clear();
+                }
+                match stream.read(&mut chunk) {
+                    Ok(0) | Err(_) => break, // fin de connexion
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                }
+                continue;
+            },
+            Err(e) => {
+                // Erreur de protocole : on répond puis on ferme la connexion
+                let reply = Reply::error(format!("ERR {}", e));
+                if buffer.get(pos) == Some(&b'*') {
+                    reply.encode(protover, &mut out);
+                } else {
+                    reply.encode_inline(&mut out);
+                }
+                let _ = stream.write_all(&out);
+                break;
+            },
+        };
+        pos += consumed;
+        if request.args.is_empty() {
             continue;
         }
 
+        let parts = request.args;
+        let command = String::from_utf8_lossy(&parts[0]).to_uppercase();
+        let mut quit = false;
+
         // Si on est dans une transaction, on met en file d'attente ou on exécute selon la commande reçue
-        if in_transaction {
-            match trimmed.to_uppercase().as_str() {
+        let reply = if in_transaction {
+            match command.as_str() {
                 "EXEC" => {
-                    let mut responses = Vec::new();
-                    {
-                        // On verrouille la base de données une seule fois pour exécuter la transaction
-                        let mut db_guard = db.lock().unwrap();
+                    // On verrouille la base de données une seule fois pour vérifier les clés
+                    // surveillées puis exécuter la transaction
+                    let mut db_guard = db.lock().unwrap();
+                    let reply = if transaction_aborted {
+                        Reply::error("EXECABORT Transaction discarded because of previous errors.")
+                    } else if watched.iter().any(|watched| watched.changed(&mut db_guard)) {
+                        db_guard.log_expired(&aof_tx);
+                        Reply::NilArray
+                    } else {
+                        // Les écritures de la transaction sont encadrées par MULTI et EXEC dans l'AOF
+                        let (exec_tx, exec_rx) = mpsc::channel();
+                        let mut responses = Vec::new();
                         for cmd in &transaction_queue {
-                            let parts: Vec<&str> = cmd.split_whitespace().collect();
-                            let response = process_command_parts(&parts, &mut db_guard, &aof_tx);
+                            let response = match String::from_utf8_lossy(&cmd[0]).to_uppercase().as_str() {
+                                "SELECT" => select(&cmd[1..], &mut db_index),
+                                "HELLO" => hello(&cmd[1..], client_id, &mut protover, &mut client_name),
+                                // Les clés surveillées sont relâchées juste après
+                                "UNWATCH" => Reply::ok(),
+                                _ => process_command_parts(cmd, &mut db_guard, db_index, &exec_tx),
+                            };
                             responses.push(response);
                         }
-                    }
+                        let lines: Vec<String> = exec_rx.try_iter().collect();
+                        if !lines.is_empty() {
+                            aof_tx.send("MULTI".to_string()).unwrap();
+                            for line in lines {
+                                aof_tx.send(line).unwrap();
+                            }
+                            aof_tx.send("EXEC".to_string()).unwrap();
+                        }
+                        Reply::Array(responses)
+                    };
+                    unwatch(&mut db_guard, &mut watched);
                     // Réinitialisation de l'état transactionnel
                     in_transaction = false;
+                    transaction_aborted = false;
                     transaction_queue.clear();
-                    // Envoi des réponses de chaque commande de la transaction
-                    for response in responses {
-                        writeln!(&stream, "{}", response).unwrap();
-                    }
+                    reply
                 },
                 "DISCARD" => {
+                    unwatch(&mut db.lock().unwrap(), &mut watched);
                     in_transaction = false;
+                    transaction_aborted = false;
                     transaction_queue.clear();
-                    writeln!(&stream, "OK").unwrap();
+                    Reply::ok()
+                },
+                "MULTI" => Reply::error("ERR MULTI calls can not be nested"),
+                "WATCH" => Reply::error("ERR WATCH inside MULTI is not allowed"),
+                // Toute autre commande est vérifiée puis mise en file d'attente
+                _ => match check_command(&parts) {
+                    Ok(()) => {
+                        transaction_queue.push(parts);
+                        Reply::Simple("QUEUED".to_string())
+                    },
+                    Err(e) => {
+                        transaction_aborted = true;
+                        Reply::from(e)
+                    },
                 },
-                _ => {
-                    // Toute autre commande est mise en file d'attente
-                    transaction_queue.push(trimmed.to_string());
-                    writeln!(&stream, "QUEUED").unwrap();
-                }
             }
         } else {
             // En mode normal (pas de transaction)
-            match trimmed.to_uppercase().as_str() {
+            match command.as_str() {
                 "MULTI" => {
                     in_transaction = true;
                     transaction_queue.clear();
-                    writeln!(&stream, "OK").unwrap();
+                    Reply::ok()
                 },
-                _ => {
-                    let parts: Vec<&str> = trimmed.split_whitespace().collect();
+                "EXEC" => Reply::error("ERR EXEC without MULTI"),
+                "DISCARD" => Reply::error("ERR DISCARD without MULTI"),
+                "HELLO" => hello(&parts[1..], client_id, &mut protover, &mut client_name),
+                "SELECT" => select(&parts[1..], &mut db_index),
+                "WATCH" => {
                     let mut db_guard = db.lock().unwrap();
-                    let response = process_command_parts(&parts, &mut db_guard, &aof_tx);
-                    writeln!(&stream, "{}", response).unwrap();
-                    if parts[0].to_uppercase() == "QUIT" {
-                        break;
-                    }
-                }
+                    let reply = watch(&parts[1..], &mut db_guard, db_index, &mut watched);
+                    db_guard.log_expired(&aof_tx);
+                    reply
+                },
+                "UNWATCH" => {
+                    unwatch(&mut db.lock().unwrap(), &mut watched);
+                    Reply::ok()
+                },
+                _ => match process_blocking_command(&parts, &db, db_index, &aof_tx, &stream) {
+                    Some(reply) => reply,
+                    None => {
+                        let mut db_guard = db.lock().unwrap();
+                        quit = command == "QUIT";
+                        process_command_parts(&parts, &mut db_guard, db_index, &aof_tx)
+                    },
+                },
             }
+        };
+
+        if request.inline && command == "EXEC" {
+            reply.encode_inline_numbered(&mut out);
+        } else if request.inline {
+            reply.encode_inline(&mut out);
+        } else {
+            reply.encode(protover, &mut out);
         }
+        // Un gros pipeline ne doit pas accumuler toutes ses réponses en mémoire
+        if quit || out.len() >= MAX_PENDING_OUTPUT {
+            if stream.write_all(&out).is_err() || quit {
+                break;
+            }
+            out.clear();
+        }
+    }
+
+    if !watched.is_empty() {
+        unwatch(&mut db.lock().unwrap(), &mut watched);
     }
 }
 
+/// WATCH key [key ...]
+///
+/// EXEC échouera (réponse nil) si l'une des clés est modifiée, supprimée ou expire d'ici là.
+fn watch(args: &[Vec<u8>], dbs: &mut Databases, db_index: usize, watched: &mut Vec<Watched>) -> Reply {
+    if args.is_empty() {
+        return Reply::error("ERR wrong number of arguments for 'watch' command");
+    }
+    for key in args {
+        let version = dbs.watch(db_index, key);
+        let existed = peek_live(dbs.keyspace_mut(db_index), key).is_some();
+        watched.push(Watched { index: db_index, key: key.clone(), version, existed });
+    }
+    Reply::ok()
+}
 
-fn process_command_parts(parts: &[&str], db: &mut HashMap<String, Entry>, aof_tx: &Sender<String>) -> String {
-    match parts[0].to_uppercase().as_str() {
-        "SET" => {
-            if parts.len() < 3 {
-                return "ERR: Usage: SET key value [TTL seconds]".to_string();
-            }
-            let key = parts[1].to_string();
-            if db.contains_key(&key) {
-                return "ERR: La clé existe déjà.".to_string();
-            }
-            let value = parts[2].to_string();
-            let expire_at = if parts.len() >= 5 && parts[3].to_uppercase() == "TTL" {
-                if let Ok(sec) = parts[4].parse::<u64>() {
-                    Some(SystemTime::now() + Duration::from_secs(sec))
-                } else { None }
-            } else { None };
-            let entry = Entry { value: value.clone(), expire_at };
-            db.insert(key.clone(), entry);
-            let cmd = if let Some(exp) = expire_at {
-                let ts = exp.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-                format!("SET {} {} TTL {}", key, value, ts)
-            } else {
-                format!("SET {} {}", key, value)
-            };
-            aof_tx.send(cmd).unwrap();
-            "OK".to_string()
-        },
-        "UPDATE" => {
-            if parts.len() < 3 {
-                return "ERR: Usage: UPDATE key value [TTL seconds]".to_string();
-            }
-            let key = parts[1].to_string();
-            if !db.contains_key(&key) {
-                return "ERR: La clé n'existe pas.".to_string();
-            }
-            let value = parts[2].to_string();
-            let expire_at = if parts.len() >= 5 && parts[3].to_uppercase() == "TTL" {
-                if let Ok(sec) = parts[4].parse::<u64>() {
-                    Some(SystemTime::now() + Duration::from_secs(sec))
-                } else { None }
-            } else { None };
-            let entry = Entry { value: value.clone(), expire_at };
-            db.insert(key.clone(), entry);
-            let cmd = if let Some(exp) = expire_at {
-                let ts = exp.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-                format!("UPDATE {} {} TTL {}", key, value, ts)
-            } else {
-                format!("UPDATE {} {}", key, value)
-            };
-            aof_tx.send(cmd).unwrap();
-            "OK".to_string()
-        },
-        "GET" => {
-            if parts.len() < 2 {
-                return "ERR: Usage: GET key".to_string();
-            }
-            let key = parts[1];
-            if let Some(entry) = db.get(key) {
-                if let Some(exp) = entry.expire_at {
-                    if SystemTime::now() > exp {
-                        "nil".to_string()
-                    } else {
-                        entry.value.clone()
-                    }
-                } else {
-                    entry.value.clone()
-                }
-            } else {
-                "nil".to_string()
-            }
+/// Relâche toutes les clés surveillées par la connexion (UNWATCH, EXEC, DISCARD ou déconnexion)
+fn unwatch(dbs: &mut Databases, watched: &mut Vec<Watched>) {
+    for watched in watched.drain(..) {
+        dbs.unwatch(watched.index, &watched.key);
+    }
+}
+
+/// SELECT index
+///
+/// Change la base de la connexion ; rien n'est journalisé, chaque commande écrite dans l'AOF
+/// étant précédée au besoin du SELECT de sa propre base.
+fn select(args: &[Vec<u8>], db_index: &mut usize) -> Reply {
+    if args.len() != 1 {
+        return Reply::error("ERR wrong number of arguments for 'select' command");
+    }
+    match parse_db_index(&args[0]) {
+        Ok(index) => {
+            *db_index = index;
+            Reply::ok()
         },
-        "DELETE" => {
-            if parts.len() < 2 {
-                return "ERR: Usage: DELETE key".to_string();
-            }
-            let key = parts[1].to_string();
-            if db.remove(&key).is_some() {
-                aof_tx.send(format!("DELETE {}", key)).unwrap();
-                "OK".to_string()
-            } else {
-                "ERR: La clé n'existe pas.".to_string()
+        Err(e) => Reply::from(e),
+    }
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+///
+/// Change la version du protocole de la connexion et renvoie les informations du serveur.
+fn hello(args: &[Vec<u8>], client_id: u64, protover: &mut u8, client_name: &mut Option<String>) -> Reply {
+    let mut version = *protover;
+    let mut name = client_name.clone();
+    if let Some(requested) = args.first() {
+        version = match parse_arg::<u8>(requested) {
+            Some(v @ 2..=3) => v,
+            Some(_) => return Reply::error("NOPROTO unsupported protocol version"),
+            None => return Reply::error("ERR Protocol version is not an integer or out of range"),
+        };
+        let mut i = 1;
+        while i < args.len() {
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                // Aucune authentification n'est configurée : tout mot de passe est accepté
+                "AUTH" if i + 2 < args.len() => i += 3,
+                "SETNAME" if i + 1 < args.len() => {
+                    name = Some(String::from_utf8_lossy(&args[i + 1]).into_owned());
+                    i += 2;
+                },
+                option => return Reply::error(format!("ERR Syntax error in HELLO option '{}'", option)),
             }
-        },
-        "QUIT" => "BYE".to_string(),
-        _ => "ERR: Commande inconnue".to_string(),
+        }
     }
+    *protover = version;
+    *client_name = name;
+
+    Reply::Map(vec![
+        (Reply::Bulk(b"server".to_vec()), Reply::Bulk(b"redust".to_vec())),
+        (Reply::Bulk(b"version".to_vec()), Reply::Bulk(env!("CARGO_PKG_VERSION").as_bytes().to_vec())),
+        (Reply::Bulk(b"proto".to_vec()), Reply::Integer(version as i64)),
+        (Reply::Bulk(b"id".to_vec()), Reply::Integer(client_id as i64)),
+        (Reply::Bulk(b"mode".to_vec()), Reply::Bulk(b"standalone".to_vec())),
+        (Reply::Bulk(b"role".to_vec()), Reply::Bulk(b"master".to_vec())),
+        (Reply::Bulk(b"modules".to_vec()), Reply::Array(Vec::new())),
+    ])
 }
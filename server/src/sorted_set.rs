@@ -0,0 +1,414 @@
+// src/sorted_set.rs
+//! Ensemble trié : une skiplist indexée ordonnée par (score, membre) et une table membre → score.
+//!
+//! Chaque lien de la skiplist retient le nombre d'éléments qu'il enjambe (`span`), ce qui permet
+//! de calculer un rang ou d'accéder au n-ième élément en O(log n), comme dans Redis.
+
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+
+const MAX_LEVEL: usize = 32;
+/// Indice du nœud de tête (sentinelle sans membre) dans `nodes`
+const HEAD: usize = 0;
+
+#[derive(Clone)]
+struct Level {
+    forward: Option<usize>,
+    span: usize,
+}
+
+#[derive(Clone)]
+struct Node {
+    member: Vec<u8>,
+    score: f64,
+    levels: Vec<Level>,
+    backward: Option<usize>,
+}
+
+impl Node {
+    /// Vrai si ce nœud se place strictement avant (score, member)
+    fn precedes(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || (self.score == score && self.member.as_slice() < member)
+    }
+}
+
+/// Intervalle de scores, bornes inclusives ou exclusives (`(1.5` dans la syntaxe Redis)
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub min_exclusive: bool,
+    pub max: f64,
+    pub max_exclusive: bool,
+}
+
+impl ScoreRange {
+    pub fn above_min(&self, score: f64) -> bool {
+        if self.min_exclusive { score > self.min } else { score >= self.min }
+    }
+
+    pub fn below_max(&self, score: f64) -> bool {
+        if self.max_exclusive { score < self.max } else { score <= self.max }
+    }
+
+    pub fn contains(&self, score: f64) -> bool {
+        self.above_min(score) && self.below_max(score)
+    }
+}
+
+/// Ensemble de membres uniques (octets) ordonnés par score puis par ordre lexicographique
+#[derive(Clone)]
+pub struct SortedSet {
+    /// Nœuds de la skiplist, la tête en premier ; les emplacements libérés sont réutilisés via `free`
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    scores: HashMap<Vec<u8>, f64>,
+    rng: u64,
+}
+
+impl Default for SortedSet {
+    fn default() -> SortedSet {
+        SortedSet::new()
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> SortedSet {
+        let head = Node {
+            member: Vec::new(),
+            score: f64::NEG_INFINITY,
+            levels: vec![Level { forward: None, span: 0 }; MAX_LEVEL],
+            backward: None,
+        };
+        SortedSet {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            scores: HashMap::new(),
+            rng: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Ajoute `member` ou met à jour son score. Renvoie vrai si le membre est nouveau.
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        match self.scores.get(&member).copied() {
+            Some(old) if old == score => false,
+            Some(old) => {
+                self.unlink(old, &member);
+                self.link(score, member.clone());
+                self.scores.insert(member, score);
+                false
+            },
+            None => {
+                self.link(score, member.clone());
+                self.scores.insert(member, score);
+                true
+            },
+        }
+    }
+
+    /// Retire `member`. Renvoie vrai s'il était présent.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => {
+                self.unlink(score, member);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Rang (à partir de 0, par score croissant) de `member`
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                let node = &self.nodes[next];
+                if node.precedes(score, member) || (node.score == score && node.member == member) {
+                    rank += self.nodes[x].levels[i].span;
+                    x = next;
+                } else {
+                    break;
+                }
+            }
+            if x != HEAD && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// Parcours par score croissant
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { set: self, next: self.nodes[HEAD].levels[0].forward, reverse: false }
+    }
+
+    /// Parcours à partir du rang `rank` (croissant), ou vers les rangs inférieurs si `reverse`
+    pub fn iter_from_rank(&self, rank: usize, reverse: bool) -> Iter<'_> {
+        Iter { set: self, next: self.node_at(rank), reverse }
+    }
+
+    /// Éléments dont le score est dans `range`, par score croissant (ou décroissant si `reverse`)
+    pub fn range_by_score(&self, range: ScoreRange, reverse: bool) -> impl Iterator<Item = (&[u8], f64)> {
+        let start = if reverse { self.last_below_max(&range) } else { self.first_above_min(&range) };
+        Iter { set: self, next: start, reverse }.take_while(move |(_, score)| range.contains(*score))
+    }
+
+    /// Nombre d'éléments dont le score est dans `range`
+    pub fn count(&self, range: ScoreRange) -> usize {
+        let first = match self.first_above_min(&range) {
+            Some(node) if range.below_max(self.nodes[node].score) => node,
+            _ => return 0,
+        };
+        let last = self.last_below_max(&range).expect("un élément au moins est dans l'intervalle");
+        let rank_of = |node: usize| self.rank(&self.nodes[node].member).unwrap();
+        rank_of(last) - rank_of(first) + 1
+    }
+
+    /// Nœud de rang `rank` (à partir de 0)
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    fn first_above_min(&self, range: &ScoreRange) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if range.above_min(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.nodes[x].levels[0].forward
+    }
+
+    fn last_below_max(&self, range: &ScoreRange) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !range.below_max(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        (x != HEAD).then_some(x)
+    }
+
+    /// Niveau aléatoire d'un nouveau nœud (probabilité 1/4 de monter d'un niveau)
+    fn random_level(&mut self) -> usize {
+        let mut level = 1;
+        loop {
+            // xorshift64
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            if level >= MAX_LEVEL || self.rng & 3 != 0 {
+                return level;
+            }
+            level += 1;
+        }
+    }
+
+    /// Insère un nœud (score, member) absent de la skiplist
+    fn link(&mut self, score: f64, member: Vec<u8>) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let length = self.len();
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = length;
+            }
+            self.level = level;
+        }
+
+        let node = Node { member, score, levels: vec![Level { forward: None, span: 0 }; level], backward: None };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            },
+        };
+        for i in 0..level {
+            let prev = update[i];
+            let prev_span = self.nodes[prev].levels[i].span;
+            self.nodes[id].levels[i].forward = self.nodes[prev].levels[i].forward;
+            self.nodes[id].levels[i].span = prev_span - (rank[0] - rank[i]);
+            self.nodes[prev].levels[i].forward = Some(id);
+            self.nodes[prev].levels[i].span = rank[0] - rank[i] + 1;
+        }
+        for (i, &prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[prev].levels[i].span += 1;
+        }
+
+        self.nodes[id].backward = (update[0] != HEAD).then_some(update[0]);
+        if let Some(next) = self.nodes[id].levels[0].forward {
+            self.nodes[next].backward = Some(id);
+        }
+    }
+
+    /// Retire le nœud (score, member) de la skiplist
+    fn unlink(&mut self, score: f64, member: &[u8]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        let id = match self.nodes[x].levels[0].forward {
+            Some(id) if self.nodes[id].member == member => id,
+            _ => return,
+        };
+
+        for (i, &prev) in update.iter().enumerate().take(self.level) {
+            if self.nodes[prev].levels[i].forward == Some(id) {
+                let span = self.nodes[prev].levels[i].span + self.nodes[id].levels[i].span - 1;
+                self.nodes[prev].levels[i].span = span;
+                self.nodes[prev].levels[i].forward = self.nodes[id].levels[i].forward;
+            } else {
+                self.nodes[prev].levels[i].span -= 1;
+            }
+        }
+        let backward = self.nodes[id].backward;
+        if let Some(next) = self.nodes[id].levels[0].forward {
+            self.nodes[next].backward = backward;
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+
+        self.nodes[id].member = Vec::new();
+        self.nodes[id].levels = Vec::new();
+        self.free.push(id);
+    }
+}
+
+/// Parcours de la skiplist dans un sens ou dans l'autre
+pub struct Iter<'a> {
+    set: &'a SortedSet,
+    next: Option<usize>,
+    reverse: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a [u8], f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.set.nodes[self.next?];
+        self.next = if self.reverse { node.backward } else { node.levels[0].forward };
+        Some((node.member.as_slice(), node.score))
+    }
+}
+
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &SortedSet) -> bool {
+        self.scores == other.scores
+    }
+}
+
+impl fmt::Debug for SortedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(member, score)| (String::from_utf8_lossy(member), score)))
+            .finish()
+    }
+}
+
+/// Dans le snapshot, un ensemble trié est une liste de paires `[membre, score]` par ordre croissant.
+/// Le score est écrit comme une chaîne pour conserver `inf` et `-inf`, que JSON ne sait pas représenter.
+impl Serialize for SortedSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.iter()
+                .map(|(member, score)| (crate::db::escape_bytes(member), crate::protocol::format_double(score))),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for SortedSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SortedSet, D::Error> {
+        struct PairsVisitor;
+
+        impl<'de> Visitor<'de> for PairsVisitor {
+            type Value = SortedSet;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("une liste de paires [membre, score]")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<SortedSet, A::Error> {
+                let mut set = SortedSet::new();
+                while let Some((member, score)) = seq.next_element::<(String, String)>()? {
+                    let score = score
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|score| !score.is_nan())
+                        .ok_or_else(|| serde::de::Error::custom(format!("score invalide: {}", score)))?;
+                    set.insert(crate::db::unescape_bytes(&member), score);
+                }
+                Ok(set)
+            }
+        }
+
+        deserializer.deserialize_seq(PairsVisitor)
+    }
+}
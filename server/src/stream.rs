@@ -0,0 +1,224 @@
+// src/stream.rs
+//! Stream : journal d'entrées ordonnées par identifiant `ms-seq`, chacune portant des paires champ/valeur.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Identifiant d'une entrée : millisecondes et numéro de séquence dans la milliseconde
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    pub fn new(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    /// Identifiant immédiatement supérieur, s'il existe
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| StreamId::new(ms, 0)),
+        }
+    }
+
+    /// Identifiant immédiatement inférieur, s'il existe
+    pub fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_sub(1).map(|ms| StreamId::new(ms, u64::MAX)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// Forme complète `ms-seq` (les formes abrégées des commandes sont traitées dans `commands::streams`)
+impl FromStr for StreamId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<StreamId, ()> {
+        let (ms, seq) = s.split_once('-').ok_or(())?;
+        Ok(StreamId::new(ms.parse().map_err(|_| ())?, seq.parse().map_err(|_| ())?))
+    }
+}
+
+impl Serialize for StreamId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StreamId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<StreamId, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| serde::de::Error::custom(format!("identifiant de stream invalide: {}", s)))
+    }
+}
+
+/// Stream complet. `last_id` est conservé même quand les entrées sont supprimées,
+/// pour que les identifiants restent croissants (y compris après un redémarrage).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stream {
+    /// Entrées par identifiant, chacune avec ses champs et valeurs alternés
+    #[serde(with = "entries")]
+    pub entries: BTreeMap<StreamId, Vec<Vec<u8>>>,
+    pub last_id: StreamId,
+    /// Groupes de consommateurs (absents des snapshots antérieurs aux groupes)
+    #[serde(default, with = "crate::db::escaped_keys")]
+    pub groups: HashMap<Vec<u8>, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Identifiant automatique (`*`) pour l'horloge `now_ms`, toujours supérieur à `last_id`
+    pub fn next_id(&self, now_ms: u64) -> Option<StreamId> {
+        if now_ms > self.last_id.ms {
+            Some(StreamId::new(now_ms, 0))
+        } else {
+            self.last_id.next()
+        }
+    }
+
+    /// Ajoute une entrée ; `id` doit être supérieur à `last_id`
+    pub fn add(&mut self, id: StreamId, fields: Vec<Vec<u8>>) {
+        self.entries.insert(id, fields);
+        self.last_id = id;
+    }
+
+    /// Garde au plus `max_len` entrées (les plus récentes). Renvoie le nombre d'entrées supprimées.
+    pub fn trim_max_len(&mut self, max_len: usize) -> usize {
+        let excess = self.len().saturating_sub(max_len);
+        for _ in 0..excess {
+            self.entries.pop_first();
+        }
+        excess
+    }
+
+    /// Supprime les entrées d'identifiant inférieur à `min_id`. Renvoie le nombre d'entrées supprimées.
+    pub fn trim_min_id(&mut self, min_id: StreamId) -> usize {
+        let kept = self.entries.split_off(&min_id);
+        let removed = self.len();
+        self.entries = kept;
+        removed
+    }
+}
+
+/// Groupe de consommateurs : position de lecture et entrées délivrées en attente d'acquittement
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConsumerGroup {
+    /// Dernier identifiant délivré par `XREADGROUP ... >`
+    pub last_delivered: StreamId,
+    /// Liste des entrées en attente (PEL) du groupe, avec le consommateur qui les détient
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    #[serde(with = "crate::db::escaped_keys")]
+    pub consumers: HashMap<Vec<u8>, Consumer>,
+}
+
+/// Entrée délivrée mais pas encore acquittée
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingEntry {
+    #[serde(with = "crate::db::escaped")]
+    pub consumer: Vec<u8>,
+    /// Date de la dernière livraison, en millisecondes depuis l'époque Unix
+    pub delivered_at: u64,
+    pub delivery_count: u64,
+}
+
+/// Consommateur d'un groupe, avec sa propre liste d'entrées en attente
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Consumer {
+    /// Dernière activité, en millisecondes depuis l'époque Unix
+    pub seen_at: u64,
+    pub pending: BTreeSet<StreamId>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_delivered: StreamId) -> ConsumerGroup {
+        ConsumerGroup { last_delivered, ..ConsumerGroup::default() }
+    }
+
+    /// Consommateur `name`, créé s'il n'existe pas encore
+    pub fn consumer(&mut self, name: &[u8]) -> &mut Consumer {
+        self.consumers.entry(name.to_vec()).or_default()
+    }
+
+    /// Attribue `id` à `consumer` dans la liste des entrées en attente (en le retirant de son ancien détenteur)
+    pub fn deliver(&mut self, id: StreamId, consumer: &[u8], delivered_at: u64, delivery_count: u64) {
+        let entry = PendingEntry { consumer: consumer.to_vec(), delivered_at, delivery_count };
+        if let Some(previous) = self.pending.insert(id, entry) {
+            if previous.consumer != consumer {
+                if let Some(owner) = self.consumers.get_mut(&previous.consumer) {
+                    owner.pending.remove(&id);
+                }
+            }
+        }
+        let owner = self.consumer(consumer);
+        owner.pending.insert(id);
+        owner.seen_at = owner.seen_at.max(delivered_at);
+    }
+
+    /// Acquitte `id`. Renvoie vrai si l'entrée était en attente.
+    pub fn ack(&mut self, id: StreamId) -> bool {
+        match self.pending.remove(&id) {
+            Some(entry) => {
+                if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+                    owner.pending.remove(&id);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Supprime un consommateur et ses entrées en attente. Renvoie le nombre d'entrées qu'il détenait.
+    pub fn remove_consumer(&mut self, name: &[u8]) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in &consumer.pending {
+            self.pending.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+}
+
+/// Dans le snapshot, les entrées sont une map `"ms-seq"` → liste des champs et valeurs (octets échappés)
+mod entries {
+    use super::StreamId;
+    use crate::db::{escape_bytes, unescape_bytes};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(entries: &BTreeMap<StreamId, Vec<Vec<u8>>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            entries
+                .iter()
+                .map(|(id, fields)| (id, fields.iter().map(|f| escape_bytes(f)).collect::<Vec<_>>())),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<StreamId, Vec<Vec<u8>>>, D::Error> {
+        let entries = BTreeMap::<StreamId, Vec<String>>::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|(id, fields)| (id, fields.iter().map(|f| unescape_bytes(f)).collect()))
+            .collect())
+    }
+}
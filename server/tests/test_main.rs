@@ -1,5 +1,6 @@
 // tests/test_main.rs
-use redust::db::{Db, Entry};
+use redust::db::{Databases, Db, Entry, Keyspace, Value as DbValue};
+use redust::expiry::{self, ExpiryIndex};
 use redust::server;
 use redust::persistence::snapshot;
 use std::net::{TcpListener, TcpStream};
@@ -9,12 +10,23 @@ use std::collections::HashMap;
 use std::thread;
 use std::time::{Duration, SystemTime};
 use redust::persistence;
+use redust::protocol::{self, Reply};
+use redust::geo::{self, Shape};
+use redust::glob;
+use redust::hyperloglog::HyperLogLog;
+use redust::json_path::Path;
+use redust::sorted_set::{ScoreRange, SortedSet};
+use redust::stream::StreamId;
+use redust_client::connection::{Connection, Pipeline, Value};
 use std::fs::{remove_file, OpenOptions};
 
+// snapshot.json et appendonly.aof sont partagés par les tests de persistance, exécutés en parallèle
+static PERSISTENCE_FILES: Mutex<()> = Mutex::new(());
+
 fn start_test_server() -> std::net::SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
     let addr = listener.local_addr().unwrap();
-    let db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
     let (aof_tx, aof_rx) = mpsc::channel::<String>();
 
     // AOF writer pour les tests
@@ -33,16 +45,12 @@ fn start_test_server() -> std::net::SocketAddr {
         }
     });
 
-    // Thread TTL
+    // Thread d'expiration active
     {
-        let ttl_db = db.clone();
+        let expiry_db = db.clone();
+        let expiry_tx = aof_tx.clone();
         thread::spawn(move || {
-            loop {
-                thread::sleep(Duration::from_secs(1));
-                let now = SystemTime::now();
-                let mut db = ttl_db.lock().unwrap();
-                db.retain(|_, entry| entry.expire_at.map_or(true, |exp| exp > now));
-            }
+            expiry::run_active_expiry(expiry_db, expiry_tx);
         });
     }
 
@@ -60,6 +68,32 @@ fn start_test_server() -> std::net::SocketAddr {
     addr
 }
 
+/// Serveur de test dont l'AOF est renvoyé au test au lieu d'être écrit sur disque
+fn start_test_server_with_aof() -> (std::net::SocketAddr, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    let (aof_tx, aof_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let db = db.clone();
+            let aof_tx = aof_tx.clone();
+            thread::spawn(move || {
+                server::handle_client(stream, db, aof_tx);
+            });
+        }
+    });
+    (addr, aof_rx)
+}
+
+/// Rejoue dans une base vide toutes les lignes AOF reçues jusqu'ici
+fn replay_aof(aof_rx: &mpsc::Receiver<String>) -> Db {
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::replay(aof_rx.try_iter(), &db);
+    db
+}
+
 #[test]
 fn test_set_get_update_delete() {
     let addr = start_test_server();
@@ -142,18 +176,20 @@ fn test_quit() {
 
 #[test]
 fn test_snapshot() {
-    let db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let _files = PERSISTENCE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
     {
         let mut db_lock = db.lock().unwrap();
-        db_lock.insert("snapshot_key".to_string(), Entry { value: "snapshot_value".to_string(), expire_at: None });
+        db_lock.insert(b"snapshot_key".to_vec(), Entry::new(DbValue::String(b"snapshot_value".to_vec())));
     }
     snapshot(&db);
     use std::fs::File;
     use serde_json;
     let file = File::open("snapshot.json").unwrap();
-    let loaded: HashMap<String, Entry> = serde_json::from_reader(file).unwrap();
-    assert!(loaded.contains_key("snapshot_key"));
-    assert_eq!(loaded.get("snapshot_key").unwrap().value, "snapshot_value");
+    let loaded: Vec<HashMap<String, Entry>> = serde_json::from_reader(file).unwrap();
+    assert_eq!(loaded.len(), 16);
+    assert!(loaded[0].contains_key("snapshot_key"));
+    assert_eq!(loaded[0].get("snapshot_key").unwrap().value, DbValue::String(b"snapshot_value".to_vec()));
 }
 
 #[test]
@@ -179,12 +215,13 @@ fn test_transaction_exec() {
     assert_eq!(resp.trim(), "QUEUED");
     resp.clear();
 
+    // Une seule réponse numérotée pour toute la transaction
     writeln!(stream, "EXEC").unwrap();
     reader.read_line(&mut resp).unwrap();
-    assert_eq!(resp.trim(), "OK");
+    assert_eq!(resp.trim(), "1) OK");
     resp.clear();
     reader.read_line(&mut resp).unwrap();
-    assert_eq!(resp.trim(), "txvalue");
+    assert_eq!(resp.trim(), "2) txvalue");
 }
 
 #[test]
@@ -215,17 +252,157 @@ fn test_transaction_discard() {
     assert_eq!(resp.trim(), "nil");
 }
 
+#[test]
+fn test_transaction_errors() {
+    let addr = start_test_server();
+    let mut conn = Connection::connect(addr).unwrap();
+    let ok = Value::Simple("OK".to_string());
+    let queued = Value::Simple("QUEUED".to_string());
+    let error = |s: &str| Value::Error(s.to_string());
+
+    assert_eq!(conn.command(&[b"EXEC"]).unwrap(), error("ERR EXEC without MULTI"));
+    assert_eq!(conn.command(&[b"DISCARD"]).unwrap(), error("ERR DISCARD without MULTI"));
+
+    // Les erreurs détectées à la mise en file annulent toute la transaction
+    assert_eq!(conn.command(&[b"MULTI"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"tx:a", b"1"]).unwrap(), queued);
+    assert_eq!(conn.command(&[b"NOSUCHCOMMAND", b"x"]).unwrap(), error("ERR Commande inconnue"));
+    assert_eq!(conn.command(&[b"GET"]).unwrap(), error("ERR wrong number of arguments for 'get' command"));
+    assert_eq!(conn.command(&[b"LLEN", b"a", b"b"]).unwrap(), error("ERR wrong number of arguments for 'llen' command"));
+    assert_eq!(conn.command(&[b"SET", b"tx:b", b"2"]).unwrap(), queued);
+    assert_eq!(
+        conn.command(&[b"EXEC"]).unwrap(),
+        error("EXECABORT Transaction discarded because of previous errors.")
+    );
+    assert_eq!(conn.command(&[b"EXISTS", b"tx:a", b"tx:b"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXEC"]).unwrap(), error("ERR EXEC without MULTI"));
+
+    // MULTI imbriqué : erreur, sans annuler la transaction en cours
+    conn.command(&[b"MULTI"]).unwrap();
+    assert_eq!(conn.command(&[b"MULTI"]).unwrap(), error("ERR MULTI calls can not be nested"));
+    assert_eq!(conn.command(&[b"SET", b"tx:a", b"1"]).unwrap(), queued);
+    assert_eq!(conn.command(&[b"EXEC"]).unwrap(), Value::Array(vec![ok.clone()]));
+
+    // Une erreur à l'exécution n'empêche pas les autres commandes : une réponse par commande
+    conn.command(&[b"MULTI"]).unwrap();
+    conn.command(&[b"INCR", b"tx:a"]).unwrap();
+    conn.command(&[b"LPUSH", b"tx:a", b"x"]).unwrap();
+    conn.command(&[b"SET", b"tx:b", b"2", b"FOO"]).unwrap();
+    conn.command(&[b"RPUSH", b"tx:list", b"x", b"y"]).unwrap();
+    conn.command(&[b"LRANGE", b"tx:list", b"0", b"-1"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"EXEC"]).unwrap(),
+        Value::Array(vec![
+            Value::Integer(2),
+            error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            error("ERR syntax error"),
+            Value::Integer(2),
+            Value::Array(vec![Value::Bulk(b"x".to_vec()), Value::Bulk(b"y".to_vec())]),
+        ])
+    );
+
+    // Une transaction annulée par DISCARD repart de zéro
+    conn.command(&[b"MULTI"]).unwrap();
+    conn.command(&[b"NOSUCHCOMMAND"]).unwrap();
+    assert_eq!(conn.command(&[b"DISCARD"]).unwrap(), ok);
+    conn.command(&[b"MULTI"]).unwrap();
+    assert_eq!(conn.command(&[b"EXEC"]).unwrap(), Value::Array(Vec::new()));
+
+    // Client inline : les réponses imbriquées restent numérotées
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    for line in ["MULTI", "GET tx:a", "LRANGE tx:list 0 -1", "EXEC"] {
+        writeln!(stream, "{}", line).unwrap();
+    }
+    let lines: Vec<String> = (0..5)
+        .map(|_| {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.trim_end().to_string()
+        })
+        .collect();
+    assert_eq!(lines, ["OK", "QUEUED", "QUEUED", "1) 2", "2) 1) x"]);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line.trim_end(), "   2) y");
+}
+
+#[test]
+fn test_aof_transactions() {
+    let _files = PERSISTENCE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    conn.command(&[b"SET", b"a", b"0"]).unwrap();
+    conn.command(&[b"MULTI"]).unwrap();
+    conn.command(&[b"SET", b"a", b"1"]).unwrap();
+    conn.command(&[b"SELECT", b"2"]).unwrap();
+    conn.command(&[b"INCR", b"b"]).unwrap();
+    conn.command(&[b"EXEC"]).unwrap();
+    // Une transaction qui n'écrit rien ne laisse pas de marqueurs
+    conn.command(&[b"MULTI"]).unwrap();
+    conn.command(&[b"GET", b"b"]).unwrap();
+    conn.command(&[b"EXEC"]).unwrap();
+    conn.command(&[b"SET", b"c", b"after"]).unwrap();
+
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert_eq!(lines.iter().filter(|line| *line == "MULTI").count(), 1);
+    let multi = lines.iter().position(|line| line == "MULTI").unwrap();
+    let exec = lines.iter().position(|line| line == "EXEC").unwrap();
+    assert_eq!(lines[..multi], ["SET a 0"]);
+    assert_eq!(lines[multi + 1], "SET a 1");
+    assert_eq!(lines[multi + 2], "SELECT 2");
+    assert_eq!(exec, lines.len() - 2);
+
+    let value = |db: &Db, index: usize, key: &[u8]| db.lock().unwrap().keyspace(index).get(key).map(|entry| entry.value.clone());
+    let string = |s: &str| Some(DbValue::String(s.as_bytes().to_vec()));
+
+    // AOF complet : la transaction est rejouée, dans les bases où elle a écrit
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    assert_eq!(persistence::replay(lines.clone(), &db), 0);
+    assert_eq!(value(&db, 0, b"a"), string("1"));
+    assert_eq!(value(&db, 2, b"b"), string("1"));
+    assert_eq!(value(&db, 2, b"c"), string("after"));
+
+    // Arrêt brutal avant l'écriture du EXEC, ou au milieu de sa ligne : rien de la transaction n'est rejoué
+    for truncated in [lines[..exec].to_vec(), [&lines[..exec], &["EX".to_string()]].concat()] {
+        let db: Db = Arc::new(Mutex::new(Databases::default()));
+        assert_eq!(persistence::replay(truncated.clone(), &db), truncated.len() - multi - 1);
+        assert_eq!(value(&db, 0, b"a"), string("0"));
+        assert!(db.lock().unwrap().keyspace(2).is_empty());
+    }
+
+    // Arrêt brutal, redémarrage, écriture puis nouveau redémarrage : la transaction inachevée est
+    // retirée du fichier au chargement, et l'écriture faite après le premier redémarrage survit
+    let _ = remove_file("snapshot.json");
+    let _ = remove_file("appendonly.aof");
+    std::fs::write("appendonly.aof", format!("{}\nEX", lines[..exec].join("\n"))).unwrap();
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::restore_state(&db);
+    assert_eq!(value(&db, 0, b"a"), string("0"));
+    assert_eq!(std::fs::read_to_string("appendonly.aof").unwrap(), "SET a 0\n");
+    {
+        let mut aof_file = OpenOptions::new().append(true).open("appendonly.aof").unwrap();
+        writeln!(aof_file, "SET z 3").unwrap();
+    }
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::restore_state(&db);
+    assert_eq!(value(&db, 0, b"a"), string("0"));
+    assert_eq!(value(&db, 0, b"z"), string("3"));
+    let _ = remove_file("appendonly.aof");
+}
+
 #[test]
 fn test_restore_state() {
+    let _files = PERSISTENCE_FILES.lock().unwrap_or_else(|e| e.into_inner());
     let _ = remove_file("snapshot.json");
     let _ = remove_file("appendonly.aof");
 
     // 1. Création d'une base de données initiale et insertion d'entrées.
-    let db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
     {
         let mut db_lock = db.lock().unwrap();
-        db_lock.insert("key1".to_string(), Entry { value: "value1".to_string(), expire_at: None });
-        db_lock.insert("key2".to_string(), Entry { value: "value2".to_string(), expire_at: None });
+        db_lock.insert(b"key1".to_vec(), Entry::new(DbValue::String(b"value1".to_vec())));
+        db_lock.insert(b"key2".to_vec(), Entry::new(DbValue::String(b"value2".to_vec())));
     }
     
     persistence::snapshot(&db);
@@ -246,18 +423,2451 @@ fn test_restore_state() {
     }
 
     // Pour simuler un crash, on crée une nouvelle base vide.
-    let new_db: Db = Arc::new(Mutex::new(HashMap::new()));
+    let new_db: Db = Arc::new(Mutex::new(Databases::default()));
 
     persistence::restore_state(&new_db);
 
     let new_db_lock = new_db.lock().unwrap();
 
-    assert_eq!(new_db_lock.get("key1").unwrap().value, "new_value1");
+    assert_eq!(new_db_lock.get(b"key1".as_slice()).unwrap().value, DbValue::String(b"new_value1".to_vec()));
 
-    assert!(new_db_lock.get("key2").is_none());
+    assert!(new_db_lock.get(b"key2".as_slice()).is_none());
 
-    assert_eq!(new_db_lock.get("key3").unwrap().value, "value3");
+    assert_eq!(new_db_lock.get(b"key3".as_slice()).unwrap().value, DbValue::String(b"value3".to_vec()));
 
     let _ = remove_file("snapshot.json");
     let _ = remove_file("appendonly.aof");
 }
+
+#[test]
+fn test_snapshot_truncates_aof() {
+    let _files = PERSISTENCE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = remove_file("snapshot.json");
+    let _ = remove_file("appendonly.aof");
+
+    let append = |line: &str| {
+        let mut aof_file = OpenOptions::new().create(true).append(true).open("appendonly.aof").unwrap();
+        writeln!(aof_file, "{}", line).unwrap();
+    };
+
+    // LPUSH n'est pas idempotent : rejouée après le snapshot, la ligne doublerait la liste
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::apply_command("LPUSH l a b", &db);
+    append("LPUSH l a b");
+    persistence::apply_command("SELECT 3", &db);
+    append("SELECT 3");
+    snapshot(&db);
+    assert_eq!(std::fs::read("appendonly.aof").unwrap(), b"");
+    // L'AOF vidé repart de la base 0
+    assert_eq!(db.lock().unwrap().aof_index(), 0);
+
+    append("LPUSH l c");
+    let new_db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::restore_state(&new_db);
+    assert_eq!(
+        new_db.lock().unwrap().get(b"l".as_slice()).unwrap().value,
+        DbValue::List([b"c".to_vec(), b"b".to_vec(), b"a".to_vec()].into())
+    );
+
+    let _ = remove_file("snapshot.json");
+    let _ = remove_file("appendonly.aof");
+}
+
+#[test]
+fn test_resp_set_get() {
+    let addr = start_test_server();
+    thread::sleep(Duration::from_millis(100));
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut read_reply = |expected: &[u8]| {
+        let mut resp = vec![0u8; expected.len()];
+        std::io::Read::read_exact(&mut reader, &mut resp).unwrap();
+        assert_eq!(resp, expected);
+    };
+
+    stream.write_all(b"*3\r\n$3\r\nSET\r\n$7\r\nrespkey\r\n$9\r\nrespvalue\r\n").unwrap();
+    read_reply(b"+OK\r\n");
+
+    stream.write_all(b"*2\r\n$3\r\nGET\r\n$7\r\nrespkey\r\n").unwrap();
+    read_reply(b"$9\r\nrespvalue\r\n");
+
+    stream.write_all(b"*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n").unwrap();
+    read_reply(b"$-1\r\n");
+
+    stream.write_all(b"*1\r\n$7\r\nUNKNOWN\r\n").unwrap();
+    read_reply(b"-ERR Commande inconnue\r\n");
+
+    // EXEC renvoie un tableau contenant la réponse de chaque commande
+    stream.write_all(b"*1\r\n$5\r\nMULTI\r\n*2\r\n$3\r\nGET\r\n$7\r\nrespkey\r\n*1\r\n$4\r\nEXEC\r\n").unwrap();
+    read_reply(b"+OK\r\n+QUEUED\r\n*1\r\n$9\r\nrespvalue\r\n");
+
+    // Le format texte reste accepté sur la même connexion
+    writeln!(stream, "GET respkey").unwrap();
+    read_reply(b"respvalue\n");
+}
+
+#[test]
+fn test_parse_request() {
+    let full = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n";
+    // Une requête incomplète n'est pas décodée
+    for len in 0..full.len() {
+        assert_eq!(protocol::parse_request(&full[..len]).unwrap(), None);
+    }
+    let (request, consumed) = protocol::parse_request(full).unwrap().unwrap();
+    assert_eq!(request.args, vec![b"GET".to_vec(), b"key".to_vec()]);
+    assert!(!request.inline);
+    assert_eq!(consumed, full.len());
+
+    let (request, consumed) = protocol::parse_request(b"SET  a b\r\nGET a").unwrap().unwrap();
+    assert_eq!(request.args, vec![b"SET".to_vec(), b"a".to_vec(), b"b".to_vec()]);
+    assert!(request.inline);
+    assert_eq!(consumed, 10);
+
+    assert!(protocol::parse_request(b"*1\r\n+GET\r\n").is_err());
+
+    // Le nombre d'arguments annoncé ne dimensionne pas la préallocation, mais reste respecté
+    assert_eq!(protocol::parse_request(b"*1048576\r\n$1\r\na\r\n").unwrap(), None);
+    let many: Vec<u8> = [b"*2000\r\n".to_vec(), b"$1\r\na\r\n".repeat(2000)].concat();
+    let (request, consumed) = protocol::parse_request(&many).unwrap().unwrap();
+    assert_eq!(request.args.len(), 2000);
+    assert_eq!(consumed, many.len());
+
+    // Un décodeur de connexion reprend la requête là où il s'était arrêté, octet après octet
+    let stream = [&full[..], b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$5\r\nvalue\r\n"].concat();
+    let mut parser = protocol::RequestParser::default();
+    let (mut start, mut requests) = (0, Vec::new());
+    for len in 1..=stream.len() {
+        if let Some((request, consumed)) = parser.parse(&stream[start..len]).unwrap() {
+            requests.push(request.args);
+            start += consumed;
+        }
+    }
+    assert_eq!(start, stream.len());
+    assert_eq!(requests, [vec![b"GET".to_vec(), b"key".to_vec()], vec![b"SET".to_vec(), b"k".to_vec(), b"value".to_vec()]]);
+    // Après une erreur, le décodeur repart de zéro
+    assert!(parser.parse(b"*1\r\n+GET\r\n").is_err());
+    assert_eq!(parser.parse(full).unwrap().unwrap().1, full.len());
+
+    let mut out = Vec::new();
+    Reply::Array(vec![Reply::Integer(-3), Reply::Nil, Reply::error("ERR boom")]).encode(2, &mut out);
+    assert_eq!(out, b"*3\r\n:-3\r\n$-1\r\n-ERR boom\r\n");
+}
+
+#[test]
+fn test_hello_resp3() {
+    let addr = start_test_server();
+    thread::sleep(Duration::from_millis(100));
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+
+    stream.write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n4\r\n").unwrap();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("-NOPROTO"));
+
+    // Le passage en RESP3 renvoie une map d'informations sur le serveur
+    stream.write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n").unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "%7\r\n");
+    let mut fields = Vec::new();
+    for _ in 0..25 {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if !line.starts_with('$') && !line.starts_with('*') {
+            fields.push(line.trim_end().to_string());
+        }
+    }
+    assert!(fields.windows(2).any(|w| w[0] == "proto" && w[1] == ":3"));
+
+    // nil est désormais encodé avec le type RESP3 dédié
+    stream.write_all(b"*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n").unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "_\r\n");
+}
+
+#[test]
+fn test_reply_resp3_encoding() {
+    let reply = Reply::Map(vec![
+        (Reply::Simple("a".to_string()), Reply::Double(1.5)),
+        (Reply::Simple("b".to_string()), Reply::Set(vec![Reply::Boolean(true), Reply::BigNumber("123".to_string())])),
+    ]);
+
+    let mut resp3 = Vec::new();
+    reply.encode(3, &mut resp3);
+    assert_eq!(resp3, b"%2\r\n+a\r\n,1.5\r\n+b\r\n~2\r\n#t\r\n(123\r\n");
+
+    // En RESP2, chaque type est ramené à son équivalent le plus proche
+    let mut resp2 = Vec::new();
+    reply.encode(2, &mut resp2);
+    assert_eq!(resp2, b"*4\r\n+a\r\n$3\r\n1.5\r\n+b\r\n*2\r\n:1\r\n$3\r\n123\r\n");
+
+    let mut push = Vec::new();
+    Reply::Push(vec![Reply::Double(f64::INFINITY), Reply::Nil]).encode(3, &mut push);
+    assert_eq!(push, b">2\r\n,inf\r\n_\r\n");
+}
+
+#[test]
+fn test_binary_safe_values() {
+    let addr = start_test_server();
+    thread::sleep(Duration::from_millis(100));
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut resp = String::new();
+
+    // Arguments entre guillemets avec échappements, comme dans redis-cli
+    stream.write_all(br#"SET "key with spaces" "{\"name\": \"redust\"}\n\x00""#).unwrap();
+    stream.write_all(b"\n").unwrap();
+    reader.read_line(&mut resp).unwrap();
+    assert_eq!(resp.trim(), "OK");
+
+    // La valeur est relue en RESP à l'identique, octet par octet
+    stream.write_all(b"*2\r\n$3\r\nGET\r\n$15\r\nkey with spaces\r\n").unwrap();
+    let mut value = vec![0u8; 27];
+    std::io::Read::read_exact(&mut reader, &mut value).unwrap();
+    assert_eq!(value, b"$20\r\n{\"name\": \"redust\"}\n\x00\r\n");
+
+    // En mode texte, la valeur est renvoyée entre guillemets pour rester sur une ligne
+    resp.clear();
+    writeln!(stream, "GET 'key with spaces'").unwrap();
+    reader.read_line(&mut resp).unwrap();
+    assert_eq!(resp.trim_end(), r#""{\"name\": \"redust\"}\n\x00""#);
+
+    resp.clear();
+    writeln!(stream, "GET \"unbalanced").unwrap();
+    reader.read_line(&mut resp).unwrap();
+    assert!(resp.starts_with("ERR: Protocol error"));
+}
+
+#[test]
+fn test_split_args_round_trip() {
+    let values: Vec<&[u8]> = vec![b"plain", b"", b"with space", b"quote\"and\\slash", b"\x00\xff\r\n", "éà".as_bytes()];
+    let line = protocol::format_command(&values);
+    assert_eq!(protocol::split_args(line.as_bytes()).unwrap(), values);
+
+    assert_eq!(
+        protocol::split_args(b"SET 'it\\'s' \"a\\x41\\tb\"").unwrap(),
+        vec![b"SET".to_vec(), b"it's".to_vec(), b"aA\tb".to_vec()]
+    );
+    assert!(protocol::split_args(b"GET \"open").is_none());
+    assert!(protocol::split_args(b"GET \"closed\"suffix").is_none());
+}
+
+#[test]
+fn test_apply_command_quoted() {
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::apply_command(r#"SET "a key" "line1\nline2\xff" TTL 4102444800"#, &db);
+    persistence::apply_command(r#"SET other "to delete""#, &db);
+    persistence::apply_command("DELETE other", &db);
+
+    let db_lock = db.lock().unwrap();
+    let entry = db_lock.get(b"a key".as_slice()).unwrap();
+    assert_eq!(entry.value, DbValue::String(b"line1\nline2\xff".to_vec()));
+    assert!(entry.expire_at.is_some());
+    assert!(db_lock.get(b"other".as_slice()).is_none());
+}
+
+#[test]
+fn test_snapshot_escaping() {
+    let mut db = HashMap::new();
+    db.insert(b"bin\xfe\\key".to_vec(), Entry::new(DbValue::String(b"caf\xc3\xa9 \x00\\x41".to_vec())));
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&db, &mut serde_json::Serializer::new(&mut json)).unwrap();
+    assert_eq!(String::from_utf8(json.clone()).unwrap(), r#"{"bin\\xfe\\\\key":{"value":{"string":"café \u0000\\\\x41"},"expire_at":null}}"#);
+
+    let loaded: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(loaded.get(b"bin\xfe\\key".as_slice()).unwrap().value, DbValue::String(b"caf\xc3\xa9 \x00\\x41".to_vec()));
+
+    // Les snapshots écrits avant l'introduction des types restent lisibles
+    let legacy: HashMap<String, Entry> = serde_json::from_str(r#"{"old":{"value":"plain","expire_at":null}}"#).unwrap();
+    assert_eq!(legacy.get("old").unwrap().value, DbValue::String(b"plain".to_vec()));
+    // Leurs clés et leurs valeurs n'étaient pas échappées : une barre oblique inverse reste telle quelle,
+    // alors qu'une entrée typée de la même table garde sa clé échappée
+    let legacy = Databases::from_snapshot(
+        br#"{"path":{"value":"C:\\temp\\x41","expire_at":null},"a\\x41":{"value":"v","expire_at":null},"bin\\xfe":{"value":{"string":"w"},"expire_at":null}}"#,
+    )
+    .unwrap();
+    assert_eq!(legacy.get(b"path".as_slice()).unwrap().value, DbValue::String(br"C:\temp\x41".to_vec()));
+    assert_eq!(legacy.get(br"a\x41".as_slice()).unwrap().value, DbValue::String(b"v".to_vec()));
+    assert_eq!(legacy.get(b"bin\xfe".as_slice()).unwrap().value, DbValue::String(b"w".to_vec()));
+    assert_eq!(legacy.len(), 3);
+}
+
+#[test]
+fn test_pipelined_sets() {
+    let addr = start_test_server();
+    thread::sleep(Duration::from_millis(100));
+    let mut conn = Connection::connect(addr).unwrap();
+
+    // 10 000 SET envoyés en une seule écriture, réponses lues ensuite dans l'ordre
+    let mut pipeline = Pipeline::new();
+    let keys: Vec<String> = (0..10_000).map(|i| format!("pipe:{}", i)).collect();
+    for (i, key) in keys.iter().enumerate() {
+        pipeline.cmd(&[b"SET", key.as_bytes(), i.to_string().as_bytes()]);
+    }
+    let replies = conn.execute(&pipeline).unwrap();
+    assert_eq!(replies.len(), 10_000);
+    assert!(replies.iter().all(|r| *r == Value::Simple("OK".to_string())));
+
+    // L'ordre des réponses suit celui des commandes
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .cmd(&[b"GET", b"pipe:0"])
+        .cmd(&[b"GET", b"missing"])
+        .cmd(&[b"GET", b"pipe:9999"]);
+    assert_eq!(
+        conn.execute(&pipeline).unwrap(),
+        vec![Value::Bulk(b"0".to_vec()), Value::Nil, Value::Bulk(b"9999".to_vec())]
+    );
+
+    // Les commandes texte envoyées d'un bloc sont elles aussi traitées dans l'ordre
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    stream.write_all(b"GET pipe:1\nGET pipe:2\nGET pipe:3\n").unwrap();
+    for expected in ["1", "2", "3"] {
+        let mut resp = String::new();
+        reader.read_line(&mut resp).unwrap();
+        assert_eq!(resp.trim(), expected);
+    }
+}
+
+#[test]
+fn test_counters() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+
+    assert_eq!(conn.command(&[b"INCR", b"hits"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"INCRBY", b"hits", b"41"]).unwrap(), Value::Integer(42));
+    assert_eq!(conn.command(&[b"DECR", b"hits"]).unwrap(), Value::Integer(41));
+    assert_eq!(conn.command(&[b"DECRBY", b"hits", b"50"]).unwrap(), Value::Integer(-9));
+    assert_eq!(conn.command(&[b"DECR", b"fresh"]).unwrap(), Value::Integer(-1));
+
+    assert_eq!(conn.command(&[b"INCRBYFLOAT", b"ratio", b"10.5"]).unwrap(), Value::Bulk(b"10.5".to_vec()));
+    assert_eq!(conn.command(&[b"INCRBYFLOAT", b"ratio", b"-0.25"]).unwrap(), Value::Bulk(b"10.25".to_vec()));
+    assert_eq!(conn.command(&[b"INCRBYFLOAT", b"hits", b"1.5"]).unwrap(), Value::Bulk(b"-7.5".to_vec()));
+
+    // Valeurs non numériques et dépassements
+    conn.command(&[b"SET", b"text", b"hello"]).unwrap();
+    assert_eq!(conn.command(&[b"INCR", b"text"]).unwrap(), Value::Error("ERR value is not an integer or out of range".to_string()));
+    assert_eq!(conn.command(&[b"INCR", b"ratio"]).unwrap(), Value::Error("ERR value is not an integer or out of range".to_string()));
+    assert_eq!(conn.command(&[b"INCRBYFLOAT", b"text", b"1"]).unwrap(), Value::Error("ERR value is not a valid float".to_string()));
+    assert_eq!(conn.command(&[b"INCRBY", b"hits", b"abc"]).unwrap(), Value::Error("ERR value is not an integer or out of range".to_string()));
+    conn.command(&[b"SET", b"big", i64::MAX.to_string().as_bytes()]).unwrap();
+    assert_eq!(conn.command(&[b"INCR", b"big"]).unwrap(), Value::Error("ERR increment or decrement would overflow".to_string()));
+    assert_eq!(conn.command(&[b"INCRBYFLOAT", b"ratio", b"inf"]).unwrap(), Value::Error("ERR increment would produce NaN or Infinity".to_string()));
+    assert_eq!(conn.command(&[b"INCR"]).unwrap(), Value::Error("ERR wrong number of arguments for 'incr' command".to_string()));
+
+    // Le rejeu de l'AOF reproduit exactement les valeurs, flottants compris
+    let replayed = replay_aof(&aof_rx);
+    let replayed = replayed.lock().unwrap();
+    assert_eq!(replayed.get(b"hits".as_slice()).unwrap().value, DbValue::String(b"-7.5".to_vec()));
+    assert_eq!(replayed.get(b"ratio".as_slice()).unwrap().value, DbValue::String(b"10.25".to_vec()));
+    assert_eq!(replayed.get(b"fresh".as_slice()).unwrap().value, DbValue::String(b"-1".to_vec()));
+}
+
+#[test]
+fn test_concurrent_incr() {
+    let (addr, _aof_rx) = start_test_server_with_aof();
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut conn = Connection::connect(addr).unwrap();
+                for _ in 0..250 {
+                    conn.command(&[b"INCR", b"shared"]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let mut conn = Connection::connect(addr).unwrap();
+    assert_eq!(conn.command(&[b"GET", b"shared"]).unwrap(), Value::Bulk(b"2000".to_vec()));
+}
+
+#[test]
+fn test_set_options() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let ok = Value::Simple("OK".to_string());
+
+    // Verrou distribué : seul le premier SET NX réussit
+    assert_eq!(conn.command(&[b"SET", b"lock", b"owner1", b"NX", b"PX", b"30000"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"lock", b"owner2", b"NX", b"PX", b"30000"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"lock"]).unwrap(), Value::Bulk(b"owner1".to_vec()));
+
+    // XX ne crée pas la clé, GET renvoie l'ancienne valeur
+    assert_eq!(conn.command(&[b"SET", b"absent", b"v", b"XX"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"absent"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"SET", b"lock", b"owner3", b"XX", b"GET", b"KEEPTTL"]).unwrap(), Value::Bulk(b"owner1".to_vec()));
+    assert_eq!(conn.command(&[b"SET", b"newkey", b"v", b"GET"]).unwrap(), Value::Nil);
+
+    // SET remplace désormais une clé existante, et supprime son expiration sans KEEPTTL
+    assert_eq!(conn.command(&[b"SET", b"newkey", b"v2", b"EX", b"100"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"newkey", b"v3"]).unwrap(), ok);
+
+    // Expirations relatives et absolues
+    let now_ms = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert_eq!(conn.command(&[b"SET", b"px", b"v", b"PX", b"100"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"pxat", b"v", b"PXAT", (now_ms + 100).to_string().as_bytes()]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"exat", b"v", b"EXAT", (now_ms / 1000 + 100).to_string().as_bytes()]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"SET", b"legacy", b"v", b"TTL", b"100"]).unwrap(), ok);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(conn.command(&[b"GET", b"px"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"pxat"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"exat"]).unwrap(), Value::Bulk(b"v".to_vec()));
+    assert_eq!(conn.command(&[b"GET", b"legacy"]).unwrap(), Value::Bulk(b"v".to_vec()));
+
+    // Combinaisons invalides
+    let syntax = Value::Error("ERR syntax error".to_string());
+    assert_eq!(conn.command(&[b"SET", b"k", b"v", b"NX", b"XX"]).unwrap(), syntax);
+    assert_eq!(conn.command(&[b"SET", b"k", b"v", b"EX", b"10", b"PX", b"10"]).unwrap(), syntax);
+    assert_eq!(conn.command(&[b"SET", b"k", b"v", b"KEEPTTL", b"EX", b"10"]).unwrap(), syntax);
+    assert_eq!(conn.command(&[b"SET", b"k", b"v", b"EX"]).unwrap(), syntax);
+    assert_eq!(conn.command(&[b"SET", b"k", b"v", b"EX", b"0"]).unwrap(), Value::Error("ERR invalid expire time in 'set' command".to_string()));
+
+    // UPDATE reste un alias de SET XX
+    assert_eq!(conn.command(&[b"UPDATE", b"missing", b"v"]).unwrap(), Value::Error("ERR La clé n'existe pas.".to_string()));
+    assert_eq!(conn.command(&[b"UPDATE", b"newkey", b"v4", b"TTL", b"100"]).unwrap(), ok);
+
+    // L'AOF contient des expirations absolues : le rejeu retrouve les mêmes échéances
+    let live = conn.command(&[b"GET", b"lock"]).unwrap();
+    assert_eq!(live, Value::Bulk(b"owner3".to_vec()));
+    let replayed = replay_aof(&aof_rx);
+    let replayed = replayed.lock().unwrap();
+    let lock = replayed.get(b"lock".as_slice()).unwrap();
+    assert_eq!(lock.value, DbValue::String(b"owner3".to_vec()));
+    let lock_expiry = lock.expire_at.unwrap().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert!(lock_expiry > now_ms && lock_expiry <= now_ms + 30_000);
+    assert!(replayed.get(b"newkey".as_slice()).unwrap().expire_at.is_some());
+    assert!(replayed.get(b"absent".as_slice()).is_none());
+}
+
+#[test]
+fn test_string_commands() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &[u8]| Value::Bulk(s.to_vec());
+    let ok = Value::Simple("OK".to_string());
+
+    assert_eq!(conn.command(&[b"APPEND", b"log", b"Hello"]).unwrap(), Value::Integer(5));
+    assert_eq!(conn.command(&[b"APPEND", b"log", b" World"]).unwrap(), Value::Integer(11));
+    assert_eq!(conn.command(&[b"STRLEN", b"log"]).unwrap(), Value::Integer(11));
+    assert_eq!(conn.command(&[b"STRLEN", b"missing"]).unwrap(), Value::Integer(0));
+
+    assert_eq!(conn.command(&[b"GETRANGE", b"log", b"0", b"4"]).unwrap(), bulk(b"Hello"));
+    assert_eq!(conn.command(&[b"GETRANGE", b"log", b"-5", b"-1"]).unwrap(), bulk(b"World"));
+    assert_eq!(conn.command(&[b"GETRANGE", b"log", b"6", b"100"]).unwrap(), bulk(b"World"));
+    assert_eq!(conn.command(&[b"GETRANGE", b"log", b"5", b"2"]).unwrap(), bulk(b""));
+    assert_eq!(conn.command(&[b"GETRANGE", b"log", b"-1", b"-5"]).unwrap(), bulk(b""));
+    assert_eq!(conn.command(&[b"GETRANGE", b"missing", b"0", b"-1"]).unwrap(), bulk(b""));
+
+    assert_eq!(conn.command(&[b"SETRANGE", b"log", b"6", b"Redis"]).unwrap(), Value::Integer(11));
+    assert_eq!(conn.command(&[b"GET", b"log"]).unwrap(), bulk(b"Hello Redis"));
+    assert_eq!(conn.command(&[b"SETRANGE", b"padded", b"3", b"x"]).unwrap(), Value::Integer(4));
+    assert_eq!(conn.command(&[b"GET", b"padded"]).unwrap(), bulk(b"\0\0\0x"));
+    assert_eq!(conn.command(&[b"SETRANGE", b"empty", b"10", b""]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"GET", b"empty"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"SETRANGE", b"log", b"-1", b"x"]).unwrap(), Value::Error("ERR offset is out of range".to_string()));
+    assert_eq!(
+        conn.command(&[b"SETRANGE", b"log", b"536870911", b"xy"]).unwrap(),
+        Value::Error("ERR string exceeds maximum allowed size (proto-max-bulk-len)".to_string())
+    );
+
+    // MSET / MGET, et MSETNX tout ou rien
+    assert_eq!(conn.command(&[b"MSET", b"a", b"1", b"b", b"2"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"MSET", b"a", b"1", b"b"]).unwrap(), Value::Error("ERR wrong number of arguments for 'mset' command".to_string()));
+    conn.command(&[b"LPUSH", b"list", b"x"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"MGET", b"a", b"missing", b"b", b"list"]).unwrap(),
+        Value::Array(vec![bulk(b"1"), Value::Nil, bulk(b"2"), Value::Nil])
+    );
+    assert_eq!(conn.command(&[b"MSETNX", b"c", b"3", b"a", b"9"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"GET", b"c"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"a"]).unwrap(), bulk(b"1"));
+    assert_eq!(conn.command(&[b"MSETNX", b"c", b"3", b"d", b"4"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"MGET", b"c", b"d"]).unwrap(), Value::Array(vec![bulk(b"3"), bulk(b"4")]));
+
+    // GETDEL et GETEX
+    assert_eq!(conn.command(&[b"GETDEL", b"d"]).unwrap(), bulk(b"4"));
+    assert_eq!(conn.command(&[b"GETDEL", b"d"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"GETDEL", b"list"]).unwrap(),
+        Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+    );
+    assert_eq!(conn.command(&[b"GETEX", b"a", b"PX", b"100"]).unwrap(), bulk(b"1"));
+    assert_eq!(conn.command(&[b"GETEX", b"b", b"EX", b"100"]).unwrap(), bulk(b"2"));
+    assert_eq!(conn.command(&[b"SET", b"kept", b"v", b"EX", b"100"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"GETEX", b"kept", b"PERSIST"]).unwrap(), bulk(b"v"));
+    assert_eq!(conn.command(&[b"GETEX", b"kept"]).unwrap(), bulk(b"v"));
+    assert_eq!(conn.command(&[b"GETEX", b"missing", b"EX", b"10"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GETEX", b"a", b"EX", b"0"]).unwrap(), Value::Error("ERR invalid expire time in 'getex' command".to_string()));
+    assert_eq!(conn.command(&[b"GETEX", b"a", b"PERSIST", b"EX", b"1"]).unwrap(), Value::Error("ERR syntax error".to_string()));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(conn.command(&[b"GET", b"a"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"GET", b"b"]).unwrap(), bulk(b"2"));
+    assert_eq!(conn.command(&[b"APPEND", b"b", b"!"]).unwrap(), Value::Integer(2));
+
+    // APPEND est journalisé comme un SET de la valeur complète, qui garde l'expiration :
+    // rejouer la même ligne deux fois ne double pas l'ajout
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert!(lines.iter().all(|line| !line.starts_with("APPEND ")), "{:?}", lines);
+    let (tx, rx) = mpsc::channel();
+    assert!(lines.last().unwrap().starts_with("SET b 2! PXAT "), "{:?}", lines);
+    for line in lines.iter().chain(lines.last()) {
+        tx.send(line.clone()).unwrap();
+    }
+
+    // Le rejeu de l'AOF redonne les mêmes chaînes et les mêmes expirations
+    let replayed = replay_aof(&rx);
+    let mut replayed = replayed.lock().unwrap();
+    for key in ["log", "padded", "b", "c", "kept", "a", "d", "empty"] {
+        let live = conn.command(&[b"GET", key.as_bytes()]).unwrap();
+        let value = redust::db::get_live(&mut replayed, key.as_bytes()).map(|entry| match &entry.value {
+            DbValue::String(value) => Value::Bulk(value.clone()),
+            other => panic!("chaîne attendue, reçu {:?}", other),
+        });
+        assert_eq!(value.unwrap_or(Value::Nil), live, "clé {}", key);
+    }
+    assert!(replayed.get(b"b".as_slice()).unwrap().expire_at.is_some());
+    assert!(replayed.get(b"kept".as_slice()).unwrap().expire_at.is_none());
+}
+
+#[test]
+fn test_expire_commands() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let now_ms = || SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as i64;
+    let int = |reply: Value| match reply {
+        Value::Integer(n) => n,
+        other => panic!("entier attendu, reçu {:?}", other),
+    };
+
+    conn.command(&[b"SET", b"k", b"v"]).unwrap();
+    assert_eq!(conn.command(&[b"TTL", b"missing"]).unwrap(), Value::Integer(-2));
+    assert_eq!(conn.command(&[b"PTTL", b"k"]).unwrap(), Value::Integer(-1));
+    assert_eq!(conn.command(&[b"EXPIRETIME", b"k"]).unwrap(), Value::Integer(-1));
+    assert_eq!(conn.command(&[b"EXPIRE", b"missing", b"10"]).unwrap(), Value::Integer(0));
+
+    // Conditions NX / XX / GT / LT
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"100", b"XX"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"100", b"GT"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"100", b"NX"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"200", b"NX"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"50", b"GT"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"200", b"GT"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"300", b"LT"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"150", b"XX", b"LT"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"TTL", b"k"]).unwrap(), Value::Integer(150));
+    let pttl = int(conn.command(&[b"PTTL", b"k"]).unwrap());
+    assert!(pttl > 149_000 && pttl <= 150_000, "{}", pttl);
+    assert_eq!(
+        conn.command(&[b"EXPIRE", b"k", b"10", b"NX", b"GT"]).unwrap(),
+        Value::Error("ERR NX and XX, GT or LT options at the same time are not compatible".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"EXPIRE", b"k", b"10", b"GT", b"LT"]).unwrap(),
+        Value::Error("ERR GT and LT options at the same time are not compatible".to_string())
+    );
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"10", b"FOO"]).unwrap(), Value::Error("ERR Unsupported option FOO".to_string()));
+    assert_eq!(conn.command(&[b"EXPIRE", b"k", b"ten"]).unwrap(), Value::Error("ERR value is not an integer or out of range".to_string()));
+    assert_eq!(
+        conn.command(&[b"EXPIRE", b"k", b"9223372036854775807"]).unwrap(),
+        Value::Error("ERR invalid expire time in 'expire' command".to_string())
+    );
+
+    // Dates absolues, à la milliseconde près
+    let at = now_ms() + 60_000;
+    let at_arg = at.to_string();
+    assert_eq!(conn.command(&[b"PEXPIREAT", b"k", at_arg.as_bytes()]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"PEXPIRETIME", b"k"]).unwrap(), Value::Integer(at));
+    assert_eq!(conn.command(&[b"EXPIRETIME", b"k"]).unwrap(), Value::Integer(at / 1000));
+    let at_s = (at / 1000 + 30).to_string();
+    assert_eq!(conn.command(&[b"EXPIREAT", b"k", at_s.as_bytes()]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"EXPIRETIME", b"k"]).unwrap(), Value::Integer(at / 1000 + 30));
+
+    // PERSIST
+    assert_eq!(conn.command(&[b"PERSIST", b"k"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"PERSIST", b"k"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"PERSIST", b"missing"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"TTL", b"k"]).unwrap(), Value::Integer(-1));
+
+    // Une durée courte expire réellement ; une date passée supprime la clé tout de suite
+    conn.command(&[b"RPUSH", b"list", b"a"]).unwrap();
+    assert_eq!(conn.command(&[b"PEXPIRE", b"list", b"100"]).unwrap(), Value::Integer(1));
+    conn.command(&[b"SET", b"gone", b"v"]).unwrap();
+    assert_eq!(conn.command(&[b"EXPIRE", b"gone", b"-1"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"GET", b"gone"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"TTL", b"gone"]).unwrap(), Value::Integer(-2));
+    conn.command(&[b"SET", b"later", b"v"]).unwrap();
+    assert_eq!(conn.command(&[b"EXPIRE", b"later", b"1000"]).unwrap(), Value::Integer(1));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(conn.command(&[b"LLEN", b"list"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"PTTL", b"list"]).unwrap(), Value::Integer(-2));
+
+    // L'AOF ne contient que des dates absolues, et son rejeu redonne les mêmes expirations
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert!(lines.iter().all(|line| !line.starts_with("EXPIRE ") && !line.starts_with("PEXPIRE ")), "{:?}", lines);
+    let (tx, rx) = mpsc::channel();
+    for line in lines {
+        tx.send(line).unwrap();
+    }
+    let expected_later = now_ms() - 200 + 1_000_000;
+    let replayed = replay_aof(&rx);
+    let replayed = replayed.lock().unwrap();
+    assert!(replayed.get(b"k".as_slice()).unwrap().expire_at.is_none());
+    assert!(replayed.get(b"gone".as_slice()).is_none());
+    let later = replayed.get(b"later".as_slice()).unwrap().expire_at.unwrap();
+    let later = later.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as i64;
+    assert!((later - expected_later).abs() < 1000, "{} {}", later, expected_later);
+    assert_eq!(conn.command(&[b"PEXPIRETIME", b"later"]).unwrap(), Value::Integer(later));
+}
+
+#[test]
+fn test_lazy_expiry_logged() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    conn.command(&[b"SET", b"a", b"1", b"PX", b"50"]).unwrap();
+    conn.command(&[b"SELECT", b"3"]).unwrap();
+    conn.command(&[b"SET", b"b", b"1", b"PX", b"50"]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    // Les clés expirées sont supprimées à l'accès, même par une lecture, et la suppression est journalisée
+    assert_eq!(conn.command(&[b"GET", b"b"]).unwrap(), Value::Nil);
+    conn.command(&[b"SELECT", b"0"]).unwrap();
+    assert_eq!(conn.command(&[b"EXISTS", b"a"]).unwrap(), Value::Integer(0));
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[3..], ["DELETE b", "SELECT 0", "DELETE a"]);
+
+    // La suppression précède dans l'AOF la commande qui l'a provoquée
+    conn.command(&[b"SET", b"c", b"1", b"PX", b"50"]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    conn.command(&[b"RPUSH", b"c", b"x"]).unwrap();
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert_eq!(lines[1..], ["DELETE c", "RPUSH c x"]);
+}
+
+#[test]
+fn test_replay_keeps_expired_keys() {
+    // L'échéance, posée avant l'arrêt, est passée au moment du rejeu
+    let past = (SystemTime::now() - Duration::from_secs(60)).duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis();
+    let lines = vec![
+        "RPUSH k a".to_string(),
+        format!("PEXPIREAT k {}", past),
+        "RPUSH k x".to_string(),
+        "SADD s m".to_string(),
+        format!("PEXPIREAT s {}", past),
+    ];
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    persistence::replay(lines, &db);
+    {
+        let db = db.lock().unwrap();
+        let entry = db.get(b"k".as_slice()).unwrap();
+        assert_eq!(entry.value, DbValue::List(vec![b"a".to_vec(), b"x".to_vec()].into()));
+        assert!(entry.is_expired());
+        assert!(db.get(b"s".as_slice()).unwrap().is_expired());
+    }
+
+    // Une fois le serveur démarré, l'expiration active les supprime
+    let (aof_tx, _aof_rx) = mpsc::channel();
+    assert_eq!(expiry::expire_cycle(&db, &aof_tx), 2);
+    assert!(db.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_active_expiry() {
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    let (aof_tx, aof_rx) = mpsc::channel();
+    let past = SystemTime::now() - Duration::from_millis(1);
+    let future = SystemTime::now() + Duration::from_secs(3600);
+    {
+        let mut db = db.lock().unwrap();
+        for i in 0..1000 {
+            let entry = Entry::with_expiry(DbValue::String(b"v".to_vec()), Some(past));
+            db.insert(format!("old:{}", i).into_bytes(), entry);
+        }
+        for i in 0..10 {
+            let entry = Entry::with_expiry(DbValue::String(b"v".to_vec()), Some(future));
+            db.insert(format!("new:{}", i).into_bytes(), entry);
+            db.insert(format!("plain:{}", i).into_bytes(), Entry::new(DbValue::String(b"v".to_vec())));
+        }
+        // Échéances périmées : expiration prolongée, retirée, ou clé remplacée sans expiration
+        db.set_expire(b"old:0", Some(future));
+        db.set_expire(b"old:1", None);
+        db.insert(b"old:2".to_vec(), Entry::new(DbValue::String(b"w".to_vec())));
+        db.remove(b"old:3".as_slice());
+    }
+
+    // Les lots s'enchaînent jusqu'à épuisement des échéances dépassées
+    let mut deleted = 0;
+    loop {
+        let n = expiry::expire_cycle(&db, &aof_tx);
+        if n == 0 {
+            break;
+        }
+        deleted += n;
+    }
+    assert_eq!(deleted, 996);
+    {
+        let db = db.lock().unwrap();
+        assert_eq!(db.len(), 23);
+        assert!(db.contains_key(b"old:0".as_slice()));
+        assert_eq!(db.get(b"old:1".as_slice()).unwrap().expire_at, None);
+        assert_eq!(db.get(b"old:2".as_slice()).unwrap().value, DbValue::String(b"w".to_vec()));
+        assert!(!db.contains_key(b"old:4".as_slice()));
+    }
+
+    // Chaque suppression est journalisée
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert_eq!(lines.len(), 996);
+    assert!(lines.iter().all(|line| line.starts_with("DELETE old:")));
+
+    // Les autres bases aussi, avec un SELECT devant leurs suppressions
+    {
+        let mut db = db.lock().unwrap();
+        for i in 0..3 {
+            let entry = Entry::with_expiry(DbValue::String(b"v".to_vec()), Some(past));
+            db.keyspace_mut(7).insert(format!("other:{}", i).into_bytes(), entry);
+        }
+    }
+    while expiry::expire_cycle(&db, &aof_tx) > 0 {}
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "SELECT 7");
+    assert!(lines[1..].iter().all(|line| line.starts_with("DELETE other:")));
+    assert!(db.lock().unwrap().keyspace(7).is_empty());
+
+    // Un cycle respecte son budget de temps même avec beaucoup de clés expirées
+    {
+        let mut db = db.lock().unwrap();
+        for i in 0..200_000 {
+            let entry = Entry::with_expiry(DbValue::String(b"v".to_vec()), Some(past));
+            db.insert(format!("bulk:{}", i).into_bytes(), entry);
+        }
+    }
+    let start = std::time::Instant::now();
+    let first = expiry::expire_cycle(&db, &aof_tx);
+    assert!(first > 0 && first < 200_000, "{}", first);
+    assert!(start.elapsed() < Duration::from_millis(500), "{:?}", start.elapsed());
+
+    // Les clés expirées restent invisibles tant que le cycle ne les a pas supprimées
+    let mut keyspace = db.lock().unwrap();
+    assert!(redust::db::get_live(&mut keyspace, b"bulk:199999").is_none());
+}
+
+#[test]
+fn test_expiry_index_compaction() {
+    let mut index = ExpiryIndex::default();
+    let now = SystemTime::now();
+    for i in 1..=1024u64 {
+        index.push(now + Duration::from_secs(i), b"key");
+    }
+    assert!(!index.needs_compaction());
+    index.push(now, b"key");
+    assert!(index.needs_compaction());
+    index.retain(|expire_at, _| expire_at == now);
+    assert_eq!(index.len(), 1);
+    assert!(!index.needs_compaction());
+    assert_eq!(index.pop_due(now), None);
+    assert_eq!(index.pop_due(now + Duration::from_millis(1)), Some((now, b"key".to_vec())));
+    assert!(index.is_empty());
+
+    // Dans la base, des prolongations répétées ne font pas grossir l'index indéfiniment :
+    // toutes les échéances sauf la dernière sont périmées et finissent par être retirées.
+    let db: Db = Arc::new(Mutex::new(Databases::default()));
+    let mut keyspace = db.lock().unwrap();
+    keyspace.insert(b"k".to_vec(), Entry::new(DbValue::String(b"v".to_vec())));
+    for i in 0..100_000u64 {
+        keyspace.set_expire(b"k", Some(now - Duration::from_secs(1_000_000 - i)));
+    }
+    let (examined, expired) = keyspace.expire_due(SystemTime::now(), usize::MAX);
+    assert!(examined <= 2048, "{}", examined);
+    assert_eq!(expired, vec![b"k".to_vec()]);
+}
+
+#[test]
+fn test_glob_patterns() {
+    let cases: &[(&[u8], &[u8], bool)] = &[
+        (b"*", b"", true),
+        (b"*", b"anything", true),
+        (b"h?llo", b"hello", true),
+        (b"h?llo", b"hllo", false),
+        (b"h*llo", b"heeeello", true),
+        (b"h*llo", b"hello world", false),
+        (b"h[ae]llo", b"hallo", true),
+        (b"h[ae]llo", b"hillo", false),
+        (b"h[^e]llo", b"hallo", true),
+        (b"h[^e]llo", b"hello", false),
+        (b"h[a-b]llo", b"hbllo", true),
+        (b"h[b-a]llo", b"hallo", true),
+        (b"h[a-b]llo", b"hcllo", false),
+        (b"user:\\*", b"user:*", true),
+        (b"user:\\*", b"user:1", false),
+        (b"[\\]]", b"]", true),
+        (b"*:*:end", b"a:b:c:end", true),
+        (b"*a*b*c", b"xaxbxc", true),
+        (b"*a*b*c", b"xaxcxb", false),
+        (b"a[bc", b"ab", true),
+        (b"caf\xc3\xa9*", b"caf\xc3\xa9 au lait", true),
+    ];
+    for (pattern, string, expected) in cases {
+        assert_eq!(
+            glob::matches(pattern, string),
+            *expected,
+            "{} ~ {}",
+            String::from_utf8_lossy(pattern),
+            String::from_utf8_lossy(string)
+        );
+    }
+}
+
+#[test]
+fn test_keyspace_commands() {
+    let addr = start_test_server();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let sorted = |reply: Value| match reply {
+        Value::Array(items) => {
+            let mut keys: Vec<Vec<u8>> = items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Bulk(key) => key,
+                    other => panic!("clé attendue, reçu {:?}", other),
+                })
+                .collect();
+            keys.sort();
+            keys
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    };
+
+    assert_eq!(conn.command(&[b"DBSIZE"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"RANDOMKEY"]).unwrap(), Value::Nil);
+    conn.command(&[b"MSET", b"user:1", b"a", b"user:2", b"b", b"user:10", b"c"]).unwrap();
+    conn.command(&[b"RPUSH", b"queue", b"x"]).unwrap();
+    conn.command(&[b"HSET", b"user:hash", b"f", b"v"]).unwrap();
+    conn.command(&[b"SET", b"temp", b"v", b"PX", b"50"]).unwrap();
+
+    assert_eq!(conn.command(&[b"EXISTS", b"user:1", b"user:1", b"missing", b"queue"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"TYPE", b"queue"]).unwrap(), Value::Simple("list".to_string()));
+    assert_eq!(conn.command(&[b"TYPE", b"user:hash"]).unwrap(), Value::Simple("hash".to_string()));
+    assert_eq!(conn.command(&[b"TYPE", b"missing"]).unwrap(), Value::Simple("none".to_string()));
+    assert_eq!(conn.command(&[b"DBSIZE"]).unwrap(), Value::Integer(6));
+
+    assert_eq!(
+        sorted(conn.command(&[b"KEYS", b"user:?"]).unwrap()),
+        vec![b"user:1".to_vec(), b"user:2".to_vec()]
+    );
+    assert_eq!(sorted(conn.command(&[b"KEYS", b"user:[^2]*"]).unwrap()).len(), 3);
+    assert_eq!(sorted(conn.command(&[b"KEYS", b"*"]).unwrap()).len(), 6);
+
+    // Une clé expirée n'apparaît plus nulle part
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(conn.command(&[b"EXISTS", b"temp"]).unwrap(), Value::Integer(0));
+    assert_eq!(sorted(conn.command(&[b"KEYS", b"t*"]).unwrap()), Vec::<Vec<u8>>::new());
+
+    // SCAN jusqu'à ce que le curseur revienne à 0, avec filtres
+    let scan_all = |conn: &mut Connection, options: &[&[u8]]| {
+        let mut cursor = b"0".to_vec();
+        let mut keys = Vec::new();
+        loop {
+            let mut args: Vec<&[u8]> = vec![b"SCAN", &cursor];
+            args.extend_from_slice(options);
+            match conn.command(&args).unwrap() {
+                Value::Array(mut reply) => {
+                    keys.extend(sorted(reply.pop().unwrap()));
+                    cursor = match reply.pop().unwrap() {
+                        Value::Bulk(next) => next,
+                        other => panic!("curseur attendu, reçu {:?}", other),
+                    };
+                },
+                other => panic!("réponse de SCAN inattendue : {:?}", other),
+            }
+            if cursor == b"0" {
+                keys.sort();
+                return keys;
+            }
+        }
+    };
+    assert_eq!(scan_all(&mut conn, &[b"COUNT", b"1"]).len(), 5);
+    assert_eq!(
+        scan_all(&mut conn, &[b"MATCH", b"user:*", b"TYPE", b"string", b"COUNT", b"2"]),
+        vec![b"user:1".to_vec(), b"user:10".to_vec(), b"user:2".to_vec()]
+    );
+    assert_eq!(scan_all(&mut conn, &[b"TYPE", b"LIST"]), vec![b"queue".to_vec()]);
+    assert_eq!(conn.command(&[b"SCAN", b"abc"]).unwrap(), Value::Error("ERR invalid cursor".to_string()));
+    assert_eq!(conn.command(&[b"SCAN", b"0", b"COUNT", b"0"]).unwrap(), Value::Error("ERR syntax error".to_string()));
+    assert_eq!(conn.command(&[b"SCAN", b"0", b"MATCH"]).unwrap(), Value::Error("ERR syntax error".to_string()));
+
+    let random = conn.command(&[b"RANDOMKEY"]).unwrap();
+    assert!(
+        [bulk("user:1"), bulk("user:2"), bulk("user:10"), bulk("queue"), bulk("user:hash")].contains(&random),
+        "{:?}",
+        random
+    );
+}
+
+#[test]
+fn test_scan_during_resize() {
+    let mut db = Keyspace::default();
+    for i in 0..1000 {
+        db.insert(format!("key:{}", i).into_bytes(), Entry::new(DbValue::String(b"v".to_vec())));
+    }
+
+    // Première moitié du parcours, puis la table grossit fortement et perd des clés
+    let mut seen = Vec::new();
+    let mut cursor = 0;
+    for _ in 0..50 {
+        let (next, keys) = db.scan(cursor, 10);
+        seen.extend(keys.into_iter().map(<[u8]>::to_vec));
+        cursor = next;
+    }
+    assert_ne!(cursor, 0);
+    for i in 0..100_000 {
+        db.insert(format!("new:{}", i).into_bytes(), Entry::new(DbValue::String(b"v".to_vec())));
+    }
+    for i in 0..100 {
+        db.remove(format!("key:{}", i * 10).as_bytes());
+    }
+    loop {
+        let (next, keys) = db.scan(cursor, 100);
+        seen.extend(keys.into_iter().map(<[u8]>::to_vec));
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    // Toute clé présente du début à la fin est vue exactement une fois
+    let old: Vec<Vec<u8>> = seen.iter().filter(|key| key.starts_with(b"key:")).cloned().collect();
+    let unique: std::collections::HashSet<&Vec<u8>> = old.iter().collect();
+    assert_eq!(unique.len(), old.len());
+    for i in 0..1000 {
+        if i % 10 != 0 {
+            assert!(unique.contains(&format!("key:{}", i).into_bytes()), "key:{}", i);
+        }
+    }
+
+    // Tirage au sort : toutes les clés finissent par sortir
+    let mut small = Keyspace::default();
+    for key in [b"a", b"b", b"c"] {
+        small.insert(key.to_vec(), Entry::new(DbValue::String(b"v".to_vec())));
+    }
+    // Le tirage n'est pas uniforme (il dépend des écarts entre hachages) : on tire jusqu'à tout voir
+    let mut drawn = std::collections::HashSet::new();
+    for _ in 0..100_000 {
+        drawn.insert(small.random_key().unwrap().to_vec());
+        if drawn.len() == 3 {
+            break;
+        }
+    }
+    assert_eq!(drawn.len(), 3);
+}
+
+#[test]
+fn test_scan_concurrent_writes() {
+    let addr = start_test_server();
+    let mut conn = Connection::connect(addr).unwrap();
+    let mut pipeline = Pipeline::new();
+    let keys: Vec<String> = (0..2000).map(|i| format!("stable:{}", i)).collect();
+    for key in &keys {
+        pipeline.cmd(&[b"SET", key.as_bytes(), b"v"]);
+    }
+    conn.execute(&pipeline).unwrap();
+
+    // Un autre client écrit sans arrêt pendant le parcours
+    let writer = thread::spawn(move || {
+        let mut conn = Connection::connect(addr).unwrap();
+        for i in 0..3000 {
+            let key = format!("churn:{}", i);
+            conn.command(&[b"SET", key.as_bytes(), b"v"]).unwrap();
+            if i % 2 == 0 {
+                conn.command(&[b"DELETE", key.as_bytes()]).unwrap();
+            }
+        }
+    });
+    let mut seen = std::collections::HashSet::new();
+    let mut cursor = b"0".to_vec();
+    loop {
+        let reply = conn.command(&[b"SCAN", &cursor, b"MATCH", b"stable:*", b"COUNT", b"25"]).unwrap();
+        let Value::Array(mut reply) = reply else { panic!("réponse de SCAN inattendue") };
+        let Some(Value::Array(found)) = reply.pop() else { panic!("clés attendues") };
+        for key in found {
+            let Value::Bulk(key) = key else { panic!("clé attendue") };
+            assert!(seen.insert(key), "clé renvoyée deux fois");
+        }
+        let Some(Value::Bulk(next)) = reply.pop() else { panic!("curseur attendu") };
+        cursor = next;
+        if cursor == b"0" {
+            break;
+        }
+    }
+    writer.join().unwrap();
+    assert_eq!(seen.len(), keys.len());
+}
+
+#[test]
+fn test_rename_copy_object() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let ok = Value::Simple("OK".to_string());
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let error = |s: &str| Value::Error(s.to_string());
+
+    // RENAME garde l'expiration et écrase la destination
+    conn.command(&[b"SET", b"src", b"v1", b"EX", b"100"]).unwrap();
+    conn.command(&[b"RPUSH", b"dst", b"old"]).unwrap();
+    assert_eq!(conn.command(&[b"RENAME", b"src", b"dst"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"GET", b"dst"]).unwrap(), bulk("v1"));
+    assert_eq!(conn.command(&[b"EXISTS", b"src"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"TTL", b"dst"]).unwrap(), Value::Integer(100));
+    assert_eq!(conn.command(&[b"RENAME", b"src", b"dst"]).unwrap(), error("ERR no such key"));
+    assert_eq!(conn.command(&[b"RENAME", b"dst", b"dst"]).unwrap(), ok);
+
+    conn.command(&[b"SET", b"other", b"v2"]).unwrap();
+    assert_eq!(conn.command(&[b"RENAMENX", b"dst", b"other"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"RENAMENX", b"dst", b"dst"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"RENAMENX", b"dst", b"moved"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"GET", b"moved"]).unwrap(), bulk("v1"));
+    assert_eq!(conn.command(&[b"RENAMENX", b"missing", b"x"]).unwrap(), error("ERR no such key"));
+
+    // COPY, avec et sans REPLACE
+    conn.command(&[b"HSET", b"h", b"f", b"1"]).unwrap();
+    assert_eq!(conn.command(&[b"COPY", b"h", b"h2"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"COPY", b"h", b"other"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"COPY", b"moved", b"other", b"REPLACE", b"DB", b"0"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"GET", b"other"]).unwrap(), bulk("v1"));
+    assert_eq!(conn.command(&[b"TTL", b"other"]).unwrap(), Value::Integer(100));
+    assert_eq!(conn.command(&[b"COPY", b"missing", b"x"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"COPY", b"h", b"h"]).unwrap(), error("ERR source and destination objects are the same"));
+    assert_eq!(conn.command(&[b"COPY", b"h", b"x", b"DB", b"16"]).unwrap(), error("ERR DB index is out of range"));
+    assert_eq!(conn.command(&[b"COPY", b"h", b"x", b"FOO"]).unwrap(), error("ERR syntax error"));
+    // La copie est indépendante de l'original
+    conn.command(&[b"HSET", b"h2", b"f", b"2"]).unwrap();
+    assert_eq!(conn.command(&[b"HGET", b"h", b"f"]).unwrap(), bulk("1"));
+
+    assert_eq!(conn.command(&[b"MOVE", b"h", b"0"]).unwrap(), error("ERR source and destination objects are the same"));
+    assert_eq!(conn.command(&[b"MOVE", b"h", b"-1"]).unwrap(), error("ERR DB index is out of range"));
+    assert_eq!(conn.command(&[b"MOVE", b"h", b"x"]).unwrap(), error("ERR value is not an integer or out of range"));
+
+    // OBJECT ENCODING
+    let long = "x".repeat(45);
+    let big_list: Vec<String> = (0..200).map(|i| format!("{:0>64}", i)).collect();
+    conn.command(&[b"SET", b"int", b"12345"]).unwrap();
+    conn.command(&[b"SET", b"padded", b"012"]).unwrap();
+    conn.command(&[b"SET", b"long", long.as_bytes()]).unwrap();
+    conn.command(&[b"SADD", b"ints", b"1", b"2", b"3"]).unwrap();
+    conn.command(&[b"SADD", b"words", b"a", b"b"]).unwrap();
+    conn.command(&[b"ZADD", b"z", b"1", b"a"]).unwrap();
+    let mut push: Vec<&[u8]> = vec![b"RPUSH", b"biglist"];
+    push.extend(big_list.iter().map(|item| item.as_bytes()));
+    conn.command(&push).unwrap();
+    for (key, encoding) in [
+        ("int", "int"),
+        ("padded", "embstr"),
+        ("moved", "embstr"),
+        ("long", "raw"),
+        ("h", "listpack"),
+        ("ints", "intset"),
+        ("words", "listpack"),
+        ("z", "listpack"),
+        ("biglist", "quicklist"),
+    ] {
+        assert_eq!(conn.command(&[b"OBJECT", b"ENCODING", key.as_bytes()]).unwrap(), bulk(encoding), "{}", key);
+    }
+    assert_eq!(conn.command(&[b"OBJECT", b"ENCODING", b"missing"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"OBJECT", b"FOO", b"h"]).unwrap(),
+        error("ERR unknown subcommand 'FOO'. Try OBJECT HELP.")
+    );
+    assert_eq!(
+        conn.command(&[b"OBJECT", b"ENCODING"]).unwrap(),
+        error("ERR wrong number of arguments for 'object|encoding' command")
+    );
+
+    // OBJECT IDLETIME et FREQ : les lectures comptent, OBJECT, TYPE et EXISTS non
+    conn.command(&[b"SET", b"hot", b"v"]).unwrap();
+    conn.command(&[b"SET", b"cold", b"v"]).unwrap();
+    assert_eq!(conn.command(&[b"OBJECT", b"FREQ", b"cold"]).unwrap(), Value::Integer(5));
+    let mut pipeline = Pipeline::new();
+    for _ in 0..500 {
+        pipeline.cmd(&[b"GET", b"hot"]);
+    }
+    conn.execute(&pipeline).unwrap();
+    let freq = match conn.command(&[b"OBJECT", b"FREQ", b"hot"]).unwrap() {
+        Value::Integer(freq) => freq,
+        other => panic!("entier attendu, reçu {:?}", other),
+    };
+    assert!(freq > 6 && freq < 30, "{}", freq);
+    assert_eq!(conn.command(&[b"OBJECT", b"FREQ", b"hot"]).unwrap(), Value::Integer(freq));
+    thread::sleep(Duration::from_millis(1100));
+    conn.command(&[b"TYPE", b"cold"]).unwrap();
+    conn.command(&[b"EXISTS", b"cold"]).unwrap();
+    assert_eq!(conn.command(&[b"OBJECT", b"IDLETIME", b"cold"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"OBJECT", b"IDLETIME", b"cold"]).unwrap(), Value::Integer(1));
+    conn.command(&[b"GET", b"cold"]).unwrap();
+    assert_eq!(conn.command(&[b"OBJECT", b"IDLETIME", b"cold"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"OBJECT", b"REFCOUNT", b"cold"]).unwrap(), Value::Integer(1));
+
+    // Le rejeu de l'AOF redonne les mêmes clés, valeurs et expirations
+    let replayed = replay_aof(&aof_rx);
+    let replayed = replayed.lock().unwrap();
+    let mut keys: Vec<&Vec<u8>> = replayed.keys().collect();
+    keys.sort();
+    let expected: Vec<&[u8]> = vec![
+        b"biglist", b"cold", b"h", b"h2", b"hot", b"int", b"ints", b"long", b"moved", b"other", b"padded", b"words", b"z",
+    ];
+    assert_eq!(keys, expected);
+    assert_eq!(replayed[b"other".as_slice()].value, DbValue::String(b"v1".to_vec()));
+    assert!(replayed[b"other".as_slice()].expire_at.is_some());
+    assert_eq!(replayed[b"moved".as_slice()].expire_at, replayed[b"other".as_slice()].expire_at);
+    match &replayed[b"h2".as_slice()].value {
+        DbValue::Hash(hash) => assert_eq!(hash[b"f".as_slice()], b"2".to_vec()),
+        other => panic!("hash attendu, reçu {:?}", other),
+    }
+}
+
+#[test]
+fn test_databases() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut first = Connection::connect(addr).unwrap();
+    let mut second = Connection::connect(addr).unwrap();
+    let ok = Value::Simple("OK".to_string());
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let error = |s: &str| Value::Error(s.to_string());
+
+    // Chaque connexion a sa propre base sélectionnée
+    first.command(&[b"SET", b"k", b"zero"]).unwrap();
+    assert_eq!(second.command(&[b"SELECT", b"1"]).unwrap(), ok);
+    assert_eq!(second.command(&[b"GET", b"k"]).unwrap(), Value::Nil);
+    second.command(&[b"SET", b"k", b"one"]).unwrap();
+    assert_eq!(first.command(&[b"GET", b"k"]).unwrap(), bulk("zero"));
+    assert_eq!(second.command(&[b"DBSIZE"]).unwrap(), Value::Integer(1));
+    assert_eq!(second.command(&[b"SELECT", b"16"]).unwrap(), error("ERR DB index is out of range"));
+    assert_eq!(second.command(&[b"SELECT", b"x"]).unwrap(), error("ERR value is not an integer or out of range"));
+    assert_eq!(second.command(&[b"SELECT"]).unwrap(), error("ERR wrong number of arguments for 'select' command"));
+    assert_eq!(second.command(&[b"GET", b"k"]).unwrap(), bulk("one"));
+
+    // MOVE et COPY ... DB
+    first.command(&[b"SET", b"moving", b"v", b"EX", b"100"]).unwrap();
+    assert_eq!(first.command(&[b"MOVE", b"k", b"1"]).unwrap(), Value::Integer(0));
+    assert_eq!(first.command(&[b"MOVE", b"missing", b"1"]).unwrap(), Value::Integer(0));
+    assert_eq!(first.command(&[b"MOVE", b"moving", b"1"]).unwrap(), Value::Integer(1));
+    assert_eq!(first.command(&[b"EXISTS", b"moving"]).unwrap(), Value::Integer(0));
+    assert_eq!(second.command(&[b"TTL", b"moving"]).unwrap(), Value::Integer(100));
+    assert_eq!(second.command(&[b"COPY", b"k", b"k", b"DB", b"2"]).unwrap(), Value::Integer(1));
+    assert_eq!(second.command(&[b"COPY", b"k", b"k", b"DB", b"0"]).unwrap(), Value::Integer(0));
+    assert_eq!(second.command(&[b"COPY", b"k", b"k", b"DB", b"1"]).unwrap(), error("ERR source and destination objects are the same"));
+
+    // SWAPDB : les connexions voient aussitôt le contenu de l'autre base
+    assert_eq!(first.command(&[b"SWAPDB", b"0", b"1"]).unwrap(), ok);
+    assert_eq!(first.command(&[b"GET", b"k"]).unwrap(), bulk("one"));
+    assert_eq!(second.command(&[b"GET", b"k"]).unwrap(), bulk("zero"));
+    assert_eq!(first.command(&[b"SWAPDB", b"0", b"16"]).unwrap(), error("ERR DB index is out of range"));
+
+    // SELECT dans une transaction vaut pour la suite de la connexion
+    first.command(&[b"MULTI"]).unwrap();
+    first.command(&[b"SELECT", b"3"]).unwrap();
+    first.command(&[b"SET", b"t", b"3"]).unwrap();
+    assert_eq!(first.command(&[b"EXEC"]).unwrap(), Value::Array(vec![ok.clone(), ok.clone()]));
+    assert_eq!(first.command(&[b"GET", b"t"]).unwrap(), bulk("3"));
+    assert_eq!(second.command(&[b"EXISTS", b"t"]).unwrap(), Value::Integer(0));
+
+    // FLUSHDB ne vide que la base courante
+    first.command(&[b"SET", b"u", b"3"]).unwrap();
+    assert_eq!(first.command(&[b"FLUSHDB"]).unwrap(), ok);
+    assert_eq!(first.command(&[b"DBSIZE"]).unwrap(), Value::Integer(0));
+    assert_eq!(second.command(&[b"DBSIZE"]).unwrap(), Value::Integer(1));
+    assert_eq!(first.command(&[b"FLUSHDB", b"LATER"]).unwrap(), error("ERR syntax error"));
+    first.command(&[b"SET", b"after_flush", b"3"]).unwrap();
+
+    // Le rejeu de l'AOF remet chaque clé dans sa base
+    let replayed = replay_aof(&aof_rx);
+    let contents = |dbs: &Databases| -> Vec<Vec<(Vec<u8>, DbValue)>> {
+        (0..16)
+            .map(|index| {
+                let mut entries: Vec<_> =
+                    dbs.keyspace(index).iter().map(|(key, entry)| (key.clone(), entry.value.clone())).collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                entries
+            })
+            .collect()
+    };
+    let string = |key: &str, value: &str| (key.as_bytes().to_vec(), DbValue::String(value.as_bytes().to_vec()));
+    let replayed = replayed.lock().unwrap();
+    let databases = contents(&replayed);
+    assert_eq!(databases[0], vec![string("k", "one"), string("moving", "v")]);
+    assert_eq!(databases[1], vec![string("k", "zero")]);
+    assert_eq!(databases[2], vec![string("k", "one")]);
+    assert_eq!(databases[3], vec![string("after_flush", "3")]);
+    assert!(databases[4..].iter().all(Vec::is_empty));
+    assert!(replayed.keyspace(0)[b"moving".as_slice()].expire_at.is_some());
+
+    // Le snapshot garde toutes les bases ; l'ancien format est chargé dans la base 0
+    let json = serde_json::to_vec(&*replayed).unwrap();
+    let restored = Databases::from_snapshot(&json).unwrap();
+    assert_eq!(contents(&restored), databases);
+    let legacy = br#"{"old":{"value":"v","expire_at":null}}"#;
+    let restored = Databases::from_snapshot(legacy).unwrap();
+    assert_eq!(restored.keyspace(0)[b"old".as_slice()].value, DbValue::String(b"v".to_vec()));
+    assert!((1..16).all(|index| restored.keyspace(index).is_empty()));
+
+    // FLUSHALL vide toutes les bases
+    assert_eq!(second.command(&[b"FLUSHALL"]).unwrap(), ok);
+    assert_eq!(first.command(&[b"DBSIZE"]).unwrap(), Value::Integer(0));
+    assert_eq!(second.command(&[b"DBSIZE"]).unwrap(), Value::Integer(0));
+    let replayed: Db = Arc::new(Mutex::new(Databases::default()));
+    for line in ["SET a 1", "SELECT 5", "SET b 2", "FLUSHALL", "SET c 3"] {
+        persistence::apply_command(line, &replayed);
+    }
+    let replayed = replayed.lock().unwrap();
+    assert!(replayed.keyspace(0).is_empty());
+    assert_eq!(replayed.keyspace(5).len(), 1);
+    assert!(replayed.keyspace(5).contains_key(b"c".as_slice()));
+}
+
+#[test]
+fn test_watch() {
+    let addr = start_test_server();
+    let mut first = Connection::connect(addr).unwrap();
+    let mut second = Connection::connect(addr).unwrap();
+    let ok = Value::Simple("OK".to_string());
+    // Lance une transaction `SET key value` et renvoie la réponse de EXEC
+    let set_in_transaction = |conn: &mut Connection, key: &[u8]| {
+        conn.command(&[b"MULTI"]).unwrap();
+        conn.command(&[b"SET", key, b"from_transaction"]).unwrap();
+        conn.command(&[b"EXEC"]).unwrap()
+    };
+
+    // Sans modification, EXEC s'exécute ; une simple lecture ne compte pas
+    first.command(&[b"SET", b"w:k", b"v"]).unwrap();
+    assert_eq!(first.command(&[b"WATCH", b"w:k", b"w:absent"]).unwrap(), ok);
+    second.command(&[b"GET", b"w:k"]).unwrap();
+    second.command(&[b"EXISTS", b"w:absent"]).unwrap();
+    assert_eq!(set_in_transaction(&mut first, b"w:k"), Value::Array(vec![ok.clone()]));
+
+    // Modifiée, supprimée, modifiée sur place, renommée, écrite par MSET : EXEC ne fait rien
+    let writes: Vec<Vec<&[u8]>> = vec![
+        vec![b"SET", b"w:k", b"other"],
+        vec![b"DELETE", b"w:k"],
+        vec![b"RPUSH", b"w:list", b"x"],
+        vec![b"RENAME", b"w:list", b"w:k"],
+        vec![b"MSET", b"w:a", b"1", b"w:k", b"2"],
+        vec![b"APPEND", b"w:k", b"!"],
+        vec![b"EXPIRE", b"w:k", b"100"],
+        vec![b"FLUSHDB"],
+    ];
+    for write in writes {
+        let key = if write[0] == b"RPUSH" { write[1] } else { b"w:k".as_slice() };
+        first.command(&[b"WATCH", key]).unwrap();
+        assert_ne!(second.command(&write).unwrap(), Value::Error("ERR Commande inconnue".to_string()));
+        assert_eq!(set_in_transaction(&mut first, key), Value::Nil, "{:?}", write);
+        // EXEC relâche les clés : la transaction suivante passe
+        assert_eq!(set_in_transaction(&mut first, key), Value::Array(vec![ok.clone()]));
+    }
+
+    // Une clé qui expire entre WATCH et EXEC fait échouer la transaction
+    first.command(&[b"SET", b"w:volatile", b"v", b"PX", b"50"]).unwrap();
+    first.command(&[b"WATCH", b"w:volatile"]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(set_in_transaction(&mut first, b"w:volatile"), Value::Nil);
+
+    // UNWATCH et DISCARD relâchent les clés
+    first.command(&[b"WATCH", b"w:k"]).unwrap();
+    second.command(&[b"SET", b"w:k", b"other"]).unwrap();
+    assert_eq!(first.command(&[b"UNWATCH"]).unwrap(), ok);
+    assert_eq!(set_in_transaction(&mut first, b"w:k"), Value::Array(vec![ok.clone()]));
+    first.command(&[b"WATCH", b"w:k"]).unwrap();
+    second.command(&[b"SET", b"w:k", b"other"]).unwrap();
+    first.command(&[b"MULTI"]).unwrap();
+    assert_eq!(first.command(&[b"DISCARD"]).unwrap(), ok);
+    assert_eq!(set_in_transaction(&mut first, b"w:k"), Value::Array(vec![ok.clone()]));
+
+    // Les clés sont surveillées dans la base où WATCH a été appelé
+    first.command(&[b"WATCH", b"w:k"]).unwrap();
+    second.command(&[b"SELECT", b"1"]).unwrap();
+    second.command(&[b"SET", b"w:k", b"other"]).unwrap();
+    assert_eq!(set_in_transaction(&mut first, b"w:k"), Value::Array(vec![ok.clone()]));
+    first.command(&[b"WATCH", b"w:k"]).unwrap();
+    second.command(&[b"SWAPDB", b"0", b"1"]).unwrap();
+    assert_eq!(set_in_transaction(&mut first, b"w:k"), Value::Nil);
+
+    first.command(&[b"MULTI"]).unwrap();
+    assert_eq!(first.command(&[b"WATCH", b"w:k"]).unwrap(), Value::Error("ERR WATCH inside MULTI is not allowed".to_string()));
+    first.command(&[b"DISCARD"]).unwrap();
+    assert_eq!(
+        first.command(&[b"WATCH"]).unwrap(),
+        Value::Error("ERR wrong number of arguments for 'watch' command".to_string())
+    );
+}
+
+#[test]
+fn test_watch_concurrent_increments() {
+    let addr = start_test_server();
+    let clients = 8;
+    let increments = 25;
+
+    // Chaque client incrémente le compteur par lecture puis écriture, en réessayant tant que EXEC échoue
+    let handles: Vec<_> = (0..clients)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut conn = Connection::connect(addr).unwrap();
+                let mut aborted = 0;
+                for _ in 0..increments {
+                    loop {
+                        conn.command(&[b"WATCH", b"cas:counter"]).unwrap();
+                        let current = match conn.command(&[b"GET", b"cas:counter"]).unwrap() {
+                            Value::Bulk(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
+                            _ => 0,
+                        };
+                        let next = (current + 1).to_string();
+                        conn.command(&[b"MULTI"]).unwrap();
+                        conn.command(&[b"SET", b"cas:counter", next.as_bytes()]).unwrap();
+                        match conn.command(&[b"EXEC"]).unwrap() {
+                            Value::Array(_) => break,
+                            Value::Nil => aborted += 1,
+                            other => panic!("réponse inattendue de EXEC : {:?}", other),
+                        }
+                    }
+                }
+                aborted
+            })
+        })
+        .collect();
+    let aborted: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+
+    // Aucune mise à jour perdue : chaque incrément réussi compte exactement une fois
+    let mut conn = Connection::connect(addr).unwrap();
+    let expected = (clients * increments).to_string();
+    assert_eq!(conn.command(&[b"GET", b"cas:counter"]).unwrap(), Value::Bulk(expected.into_bytes()));
+    assert!(aborted > 0, "les clients concurrents auraient dû se gêner au moins une fois");
+}
+
+#[test]
+fn test_lists() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulks = |items: &[&str]| Value::Array(items.iter().map(|i| Value::Bulk(i.as_bytes().to_vec())).collect());
+
+    assert_eq!(conn.command(&[b"RPUSH", b"queue", b"a", b"b", b"c"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"LPUSH", b"queue", b"z", b"y"]).unwrap(), Value::Integer(5));
+    assert_eq!(conn.command(&[b"LRANGE", b"queue", b"0", b"-1"]).unwrap(), bulks(&["y", "z", "a", "b", "c"]));
+    assert_eq!(conn.command(&[b"LRANGE", b"queue", b"-2", b"100"]).unwrap(), bulks(&["b", "c"]));
+    assert_eq!(conn.command(&[b"LRANGE", b"queue", b"3", b"1"]).unwrap(), bulks(&[]));
+    assert_eq!(conn.command(&[b"LLEN", b"queue"]).unwrap(), Value::Integer(5));
+    assert_eq!(conn.command(&[b"LINDEX", b"queue", b"-1"]).unwrap(), Value::Bulk(b"c".to_vec()));
+    assert_eq!(conn.command(&[b"LINDEX", b"queue", b"5"]).unwrap(), Value::Nil);
+
+    assert_eq!(conn.command(&[b"LPOP", b"queue"]).unwrap(), Value::Bulk(b"y".to_vec()));
+    assert_eq!(conn.command(&[b"RPOP", b"queue", b"2"]).unwrap(), bulks(&["c", "b"]));
+    assert_eq!(conn.command(&[b"LSET", b"queue", b"0", b"first"]).unwrap(), Value::Simple("OK".to_string()));
+    assert_eq!(conn.command(&[b"LSET", b"queue", b"9", b"x"]).unwrap(), Value::Error("ERR index out of range".to_string()));
+    assert_eq!(conn.command(&[b"LSET", b"nolist", b"0", b"x"]).unwrap(), Value::Error("ERR no such key".to_string()));
+
+    conn.command(&[b"RPUSH", b"dups", b"x", b"a", b"x", b"b", b"x"]).unwrap();
+    assert_eq!(conn.command(&[b"LREM", b"dups", b"-2", b"x"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"LRANGE", b"dups", b"0", b"-1"]).unwrap(), bulks(&["x", "a", "b"]));
+    assert_eq!(conn.command(&[b"LTRIM", b"dups", b"1", b"-1"]).unwrap(), Value::Simple("OK".to_string()));
+    assert_eq!(conn.command(&[b"LRANGE", b"dups", b"0", b"-1"]).unwrap(), bulks(&["a", "b"]));
+
+    // Une liste vidée disparaît
+    assert_eq!(conn.command(&[b"LPOP", b"dups", b"10"]).unwrap(), bulks(&["a", "b"]));
+    assert_eq!(conn.command(&[b"LLEN", b"dups"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"LPOP", b"dups", b"1"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"SET", b"dups", b"now a string", b"NX"]).unwrap(), Value::Simple("OK".to_string()));
+
+    // Opérations sur le mauvais type
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    assert_eq!(conn.command(&[b"LPUSH", b"dups", b"x"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"GET", b"queue"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"INCR", b"queue"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"LPOP", b"queue", b"-1"]).unwrap(), Value::Error("ERR value is out of range, must be positive".to_string()));
+
+    // AOF puis snapshot : la liste revient à l'identique
+    let replayed = replay_aof(&aof_rx);
+    let queue = replayed.lock().unwrap().get(b"queue".as_slice()).unwrap().value.clone();
+    assert_eq!(queue, DbValue::List(vec![b"first".to_vec(), b"a".to_vec()].into()));
+    assert!(replayed.lock().unwrap().get(b"dups".as_slice()).is_some());
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(restored.get(b"queue".as_slice()).unwrap().value, queue);
+}
+
+#[test]
+fn test_hashes() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let sorted = |value: Value| match value {
+        Value::Array(mut items) => {
+            items.sort_by_key(|item| match item {
+                Value::Bulk(bytes) => bytes.clone(),
+                _ => Vec::new(),
+            });
+            items
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    };
+
+    assert_eq!(conn.command(&[b"HSET", b"user", b"name", b"alice", b"age", b"30"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"HSET", b"user", b"name", b"bob", b"city", b"Paris"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"HGET", b"user", b"name"]).unwrap(), bulk("bob"));
+    assert_eq!(conn.command(&[b"HGET", b"user", b"missing"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"HMGET", b"user", b"age", b"nope"]).unwrap(), Value::Array(vec![bulk("30"), Value::Nil]));
+    assert_eq!(conn.command(&[b"HLEN", b"user"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"HEXISTS", b"user", b"city"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"HEXISTS", b"user", b"zip"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"HSTRLEN", b"user", b"city"]).unwrap(), Value::Integer(5));
+    assert_eq!(sorted(conn.command(&[b"HKEYS", b"user"]).unwrap()), vec![bulk("age"), bulk("city"), bulk("name")]);
+    assert_eq!(sorted(conn.command(&[b"HVALS", b"user"]).unwrap()), vec![bulk("30"), bulk("Paris"), bulk("bob")]);
+    assert_eq!(conn.command(&[b"HSETNX", b"user", b"name", b"carol"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"HSETNX", b"user", b"zip", b"75001"]).unwrap(), Value::Integer(1));
+
+    assert_eq!(conn.command(&[b"HINCRBY", b"user", b"age", b"-5"]).unwrap(), Value::Integer(25));
+    assert_eq!(conn.command(&[b"HINCRBY", b"user", b"visits", b"3"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"HINCRBY", b"user", b"name", b"1"]).unwrap(), Value::Error("ERR hash value is not an integer".to_string()));
+    assert_eq!(conn.command(&[b"HINCRBYFLOAT", b"user", b"visits", b"0.5"]).unwrap(), bulk("3.5"));
+    assert_eq!(conn.command(&[b"HDEL", b"user", b"city", b"zip", b"nope"]).unwrap(), Value::Integer(2));
+
+    // HGETALL : tableau plat en RESP2
+    conn.command(&[b"HSET", b"single", b"f", b"v"]).unwrap();
+    assert_eq!(conn.command(&[b"HGETALL", b"single"]).unwrap(), Value::Array(vec![bulk("f"), bulk("v")]));
+    assert_eq!(conn.command(&[b"HGETALL", b"nohash"]).unwrap(), Value::Array(vec![]));
+
+    // Un hash vidé disparaît
+    assert_eq!(conn.command(&[b"HDEL", b"single", b"f"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"HLEN", b"single"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"SET", b"single", b"str", b"NX"]).unwrap(), Value::Simple("OK".to_string()));
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    assert_eq!(conn.command(&[b"HSET", b"single", b"f", b"v"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"GET", b"user"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"HSET", b"user", b"odd"]).unwrap(), Value::Error("ERR wrong number of arguments for 'hset' command".to_string()));
+
+    // AOF puis snapshot : le hash revient à l'identique
+    let replayed = replay_aof(&aof_rx);
+    let user = replayed.lock().unwrap().get(b"user".as_slice()).unwrap().value.clone();
+    let expected: HashMap<Vec<u8>, Vec<u8>> = [("name", "bob"), ("age", "25"), ("visits", "3.5")]
+        .iter()
+        .map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec()))
+        .collect();
+    assert_eq!(user, DbValue::Hash(expected));
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(restored.get(b"user".as_slice()).unwrap().value, user);
+}
+
+#[test]
+fn test_sets() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let sorted = |value: Value| match value {
+        Value::Array(items) => {
+            let mut members: Vec<String> = items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Bulk(bytes) => String::from_utf8(bytes).unwrap(),
+                    other => panic!("bulk attendu, reçu {:?}", other),
+                })
+                .collect();
+            members.sort();
+            members
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    };
+
+    assert_eq!(conn.command(&[b"SADD", b"tags", b"rust", b"redis", b"rust"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"SADD", b"tags", b"db", b"redis"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"SCARD", b"tags"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"SISMEMBER", b"tags", b"db"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"SISMEMBER", b"tags", b"go"]).unwrap(), Value::Integer(0));
+    assert_eq!(
+        conn.command(&[b"SMISMEMBER", b"tags", b"go", b"rust"]).unwrap(),
+        Value::Array(vec![Value::Integer(0), Value::Integer(1)])
+    );
+    assert_eq!(sorted(conn.command(&[b"SMEMBERS", b"tags"]).unwrap()), ["db", "redis", "rust"]);
+    assert_eq!(conn.command(&[b"SREM", b"tags", b"db", b"go"]).unwrap(), Value::Integer(1));
+
+    conn.command(&[b"SADD", b"other", b"rust", b"go", b"c"]).unwrap();
+    assert_eq!(sorted(conn.command(&[b"SINTER", b"tags", b"other"]).unwrap()), ["rust"]);
+    assert_eq!(sorted(conn.command(&[b"SUNION", b"tags", b"other"]).unwrap()), ["c", "go", "redis", "rust"]);
+    assert_eq!(sorted(conn.command(&[b"SDIFF", b"other", b"tags"]).unwrap()), ["c", "go"]);
+    assert_eq!(sorted(conn.command(&[b"SINTER", b"tags", b"missing"]).unwrap()), Vec::<String>::new());
+
+    // Les variantes STORE remplacent la destination, quel que soit son type
+    conn.command(&[b"SET", b"dest", b"string"]).unwrap();
+    assert_eq!(conn.command(&[b"SUNIONSTORE", b"dest", b"tags", b"other"]).unwrap(), Value::Integer(4));
+    assert_eq!(conn.command(&[b"SDIFFSTORE", b"diff", b"other", b"tags"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"SINTERSTORE", b"inter", b"tags", b"other"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"SINTERSTORE", b"diff", b"tags", b"missing"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"SCARD", b"diff"]).unwrap(), Value::Integer(0));
+
+    // Un ensemble vidé disparaît
+    assert_eq!(conn.command(&[b"SREM", b"inter", b"rust"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"SET", b"inter", b"x", b"NX"]).unwrap(), Value::Simple("OK".to_string()));
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    assert_eq!(conn.command(&[b"SADD", b"inter", b"x"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"SUNION", b"tags", b"inter"]).unwrap(), wrongtype);
+
+    // AOF puis snapshot
+    let replayed = replay_aof(&aof_rx);
+    let dest = replayed.lock().unwrap().get(b"dest".as_slice()).unwrap().value.clone();
+    let expected = ["rust", "redis", "go", "c"].iter().map(|m| m.as_bytes().to_vec()).collect();
+    assert_eq!(dest, DbValue::Set(expected));
+    assert!(replayed.lock().unwrap().get(b"diff".as_slice()).is_none());
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(restored.get(b"dest".as_slice()).unwrap().value, dest);
+    assert_eq!(restored.get(b"tags".as_slice()).unwrap().value, replayed.lock().unwrap().get(b"tags".as_slice()).unwrap().value);
+}
+
+#[test]
+fn test_sorted_sets() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulks = |items: &[&str]| Value::Array(items.iter().map(|i| Value::Bulk(i.as_bytes().to_vec())).collect());
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"10", b"alice", b"20", b"bob", b"15", b"carol"]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"CH", b"25", b"alice", b"5", b"dave"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"NX", b"1", b"alice"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"GT", b"CH", b"1", b"bob"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"INCR", b"5", b"dave"]).unwrap(), bulk("10"));
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"XX", b"INCR", b"1", b"nobody"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"ZADD", b"board", b"NX", b"XX", b"1", b"x"]).unwrap(),
+        Value::Error("ERR XX and NX options at the same time are not compatible".to_string())
+    );
+    assert_eq!(conn.command(&[b"ZADD", b"board", b"abc", b"x"]).unwrap(), Value::Error("ERR value is not a valid float".to_string()));
+
+    // dave 10, carol 15, bob 20, alice 25
+    assert_eq!(conn.command(&[b"ZCARD", b"board"]).unwrap(), Value::Integer(4));
+    assert_eq!(conn.command(&[b"ZRANGE", b"board", b"0", b"-1"]).unwrap(), bulks(&["dave", "carol", "bob", "alice"]));
+    assert_eq!(conn.command(&[b"ZRANGE", b"board", b"0", b"1", b"REV"]).unwrap(), bulks(&["alice", "bob"]));
+    assert_eq!(conn.command(&[b"ZREVRANGE", b"board", b"1", b"2"]).unwrap(), bulks(&["bob", "carol"]));
+    assert_eq!(
+        conn.command(&[b"ZRANGE", b"board", b"0", b"1", b"WITHSCORES"]).unwrap(),
+        bulks(&["dave", "10", "carol", "15"])
+    );
+    assert_eq!(conn.command(&[b"ZRANGEBYSCORE", b"board", b"(10", b"20"]).unwrap(), bulks(&["carol", "bob"]));
+    assert_eq!(conn.command(&[b"ZRANGEBYSCORE", b"board", b"-inf", b"+inf", b"LIMIT", b"1", b"2"]).unwrap(), bulks(&["carol", "bob"]));
+    assert_eq!(conn.command(&[b"ZRANGE", b"board", b"+inf", b"15", b"BYSCORE", b"REV"]).unwrap(), bulks(&["alice", "bob", "carol"]));
+    assert_eq!(conn.command(&[b"ZREVRANGEBYSCORE", b"board", b"20", b"-inf", b"LIMIT", b"0", b"1"]).unwrap(), bulks(&["bob"]));
+    assert_eq!(
+        conn.command(&[b"ZRANGE", b"board", b"0", b"1", b"LIMIT", b"0", b"1"]).unwrap(),
+        Value::Error("ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX".to_string())
+    );
+    assert_eq!(conn.command(&[b"ZRANGEBYSCORE", b"board", b"low", b"20"]).unwrap(), Value::Error("ERR min or max is not a float".to_string()));
+    assert_eq!(conn.command(&[b"ZCOUNT", b"board", b"15", b"(25"]).unwrap(), Value::Integer(2));
+
+    assert_eq!(conn.command(&[b"ZRANK", b"board", b"bob"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"ZREVRANK", b"board", b"bob"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"ZRANK", b"board", b"bob", b"WITHSCORE"]).unwrap(), Value::Array(vec![Value::Integer(2), bulk("20")]));
+    assert_eq!(conn.command(&[b"ZRANK", b"board", b"nobody"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"ZSCORE", b"board", b"carol"]).unwrap(), bulk("15"));
+    assert_eq!(conn.command(&[b"ZINCRBY", b"board", b"0.5", b"carol"]).unwrap(), bulk("15.5"));
+    assert_eq!(conn.command(&[b"ZINCRBY", b"board", b"30", b"erin"]).unwrap(), bulk("30"));
+    assert_eq!(conn.command(&[b"ZREM", b"board", b"dave", b"nobody"]).unwrap(), Value::Integer(1));
+
+    // Un ensemble trié vidé disparaît, et les scores infinis sont acceptés
+    conn.command(&[b"ZADD", b"tmp", b"-inf", b"low", b"+inf", b"high"]).unwrap();
+    assert_eq!(conn.command(&[b"ZRANGE", b"tmp", b"0", b"-1", b"WITHSCORES"]).unwrap(), bulks(&["low", "-inf", "high", "inf"]));
+    assert_eq!(conn.command(&[b"ZINCRBY", b"tmp", b"-inf", b"high"]).unwrap(), Value::Error("ERR resulting score is not a number (NaN)".to_string()));
+    assert_eq!(conn.command(&[b"ZREM", b"tmp", b"low", b"high"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"ZCARD", b"tmp"]).unwrap(), Value::Integer(0));
+    conn.command(&[b"ZADD", b"inf", b"+inf", b"top"]).unwrap();
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    conn.command(&[b"SET", b"str", b"x"]).unwrap();
+    assert_eq!(conn.command(&[b"ZADD", b"str", b"1", b"a"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"LPUSH", b"board", b"a"]).unwrap(), wrongtype);
+
+    // WITHSCORES en RESP3 : un tableau de paires [membre, double]
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"HELLO 3\r\n").unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    while line != "(empty array)\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    stream.write_all(b"*5\r\n$6\r\nZRANGE\r\n$5\r\nboard\r\n$1\r\n0\r\n$1\r\n0\r\n$10\r\nWITHSCORES\r\n").unwrap();
+    let expected = b"*1\r\n*2\r\n$5\r\ncarol\r\n,15.5\r\n";
+    let mut reply = vec![0u8; expected.len()];
+    std::io::Read::read_exact(&mut reader, &mut reply).unwrap();
+    assert_eq!(reply, expected);
+
+    // AOF puis snapshot : les scores et l'ordre reviennent à l'identique
+    let replayed = replay_aof(&aof_rx);
+    let board = replayed.lock().unwrap().get(b"board".as_slice()).unwrap().value.clone();
+    let members = match &board {
+        DbValue::SortedSet(zset) => zset.iter().map(|(m, s)| (String::from_utf8(m.to_vec()).unwrap(), s)).collect::<Vec<_>>(),
+        other => panic!("ensemble trié attendu, reçu {:?}", other),
+    };
+    assert_eq!(members, [("carol".to_string(), 15.5), ("bob".to_string(), 20.0), ("alice".to_string(), 25.0), ("erin".to_string(), 30.0)]);
+    assert!(replayed.lock().unwrap().get(b"tmp".as_slice()).is_none());
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(restored.get(b"board".as_slice()).unwrap().value, board);
+    assert_eq!(restored.get(b"inf".as_slice()).unwrap().value, replayed.lock().unwrap().get(b"inf".as_slice()).unwrap().value);
+}
+
+#[test]
+fn test_sorted_set_ranks() {
+    // Rangs et accès par rang de la skiplist comparés à un simple vecteur trié
+    let mut zset = SortedSet::new();
+    let mut expected: Vec<(f64, Vec<u8>)> = Vec::new();
+    let mut seed = 42u64;
+    let mut next = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        seed >> 33
+    };
+    for i in 0..2000 {
+        let member = format!("m{}", i).into_bytes();
+        let score = (next() % 100) as f64;
+        zset.insert(member.clone(), score);
+        expected.push((score, member));
+    }
+    // Mise à jour de scores et suppressions
+    for i in (0..2000).step_by(3) {
+        let member = format!("m{}", i).into_bytes();
+        let score = (next() % 100) as f64 + 0.5;
+        zset.insert(member.clone(), score);
+        expected.retain(|(_, m)| *m != member);
+        expected.push((score, member));
+    }
+    for i in (0..2000).step_by(7) {
+        let member = format!("m{}", i).into_bytes();
+        assert!(zset.remove(&member));
+        expected.retain(|(_, m)| *m != member);
+    }
+    expected.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+    assert_eq!(zset.len(), expected.len());
+    let iterated: Vec<(f64, Vec<u8>)> = zset.iter().map(|(m, s)| (s, m.to_vec())).collect();
+    assert_eq!(iterated, expected);
+    for (rank, (_, member)) in expected.iter().enumerate() {
+        assert_eq!(zset.rank(member), Some(rank));
+    }
+    for rank in [0, 1, 500, expected.len() - 1] {
+        assert_eq!(zset.iter_from_rank(rank, false).next().unwrap().0, expected[rank].1.as_slice());
+        let backwards: Vec<&[u8]> = zset.iter_from_rank(rank, true).map(|(m, _)| m).collect();
+        assert_eq!(backwards.len(), rank + 1);
+    }
+    assert!(zset.iter_from_rank(expected.len(), false).next().is_none());
+
+    let range = ScoreRange { min: 10.0, min_exclusive: true, max: 20.0, max_exclusive: false };
+    let in_range: Vec<&[u8]> = expected.iter().filter(|(s, _)| range.contains(*s)).map(|(_, m)| m.as_slice()).collect();
+    assert_eq!(zset.range_by_score(range, false).map(|(m, _)| m).collect::<Vec<_>>(), in_range);
+    assert_eq!(zset.count(range), in_range.len());
+    let reversed: Vec<&[u8]> = in_range.iter().rev().copied().collect();
+    assert_eq!(zset.range_by_score(range, true).map(|(m, _)| m).collect::<Vec<_>>(), reversed);
+}
+
+#[test]
+fn test_streams() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let entry = |id: &str, fields: &[&str]| Value::Array(vec![bulk(id), Value::Array(fields.iter().map(|f| bulk(f)).collect())]);
+    let ids = |value: Value| match value {
+        Value::Array(entries) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                Value::Array(mut parts) => match parts.remove(0) {
+                    Value::Bulk(id) => String::from_utf8(id).unwrap(),
+                    other => panic!("identifiant attendu, reçu {:?}", other),
+                },
+                other => panic!("entrée attendue, reçu {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("tableau attendu, reçu {:?}", other),
+    };
+
+    assert_eq!(conn.command(&[b"XADD", b"events", b"1-1", b"type", b"login"]).unwrap(), bulk("1-1"));
+    assert_eq!(conn.command(&[b"XADD", b"events", b"1-*", b"type", b"click"]).unwrap(), bulk("1-2"));
+    assert_eq!(conn.command(&[b"XADD", b"events", b"5", b"type", b"logout"]).unwrap(), bulk("5-0"));
+    assert_eq!(
+        conn.command(&[b"XADD", b"events", b"3-0", b"type", b"late"]).unwrap(),
+        Value::Error("ERR The ID specified in XADD is equal or smaller than the target stream top item".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"XADD", b"fresh", b"0-0", b"f", b"v"]).unwrap(),
+        Value::Error("ERR The ID specified in XADD must be greater than 0-0".to_string())
+    );
+    assert_eq!(conn.command(&[b"XADD", b"fresh", b"NOMKSTREAM", b"*", b"f", b"v"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"XLEN", b"fresh"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"XADD", b"events", b"*", b"odd"]).unwrap(), Value::Error("ERR wrong number of arguments for 'xadd' command".to_string()));
+
+    // Identifiants automatiques : croissants et basés sur l'horloge
+    let auto = match conn.command(&[b"XADD", b"events", b"*", b"type", b"auto"]).unwrap() {
+        Value::Bulk(id) => String::from_utf8(id).unwrap().parse::<StreamId>().unwrap(),
+        other => panic!("identifiant attendu, reçu {:?}", other),
+    };
+    assert!(auto > StreamId::new(5, 0));
+    assert_eq!(conn.command(&[b"XLEN", b"events"]).unwrap(), Value::Integer(4));
+
+    assert_eq!(
+        conn.command(&[b"XRANGE", b"events", b"-", b"+", b"COUNT", b"2"]).unwrap(),
+        Value::Array(vec![entry("1-1", &["type", "login"]), entry("1-2", &["type", "click"])])
+    );
+    assert_eq!(ids(conn.command(&[b"XRANGE", b"events", b"1", b"5"]).unwrap()), ["1-1", "1-2", "5-0"]);
+    assert_eq!(ids(conn.command(&[b"XRANGE", b"events", b"(1-1", b"(5-0"]).unwrap()), ["1-2"]);
+    assert_eq!(ids(conn.command(&[b"XREVRANGE", b"events", b"5", b"-"]).unwrap()), ["5-0", "1-2", "1-1"]);
+    assert_eq!(conn.command(&[b"XRANGE", b"events", b"abc", b"+"]).unwrap(), Value::Error("ERR Invalid stream ID specified as stream command argument".to_string()));
+
+    // XREAD renvoie les entrées strictement postérieures à l'identifiant donné
+    assert_eq!(
+        conn.command(&[b"XREAD", b"COUNT", b"1", b"STREAMS", b"events", b"1-1"]).unwrap(),
+        Value::Array(vec![Value::Array(vec![bulk("events"), Value::Array(vec![entry("1-2", &["type", "click"])])])])
+    );
+    assert_eq!(conn.command(&[b"XREAD", b"STREAMS", b"events", b"$"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"XREAD", b"STREAMS", b"events", b"fresh", b"0"]).unwrap(), Value::Error(
+        "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.".to_string()
+    ));
+
+    // XREAD bloquant : réveillé par un XADD d'une autre connexion, ou nil après le délai
+    let writer = thread::spawn(move || {
+        let mut other = Connection::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        other.command(&[b"XADD", b"events", b"*", b"type", b"pushed"]).unwrap();
+    });
+    let started = std::time::Instant::now();
+    let read = conn.command(&[b"XREAD", b"BLOCK", b"5000", b"STREAMS", b"events", b"$"]).unwrap();
+    writer.join().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    match read {
+        Value::Array(streams) => assert_eq!(streams.len(), 1),
+        other => panic!("réponse XREAD attendue, reçu {:?}", other),
+    }
+    assert_eq!(conn.command(&[b"XREAD", b"BLOCK", b"50", b"STREAMS", b"events", b"$"]).unwrap(), Value::Nil);
+
+    // Réduction de la longueur
+    assert!(matches!(conn.command(&[b"XADD", b"events", b"MAXLEN", b"~", b"4", b"*", b"type", b"capped"]).unwrap(), Value::Bulk(_)));
+    assert_eq!(conn.command(&[b"XLEN", b"events"]).unwrap(), Value::Integer(4));
+    assert_eq!(conn.command(&[b"XTRIM", b"events", b"MAXLEN", b"3"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"XDEL", b"events", auto.to_string().as_bytes(), b"9-9"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"XTRIM", b"events", b"MINID", b"0"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"XLEN", b"events"]).unwrap(), Value::Integer(2));
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    conn.command(&[b"SET", b"str", b"x"]).unwrap();
+    assert_eq!(conn.command(&[b"XADD", b"str", b"*", b"f", b"v"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"GET", b"events"]).unwrap(), wrongtype);
+
+    // AOF puis snapshot : mêmes entrées, et le dernier identifiant reste la référence après redémarrage
+    let live = ids(conn.command(&[b"XRANGE", b"events", b"-", b"+"]).unwrap());
+    let replayed = replay_aof(&aof_rx);
+    let stream = match replayed.lock().unwrap().get(b"events".as_slice()).unwrap().value.clone() {
+        DbValue::Stream(stream) => stream,
+        other => panic!("stream attendu, reçu {:?}", other),
+    };
+    assert_eq!(stream.entries.keys().map(|id| id.to_string()).collect::<Vec<_>>(), live);
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(restored.get(b"events".as_slice()).unwrap().value, DbValue::Stream(stream.clone()));
+
+    let restored: Db = Arc::new(Mutex::new(Databases::from(Keyspace::from(restored))));
+    persistence::apply_command(&format!("XADD events {} type replayed", stream.last_id), &restored);
+    persistence::apply_command("XADD events * type next", &restored);
+    let guard = restored.lock().unwrap();
+    match &guard.get(b"events".as_slice()).unwrap().value {
+        DbValue::Stream(after) => {
+            assert_eq!(after.len(), stream.len() + 1);
+            assert!(after.last_id > stream.last_id);
+        },
+        other => panic!("stream attendu, reçu {:?}", other),
+    }
+}
+
+#[test]
+fn test_consumer_groups() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let entry = |id: &str, fields: &[&str]| Value::Array(vec![bulk(id), Value::Array(fields.iter().map(|f| bulk(f)).collect())]);
+    let stream_reply = |key: &str, entries: Vec<Value>| Value::Array(vec![Value::Array(vec![bulk(key), Value::Array(entries)])]);
+
+    assert_eq!(
+        conn.command(&[b"XGROUP", b"CREATE", b"jobs", b"workers", b"$"]).unwrap(),
+        Value::Error("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".to_string())
+    );
+    assert_eq!(conn.command(&[b"XGROUP", b"CREATE", b"jobs", b"workers", b"$", b"MKSTREAM"]).unwrap(), Value::Simple("OK".to_string()));
+    assert_eq!(
+        conn.command(&[b"XGROUP", b"CREATE", b"jobs", b"workers", b"0"]).unwrap(),
+        Value::Error("BUSYGROUP Consumer Group name already exists".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"nobody", b"alice", b"STREAMS", b"jobs", b">"]).unwrap(),
+        Value::Error("NOGROUP No such key 'jobs' or consumer group 'nobody'".to_string())
+    );
+    for (id, job) in [("1-0", "a"), ("2-0", "b"), ("3-0", "c")] {
+        conn.command(&[b"XADD", b"jobs", id.as_bytes(), b"job", job.as_bytes()]).unwrap();
+    }
+
+    // Chaque entrée n'est délivrée qu'une fois au groupe, puis reste en attente chez son consommateur
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"alice", b"COUNT", b"2", b"STREAMS", b"jobs", b">"]).unwrap(),
+        stream_reply("jobs", vec![entry("1-0", &["job", "a"]), entry("2-0", &["job", "b"])])
+    );
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"bob", b"STREAMS", b"jobs", b">"]).unwrap(),
+        stream_reply("jobs", vec![entry("3-0", &["job", "c"])])
+    );
+    assert_eq!(conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"bob", b"STREAMS", b"jobs", b">"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"bob", b"STREAMS", b"jobs", b"$"]).unwrap(),
+        Value::Error("ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set.".to_string())
+    );
+
+    assert_eq!(
+        conn.command(&[b"XPENDING", b"jobs", b"workers"]).unwrap(),
+        Value::Array(vec![
+            Value::Integer(3),
+            bulk("1-0"),
+            bulk("3-0"),
+            Value::Array(vec![Value::Array(vec![bulk("alice"), bulk("2")]), Value::Array(vec![bulk("bob"), bulk("1")])]),
+        ])
+    );
+    match conn.command(&[b"XPENDING", b"jobs", b"workers", b"-", b"+", b"10", b"alice"]).unwrap() {
+        Value::Array(entries) => {
+            assert_eq!(entries.len(), 2);
+            match &entries[0] {
+                Value::Array(fields) => {
+                    assert_eq!(fields[0], bulk("1-0"));
+                    assert_eq!(fields[1], bulk("alice"));
+                    assert_eq!(fields[3], Value::Integer(1));
+                },
+                other => panic!("entrée en attente attendue, reçu {:?}", other),
+            }
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+
+    // Relecture de l'historique : les entrées en attente sont redélivrées avec un compteur incrémenté
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"alice", b"STREAMS", b"jobs", b"0"]).unwrap(),
+        stream_reply("jobs", vec![entry("1-0", &["job", "a"]), entry("2-0", &["job", "b"])])
+    );
+    assert_eq!(conn.command(&[b"XACK", b"jobs", b"workers", b"1-0", b"9-0"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"XACK", b"jobs", b"workers", b"1-0"]).unwrap(), Value::Integer(0));
+    assert_eq!(conn.command(&[b"XACK", b"jobs", b"nobody", b"1-0"]).unwrap(), Value::Integer(0));
+
+    // XCLAIM : seules les entrées inactives depuis assez longtemps changent de consommateur
+    assert_eq!(conn.command(&[b"XCLAIM", b"jobs", b"workers", b"bob", b"60000", b"2-0"]).unwrap(), Value::Array(vec![]));
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(
+        conn.command(&[b"XCLAIM", b"jobs", b"workers", b"bob", b"10", b"2-0"]).unwrap(),
+        Value::Array(vec![entry("2-0", &["job", "b"])])
+    );
+    assert_eq!(
+        conn.command(&[b"XCLAIM", b"jobs", b"workers", b"carol", b"0", b"2-0", b"JUSTID"]).unwrap(),
+        Value::Array(vec![bulk("2-0")])
+    );
+    match conn.command(&[b"XPENDING", b"jobs", b"workers", b"-", b"+", b"10"]).unwrap() {
+        Value::Array(entries) => {
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0], Value::Array(match &entries[0] {
+                Value::Array(fields) => vec![bulk("2-0"), bulk("carol"), fields[2].clone(), Value::Integer(3)],
+                other => panic!("entrée en attente attendue, reçu {:?}", other),
+            }));
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+
+    // NOACK : les entrées sont délivrées sans être mises en attente
+    conn.command(&[b"XADD", b"jobs", b"4-0", b"job", b"d"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"alice", b"NOACK", b"STREAMS", b"jobs", b">"]).unwrap(),
+        stream_reply("jobs", vec![entry("4-0", &["job", "d"])])
+    );
+
+    // XREADGROUP bloquant : réveillé par un XADD d'une autre connexion
+    let writer = thread::spawn(move || {
+        let mut other = Connection::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        other.command(&[b"XADD", b"jobs", b"5-0", b"job", b"e"]).unwrap();
+    });
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"bob", b"BLOCK", b"5000", b"STREAMS", b"jobs", b">"]).unwrap(),
+        stream_reply("jobs", vec![entry("5-0", &["job", "e"])])
+    );
+    writer.join().unwrap();
+
+    // BLOCK 0 s'arrête quand le client se déconnecte : l'entrée ajoutée ensuite n'est livrée à personne
+    conn.command(&[b"XGROUP", b"CREATE", b"tasks", b"workers", b"$", b"MKSTREAM"]).unwrap();
+    {
+        let mut ghost = TcpStream::connect(addr).unwrap();
+        ghost.write_all(b"XREADGROUP GROUP workers ghost BLOCK 0 STREAMS tasks >\r\n").unwrap();
+        thread::sleep(Duration::from_millis(100));
+    }
+    thread::sleep(Duration::from_millis(300));
+    conn.command(&[b"XADD", b"tasks", b"1-0", b"job", b"f"]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(conn.command(&[b"XPENDING", b"tasks", b"workers", b"-", b"+", b"10"]).unwrap(), Value::Array(vec![]));
+
+    // Une entrée supprimée du stream apparaît sans ses champs dans l'historique
+    conn.command(&[b"XDEL", b"jobs", b"3-0"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"bob", b"STREAMS", b"jobs", b"0"]).unwrap(),
+        stream_reply("jobs", vec![Value::Array(vec![bulk("3-0"), Value::Nil]), entry("5-0", &["job", "e"])])
+    );
+
+    assert_eq!(conn.command(&[b"XGROUP", b"CREATECONSUMER", b"jobs", b"workers", b"dave"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"XGROUP", b"DELCONSUMER", b"jobs", b"workers", b"dave"]).unwrap(), Value::Integer(0));
+    assert_eq!(
+        conn.command(&[b"XGROUP", b"FOO", b"jobs"]).unwrap(),
+        Value::Error("ERR unknown subcommand 'FOO'. Try XGROUP HELP.".to_string())
+    );
+
+    // Un consommateur créé par une lecture qui ne délivre rien est tout de même journalisé
+    assert_eq!(conn.command(&[b"XREADGROUP", b"GROUP", b"workers", b"erin", b"STREAMS", b"jobs", b">"]).unwrap(), Value::Nil);
+
+    // L'AOF puis le snapshot reconstruisent la position du groupe et les entrées en attente
+    let live = match conn.command(&[b"XPENDING", b"jobs", b"workers"]).unwrap() {
+        Value::Array(summary) => summary,
+        other => panic!("résumé attendu, reçu {:?}", other),
+    };
+    assert_eq!(live[0], Value::Integer(3));
+    let replayed = replay_aof(&aof_rx);
+    let group = match &replayed.lock().unwrap().get(b"jobs".as_slice()).unwrap().value {
+        DbValue::Stream(stream) => stream.groups.get(b"workers".as_slice()).unwrap().clone(),
+        other => panic!("stream attendu, reçu {:?}", other),
+    };
+    assert_eq!(group.last_delivered, StreamId::new(5, 0));
+    assert_eq!(group.pending.keys().map(|id| id.to_string()).collect::<Vec<_>>(), ["2-0", "3-0", "5-0"]);
+    assert_eq!(group.pending[&StreamId::new(2, 0)].consumer, b"carol");
+    assert_eq!(group.pending[&StreamId::new(2, 0)].delivery_count, 3);
+    assert_eq!(group.pending[&StreamId::new(3, 0)].delivery_count, 1);
+    assert!(group.consumers.contains_key(b"erin".as_slice()));
+
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    match &restored.get(b"jobs".as_slice()).unwrap().value {
+        DbValue::Stream(stream) => assert_eq!(stream.groups.get(b"workers".as_slice()), Some(&group)),
+        other => panic!("stream attendu, reçu {:?}", other),
+    }
+}
+
+#[test]
+fn test_bitmaps() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let int = Value::Integer;
+
+    // Utilisateurs actifs du jour : un bit par identifiant, la chaîne grandit au besoin
+    assert_eq!(conn.command(&[b"SETBIT", b"dau", b"7", b"1"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"GET", b"dau"]).unwrap(), Value::Bulk(vec![0x01]));
+    assert_eq!(conn.command(&[b"SETBIT", b"dau", b"7", b"0"]).unwrap(), int(1));
+    assert_eq!(conn.command(&[b"SETBIT", b"dau", b"100", b"1"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"GETBIT", b"dau", b"100"]).unwrap(), int(1));
+    assert_eq!(conn.command(&[b"GETBIT", b"dau", b"1000"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"GETBIT", b"missing", b"3"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"SETBIT", b"dau", b"1", b"2"]).unwrap(), Value::Error("ERR bit is not an integer or out of range".to_string()));
+    assert_eq!(
+        conn.command(&[b"SETBIT", b"dau", b"4294967296", b"1"]).unwrap(),
+        Value::Error("ERR bit offset is not an integer or out of range".to_string())
+    );
+
+    conn.command(&[b"SET", b"text", b"foobar"]).unwrap();
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text"]).unwrap(), int(26));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text", b"0", b"0"]).unwrap(), int(4));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text", b"1", b"-1", b"BYTE"]).unwrap(), int(22));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text", b"5", b"30", b"BIT"]).unwrap(), int(17));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text", b"4", b"2"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"text", b"1"]).unwrap(), Value::Error("ERR syntax error".to_string()));
+    assert_eq!(conn.command(&[b"BITCOUNT", b"missing"]).unwrap(), int(0));
+
+    conn.command(&[b"SET", b"pos", b"\xff\xf0\x00"]).unwrap();
+    assert_eq!(conn.command(&[b"BITPOS", b"pos", b"0"]).unwrap(), int(12));
+    conn.command(&[b"SET", b"pos", b"\x00\xff\xf0"]).unwrap();
+    assert_eq!(conn.command(&[b"BITPOS", b"pos", b"1", b"0"]).unwrap(), int(8));
+    assert_eq!(conn.command(&[b"BITPOS", b"pos", b"1", b"2"]).unwrap(), int(16));
+    assert_eq!(conn.command(&[b"BITPOS", b"pos", b"1", b"2", b"-1", b"BYTE"]).unwrap(), int(16));
+    assert_eq!(conn.command(&[b"BITPOS", b"pos", b"1", b"7", b"15", b"BIT"]).unwrap(), int(8));
+    conn.command(&[b"SET", b"ones", b"\xff\xff\xff"]).unwrap();
+    assert_eq!(conn.command(&[b"BITPOS", b"ones", b"0"]).unwrap(), int(24));
+    assert_eq!(conn.command(&[b"BITPOS", b"ones", b"0", b"0", b"-1"]).unwrap(), int(-1));
+    assert_eq!(conn.command(&[b"BITPOS", b"missing", b"1"]).unwrap(), int(-1));
+    assert_eq!(conn.command(&[b"BITPOS", b"missing", b"0"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"BITPOS", b"ones", b"2"]).unwrap(), Value::Error("ERR The bit argument must be 1 or 0.".to_string()));
+
+    // BITOP : les sources plus courtes sont complétées par des zéros
+    conn.command(&[b"SET", b"other", b"abcdef"]).unwrap();
+    assert_eq!(conn.command(&[b"BITOP", b"AND", b"and", b"text", b"other"]).unwrap(), int(6));
+    assert_eq!(conn.command(&[b"GET", b"and"]).unwrap(), Value::Bulk(b"`bc`ab".to_vec()));
+    assert_eq!(conn.command(&[b"BITOP", b"OR", b"or", b"pos", b"dau", b"missing"]).unwrap(), int(13));
+    assert_eq!(conn.command(&[b"BITOP", b"NOT", b"not", b"pos"]).unwrap(), int(3));
+    assert_eq!(conn.command(&[b"GET", b"not"]).unwrap(), Value::Bulk(vec![0xff, 0x00, 0x0f]));
+    assert_eq!(conn.command(&[b"BITOP", b"XOR", b"not", b"missing"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"GET", b"not"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"BITOP", b"NOT", b"not", b"pos", b"ones"]).unwrap(),
+        Value::Error("ERR BITOP NOT must be called with a single source key.".to_string())
+    );
+
+    // BITFIELD : champs signés et non signés, et modes de débordement
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"bf", b"INCRBY", b"i5", b"100", b"1", b"GET", b"u4", b"0"]).unwrap(),
+        Value::Array(vec![int(1), int(0)])
+    );
+    let sat = |conn: &mut Connection| {
+        conn.command(&[b"BITFIELD", b"counters", b"INCRBY", b"u2", b"100", b"1", b"OVERFLOW", b"SAT", b"INCRBY", b"u2", b"102", b"1"])
+            .unwrap()
+    };
+    assert_eq!(sat(&mut conn), Value::Array(vec![int(1), int(1)]));
+    assert_eq!(sat(&mut conn), Value::Array(vec![int(2), int(2)]));
+    assert_eq!(sat(&mut conn), Value::Array(vec![int(3), int(3)]));
+    assert_eq!(sat(&mut conn), Value::Array(vec![int(0), int(3)]));
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"counters", b"OVERFLOW", b"FAIL", b"INCRBY", b"u2", b"102", b"1"]).unwrap(),
+        Value::Array(vec![Value::Nil])
+    );
+    // Si toutes les écritures échouent, la clé n'est ni créée ni agrandie
+    let strlen = conn.command(&[b"STRLEN", b"counters"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"counters", b"OVERFLOW", b"FAIL", b"SET", b"u2", b"8000", b"9"]).unwrap(),
+        Value::Array(vec![Value::Nil])
+    );
+    assert_eq!(conn.command(&[b"STRLEN", b"counters"]).unwrap(), strlen);
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"failed", b"OVERFLOW", b"FAIL", b"INCRBY", b"u2", b"0", b"4"]).unwrap(),
+        Value::Array(vec![Value::Nil])
+    );
+    assert_eq!(conn.command(&[b"EXISTS", b"failed"]).unwrap(), int(0));
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"fields", b"SET", b"i8", b"#1", b"-100", b"GET", b"i8", b"8", b"GET", b"u8", b"#1"]).unwrap(),
+        Value::Array(vec![int(0), int(-100), int(156)])
+    );
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"fields", b"INCRBY", b"i8", b"8", b"-100", b"OVERFLOW", b"SAT", b"INCRBY", b"i8", b"8", b"-100"]).unwrap(),
+        Value::Array(vec![int(56), int(-44)])
+    );
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"wide", b"SET", b"i64", b"0", b"-1", b"GET", b"i64", b"0", b"GET", b"u63", b"0"]).unwrap(),
+        Value::Array(vec![int(0), int(-1), int(i64::MAX)])
+    );
+    assert_eq!(
+        conn.command(&[b"BITFIELD", b"fields", b"GET", b"u64", b"0"]).unwrap(),
+        Value::Error("ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"BITFIELD_RO", b"fields", b"SET", b"u8", b"0", b"1"]).unwrap(),
+        Value::Error("ERR BITFIELD_RO only supports the GET subcommand".to_string())
+    );
+    assert_eq!(conn.command(&[b"BITFIELD_RO", b"missing", b"GET", b"u8", b"0"]).unwrap(), Value::Array(vec![int(0)]));
+    // Un décalage proche de u64::MAX ne doit pas déborder lors du contrôle des bornes
+    let bad_offset = Value::Error("ERR bit offset is not an integer or out of range".to_string());
+    for offset in [&b"18446744073709551615"[..], b"18446744073709551610", b"#2305843009213693951"] {
+        assert_eq!(conn.command(&[b"BITFIELD", b"fields", b"SET", b"u8", offset, b"1"]).unwrap(), bad_offset);
+    }
+    assert_eq!(conn.command(&[b"BITFIELD", b"fields", b"GET", b"u8", b"0"]).unwrap(), Value::Array(vec![int(0)]));
+    assert_eq!(conn.command(&[b"GET", b"missing"]).unwrap(), Value::Nil);
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    conn.command(&[b"LPUSH", b"list", b"x"]).unwrap();
+    assert_eq!(conn.command(&[b"SETBIT", b"list", b"0", b"1"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"BITOP", b"OR", b"dest", b"list"]).unwrap(), wrongtype);
+
+    // BITFIELD est journalisé sous forme de SETRANGE des octets touchés : rejouer deux fois la
+    // même ligne ne répète pas les INCRBY
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert!(lines.iter().all(|line| !line.starts_with("BITFIELD")), "{:?}", lines);
+    let (tx, rx) = mpsc::channel();
+    for line in lines {
+        if line.starts_with("SETRANGE ") {
+            tx.send(line.clone()).unwrap();
+        }
+        tx.send(line).unwrap();
+    }
+
+    // Les octets non UTF-8 survivent au rejeu de l'AOF
+    let replayed = replay_aof(&rx);
+    let guard = replayed.lock().unwrap();
+    for key in ["dau", "not", "bf", "counters", "fields", "or"] {
+        let live = conn.command(&[b"GET", key.as_bytes()]).unwrap();
+        let replayed = guard.get(key.as_bytes()).map(|entry| match &entry.value {
+            DbValue::String(bytes) => Value::Bulk(bytes.clone()),
+            other => panic!("chaîne attendue, reçu {:?}", other),
+        });
+        assert_eq!(replayed.unwrap_or(Value::Nil), live, "clé {}", key);
+    }
+}
+
+#[test]
+fn test_hyperloglog() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let int = Value::Integer;
+
+    assert_eq!(conn.command(&[b"PFADD", b"visitors", b"foo", b"bar", b"zap"]).unwrap(), int(1));
+    assert_eq!(conn.command(&[b"PFADD", b"visitors", b"zap", b"zap", b"zap"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"PFADD", b"visitors", b"foo", b"bar"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"PFCOUNT", b"visitors"]).unwrap(), int(3));
+    assert_eq!(conn.command(&[b"PFADD", b"empty"]).unwrap(), int(1));
+    assert_eq!(conn.command(&[b"PFADD", b"empty"]).unwrap(), int(0));
+    assert_eq!(conn.command(&[b"PFCOUNT", b"empty", b"missing"]).unwrap(), int(0));
+
+    assert_eq!(conn.command(&[b"PFADD", b"other", b"zap", b"a", b"b"]).unwrap(), int(1));
+    assert_eq!(conn.command(&[b"PFCOUNT", b"visitors", b"other", b"missing"]).unwrap(), int(5));
+    assert_eq!(conn.command(&[b"PFMERGE", b"merged", b"visitors", b"other"]).unwrap(), Value::Simple("OK".to_string()));
+    assert_eq!(conn.command(&[b"PFCOUNT", b"merged"]).unwrap(), int(5));
+    assert_eq!(conn.command(&[b"PFMERGE", b"merged", b"empty"]).unwrap(), Value::Simple("OK".to_string()));
+    assert_eq!(conn.command(&[b"PFCOUNT", b"merged"]).unwrap(), int(5));
+
+    // Assez d'éléments pour passer en représentation dense
+    let mut pipeline = Pipeline::new();
+    for i in 0..20_000 {
+        pipeline.cmd(&[b"PFADD", b"big", format!("user:{}", i).as_bytes()]);
+    }
+    conn.execute(&pipeline).unwrap();
+    let estimate = match conn.command(&[b"PFCOUNT", b"big"]).unwrap() {
+        Value::Integer(n) => n,
+        other => panic!("entier attendu, reçu {:?}", other),
+    };
+    assert!((estimate - 20_000).abs() < 20_000 * 3 / 100, "estimation {}", estimate);
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    conn.command(&[b"SET", b"str", b"x"]).unwrap();
+    assert_eq!(conn.command(&[b"PFADD", b"str", b"a"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"PFCOUNT", b"visitors", b"str"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"GET", b"visitors"]).unwrap(), wrongtype);
+    // TYPE et SCAN TYPE s'accordent avec ce refus
+    assert_eq!(conn.command(&[b"TYPE", b"visitors"]).unwrap(), Value::Simple("hyperloglog".to_string()));
+    for (type_name, expected) in [(&b"string"[..], false), (b"hyperloglog", true)] {
+        let found = match conn.command(&[b"SCAN", b"0", b"MATCH", b"visitors", b"TYPE", type_name, b"COUNT", b"1000"]).unwrap() {
+            Value::Array(reply) => reply[1] == Value::Array(vec![Value::Bulk(b"visitors".to_vec())]),
+            other => panic!("réponse SCAN attendue, reçu {:?}", other),
+        };
+        assert_eq!(found, expected);
+    }
+
+    // L'AOF puis le snapshot redonnent les mêmes registres, creux comme denses
+    let replayed = replay_aof(&aof_rx);
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    for (key, encoding) in [("merged", "sparse"), ("big", "dense")] {
+        match &restored.get(key.as_bytes()).unwrap().value {
+            DbValue::HyperLogLog(hll) => {
+                assert_eq!(hll.encoding(), encoding);
+                assert_eq!(Value::Integer(hll.count() as i64), conn.command(&[b"PFCOUNT", key.as_bytes()]).unwrap());
+            },
+            other => panic!("HyperLogLog attendu, reçu {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_hyperloglog_accuracy() {
+    // Erreur type de 1,04 / sqrt(16384) = 0,81 % : on tolère trois écarts types
+    let mut hll = HyperLogLog::new();
+    let mut exact = 0u64;
+    for checkpoint in [100u64, 1_000, 10_000, 100_000, 500_000] {
+        while exact < checkpoint {
+            hll.add(format!("element-{}", exact).as_bytes());
+            exact += 1;
+        }
+        let error = (hll.count() as f64 - exact as f64).abs() / exact as f64;
+        assert!(error < 3.0 * 0.0081, "{} éléments : erreur relative {}", exact, error);
+    }
+    assert_eq!(hll.encoding(), "dense");
+
+    let mut half = HyperLogLog::new();
+    for i in 250_000..750_000 {
+        half.add(format!("element-{}", i).as_bytes());
+    }
+    half.merge(&hll);
+    let error = (half.count() as f64 - 750_000.0).abs() / 750_000.0;
+    assert!(error < 3.0 * 0.0081, "union : erreur relative {}", error);
+}
+
+#[test]
+fn test_geo() {
+    let addr = start_test_server();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let float = |value: &Value| match value {
+        Value::Bulk(text) => std::str::from_utf8(text).unwrap().parse::<f64>().unwrap(),
+        other => panic!("nombre attendu, reçu {:?}", other),
+    };
+
+    assert_eq!(
+        conn.command(&[b"GEOADD", b"Sicily", b"13.361389", b"38.115556", b"Palermo", b"15.087269", b"37.502669", b"Catania"]).unwrap(),
+        Value::Integer(2)
+    );
+    assert_eq!(conn.command(&[b"GEOADD", b"Sicily", b"NX", b"0", b"0", b"Palermo"]).unwrap(), Value::Integer(0));
+    assert_eq!(
+        conn.command(&[b"GEOADD", b"Sicily", b"200", b"91", b"Nowhere"]).unwrap(),
+        Value::Error("ERR invalid longitude,latitude pair 200.000000,91.000000".to_string())
+    );
+    assert_eq!(conn.command(&[b"ZCARD", b"Sicily"]).unwrap(), Value::Integer(2));
+
+    // Valeurs de la documentation de Redis
+    assert_eq!(conn.command(&[b"GEODIST", b"Sicily", b"Palermo", b"Catania"]).unwrap(), bulk("166274.1516"));
+    assert_eq!(conn.command(&[b"GEODIST", b"Sicily", b"Palermo", b"Catania", b"km"]).unwrap(), bulk("166.2742"));
+    assert_eq!(conn.command(&[b"GEODIST", b"Sicily", b"Palermo", b"Catania", b"MI"]).unwrap(), bulk("103.3182"));
+    assert_eq!(conn.command(&[b"GEODIST", b"Sicily", b"Palermo", b"Rome"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"GEODIST", b"Sicily", b"Palermo", b"Catania", b"yd"]).unwrap(),
+        Value::Error("ERR unsupported unit provided. please use M, KM, FT, MI".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"GEOHASH", b"Sicily", b"Palermo", b"Catania", b"Rome"]).unwrap(),
+        Value::Array(vec![bulk("sqc8b49rny0"), bulk("sqdtr74hyu0"), Value::Nil])
+    );
+    match conn.command(&[b"GEOPOS", b"Sicily", b"Palermo", b"Rome"]).unwrap() {
+        Value::Array(positions) => {
+            match &positions[0] {
+                Value::Array(position) => {
+                    assert!((float(&position[0]) - 13.361389338970184).abs() < 1e-12);
+                    assert!((float(&position[1]) - 38.1155563954963).abs() < 1e-12);
+                },
+                other => panic!("position attendue, reçu {:?}", other),
+            }
+            assert_eq!(positions[1], Value::Nil);
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+
+    conn.command(&[b"GEOADD", b"Sicily", b"12.758489", b"38.788135", b"edge1", b"17.241510", b"38.788135", b"edge2"]).unwrap();
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMLONLAT", b"15", b"37", b"BYRADIUS", b"200", b"km", b"ASC"]).unwrap(),
+        Value::Array(vec![bulk("Catania"), bulk("Palermo")])
+    );
+    match conn
+        .command(&[b"GEOSEARCH", b"Sicily", b"FROMLONLAT", b"15", b"37", b"BYBOX", b"400", b"400", b"km", b"ASC", b"WITHCOORD", b"WITHDIST"])
+        .unwrap()
+    {
+        Value::Array(items) => {
+            let summary: Vec<(Value, Value)> = items
+                .iter()
+                .map(|item| match item {
+                    Value::Array(fields) => {
+                        assert!(matches!(&fields[2], Value::Array(coords) if coords.len() == 2));
+                        (fields[0].clone(), fields[1].clone())
+                    },
+                    other => panic!("résultat attendu, reçu {:?}", other),
+                })
+                .collect();
+            assert_eq!(
+                summary,
+                [
+                    (bulk("Catania"), bulk("56.4413")),
+                    (bulk("Palermo"), bulk("190.4424")),
+                    (bulk("edge2"), bulk("279.7403")),
+                    (bulk("edge1"), bulk("279.7405")),
+                ]
+            );
+        },
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMMEMBER", b"Palermo", b"BYRADIUS", b"300", b"km", b"DESC", b"COUNT", b"2"]).unwrap(),
+        Value::Array(vec![bulk("Catania"), bulk("edge1")])
+    );
+    match conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMMEMBER", b"Palermo", b"BYRADIUS", b"1", b"m", b"WITHHASH"]).unwrap() {
+        Value::Array(items) => assert!(matches!(&items[..], [Value::Array(fields)] if fields[0] == bulk("Palermo") && matches!(fields[1], Value::Integer(_)))),
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+    match conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMLONLAT", b"15", b"37", b"BYRADIUS", b"5000", b"km", b"COUNT", b"1", b"ANY"]).unwrap() {
+        Value::Array(items) => assert_eq!(items.len(), 1),
+        other => panic!("tableau attendu, reçu {:?}", other),
+    }
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMMEMBER", b"Rome", b"BYRADIUS", b"1", b"km"]).unwrap(),
+        Value::Error("ERR could not decode requested zset member".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMLONLAT", b"15", b"37", b"COUNT", b"1"]).unwrap(),
+        Value::Error("ERR exactly one of BYRADIUS and BYBOX arguments must be provided for GEOSEARCH".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"Sicily", b"FROMLONLAT", b"15", b"37", b"BYRADIUS", b"1", b"km", b"ANY"]).unwrap(),
+        Value::Error("ERR the ANY argument requires COUNT argument".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"GEOSEARCH", b"missing", b"FROMLONLAT", b"15", b"37", b"BYRADIUS", b"1", b"km"]).unwrap(),
+        Value::Array(vec![])
+    );
+}
+
+#[test]
+fn test_geo_distances() {
+    // Distances de haversine de référence (Rosetta Code, rayon de 6372,8 km) ramenées au rayon de Redis
+    let scale = 6372.797560856 / 6372.8;
+    let cases = [
+        ((-86.67, 36.12), (-118.40, 33.94), 2887.2599506071106),
+        ((2.3522, 48.8566), (-0.1278, 51.5074), 343.65312530864696),
+        ((0.0, 0.0), (180.0, 0.0), std::f64::consts::PI * 6372.8),
+    ];
+    for ((lon1, lat1), (lon2, lat2), km) in cases {
+        let meters = geo::distance(lon1, lat1, lon2, lat2);
+        assert!((meters - km * 1000.0 * scale).abs() < 1.0, "{} m au lieu de {} km", meters, km);
+        assert_eq!(meters, geo::distance(lon2, lat2, lon1, lat1));
+    }
+
+    // Le géohash ne déplace une position que de quelques centimètres
+    for (lon, lat) in [(13.361389, 38.115556), (-179.9999, -85.0), (179.9999, 85.0), (0.0, 0.0)] {
+        let (decoded_lon, decoded_lat) = geo::decode(geo::encode(lon, lat));
+        assert!(geo::distance(lon, lat, decoded_lon, decoded_lat) < 1.0);
+    }
+
+    // Les intervalles de scores couvrent toute la zone, y compris près des pôles et de l'antiméridien
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let centers = [(2.35, 48.85), (179.5, 0.0), (-179.9, 10.0), (20.0, 84.0), (-60.0, -80.0)];
+    let shapes = [Shape::Radius(50_000.0), Shape::Radius(800_000.0), Shape::Box { width: 300_000.0, height: 100_000.0 }];
+    for (lon, lat) in centers {
+        let points: Vec<u64> = (0..2000)
+            .map(|_| {
+                let point_lon = (lon + (random() - 0.5) * 40.0 + 540.0) % 360.0 - 180.0;
+                let point_lat = (lat + (random() - 0.5) * 20.0).clamp(geo::LAT_MIN, geo::LAT_MAX);
+                geo::encode(point_lon, point_lat)
+            })
+            .collect();
+        for shape in shapes {
+            let ranges = shape.score_ranges(lon, lat);
+            for hash in &points {
+                let (point_lon, point_lat) = geo::decode(*hash);
+                if shape.distance_if_inside(lon, lat, point_lon, point_lat).is_some() {
+                    assert!(
+                        ranges.iter().any(|(min, max)| (*min..*max).contains(hash)),
+                        "({}, {}) manqué autour de ({}, {}) pour {:?}",
+                        point_lon,
+                        point_lat,
+                        lon,
+                        lat,
+                        shape
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_json() {
+    let (addr, aof_rx) = start_test_server_with_aof();
+    let mut conn = Connection::connect(addr).unwrap();
+    let bulk = |s: &str| Value::Bulk(s.as_bytes().to_vec());
+    let ok = Value::Simple("OK".to_string());
+
+    let order = br#"{"id":7,"customer":{"name":"Ana","tags":["vip"]},"items":[{"sku":"a","qty":1},{"sku":"b","qty":2.5}]}"#;
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$", order]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$.customer.name"]).unwrap(), bulk(r#"["Ana"]"#));
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b".customer.name"]).unwrap(), bulk(r#""Ana""#));
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$.items[*].sku"]).unwrap(), bulk(r#"["a","b"]"#));
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$..qty"]).unwrap(), bulk("[1,2.5]"));
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$.items[-1]['sku']"]).unwrap(), bulk(r#"["b"]"#));
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$.missing"]).unwrap(), bulk("[]"));
+    assert_eq!(
+        conn.command(&[b"JSON.GET", b"order", b".missing"]).unwrap(),
+        Value::Error("ERR Path '.missing' does not exist".to_string())
+    );
+    assert_eq!(
+        conn.command(&[b"JSON.GET", b"order", b"$.id", b"$.customer.tags"]).unwrap(),
+        bulk(r#"{"$.customer.tags":[["vip"]],"$.id":[7]}"#)
+    );
+    assert_eq!(conn.command(&[b"JSON.GET", b"missing"]).unwrap(), Value::Nil);
+
+    // Mises à jour partielles
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$.status", br#""paid""#]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$.status", br#""sent""#, b"NX"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$.note", b"1", b"XX"]).unwrap(), Value::Nil);
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$.items[*].done", b"false"]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"JSON.GET", b"order", b"$.items[1]"]).unwrap(), bulk(r#"[{"done":false,"qty":2.5,"sku":"b"}]"#));
+    assert_eq!(conn.command(&[b"JSON.SET", b"order", b"$.a.b", b"1"]).unwrap(), Value::Nil);
+    assert_eq!(
+        conn.command(&[b"JSON.SET", b"other", b"$.a", b"1"]).unwrap(),
+        Value::Error("ERR new objects must be created at the root".to_string())
+    );
+    assert!(matches!(conn.command(&[b"JSON.SET", b"other", b"$", b"{bad"]).unwrap(), Value::Error(e) if e.starts_with("ERR invalid JSON")));
+
+    assert_eq!(conn.command(&[b"JSON.NUMINCRBY", b"order", b"$..qty", b"2"]).unwrap(), bulk("[3,4.5]"));
+    assert_eq!(conn.command(&[b"JSON.NUMINCRBY", b"order", b".id", b"-10"]).unwrap(), bulk("-3"));
+    assert_eq!(conn.command(&[b"JSON.NUMINCRBY", b"order", b"$.customer.*", b"1"]).unwrap(), bulk("[null,null]"));
+    assert_eq!(
+        conn.command(&[b"JSON.NUMINCRBY", b"order", b".status", b"1"]).unwrap(),
+        Value::Error("ERR wrong type of path value - expected number but found string".to_string())
+    );
+    assert_eq!(conn.command(&[b"JSON.ARRAPPEND", b"order", b"$.customer.tags", br#""new""#, b"3"]).unwrap(), Value::Array(vec![Value::Integer(3)]));
+    assert_eq!(conn.command(&[b"JSON.ARRAPPEND", b"order", b".items", br#"{"sku":"c","qty":1}"#]).unwrap(), Value::Integer(3));
+    assert_eq!(conn.command(&[b"JSON.ARRAPPEND", b"order", b"$.id", b"1"]).unwrap(), Value::Array(vec![Value::Nil]));
+
+    assert_eq!(conn.command(&[b"JSON.DEL", b"order", b"$.items[*].done"]).unwrap(), Value::Integer(2));
+    assert_eq!(conn.command(&[b"JSON.DEL", b"order", b"$.customer.tags[0]"]).unwrap(), Value::Integer(1));
+    assert_eq!(conn.command(&[b"JSON.DEL", b"order", b"$.nothing"]).unwrap(), Value::Integer(0));
+    let expected = r#"{"customer":{"name":"Ana","tags":["new",3]},"id":-3,"items":[{"qty":3,"sku":"a"},{"qty":4.5,"sku":"b"},{"qty":1,"sku":"c"}],"status":"paid"}"#;
+    assert_eq!(conn.command(&[b"JSON.GET", b"order"]).unwrap(), bulk(expected));
+
+    // Clés bizarres : le chemin normalisé journalisé par NUMINCRBY doit être relu à l'identique
+    assert_eq!(conn.command(&[b"JSON.SET", b"odd", b"$", br#"{"a \"b\".c[0]":{"x y":1}}"#]).unwrap(), ok);
+    assert_eq!(conn.command(&[b"JSON.NUMINCRBY", b"odd", b"$.*.*", b"0.5"]).unwrap(), bulk("[1.5]"));
+    let document: serde_json::Value = serde_json::from_str(r#"{"a \"b\".c[0]":{"x y":1}}"#).unwrap();
+    assert_eq!(Path::parse(r#"$["a \"b\".c[0]"]['x y']"#).unwrap().locate(&document).len(), 1);
+
+    let wrongtype = Value::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+    conn.command(&[b"SET", b"str", b"{}"]).unwrap();
+    assert_eq!(conn.command(&[b"JSON.GET", b"str"]).unwrap(), wrongtype);
+    assert_eq!(conn.command(&[b"GET", b"order"]).unwrap(), wrongtype);
+
+    // Rejeu de l'AOF et snapshot : documents identiques
+    assert_eq!(conn.command(&[b"JSON.DEL", b"gone"]).unwrap(), Value::Integer(0));
+    conn.command(&[b"JSON.SET", b"gone", b".", b"[1,2]"]).unwrap();
+    assert_eq!(conn.command(&[b"JSON.DEL", b"gone", b"$"]).unwrap(), Value::Integer(1));
+
+    // JSON.ARRAPPEND est journalisé sous forme de JSON.SET du tableau obtenu : rejouer deux fois
+    // la même ligne n'ajoute pas les éléments deux fois
+    let lines: Vec<String> = aof_rx.try_iter().collect();
+    assert!(lines.iter().all(|line| !line.starts_with("JSON.ARRAPPEND")), "{:?}", lines);
+    assert!(lines.iter().any(|line| line.starts_with("JSON.SET order ")), "{:?}", lines);
+    let (tx, rx) = mpsc::channel();
+    for line in lines {
+        if line.starts_with("JSON.SET order ") {
+            tx.send(line.clone()).unwrap();
+        }
+        tx.send(line).unwrap();
+    }
+    let replayed = replay_aof(&rx);
+    let mut json = Vec::new();
+    redust::db::escaped_keys::serialize(&*replayed.lock().unwrap(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+    let restored: HashMap<Vec<u8>, Entry> =
+        redust::db::escaped_keys::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert!(!restored.contains_key(b"gone".as_slice()));
+    for key in ["order", "odd"] {
+        let live: serde_json::Value = match conn.command(&[b"JSON.GET", key.as_bytes()]).unwrap() {
+            Value::Bulk(text) => serde_json::from_slice(&text).unwrap(),
+            other => panic!("document attendu, reçu {:?}", other),
+        };
+        assert_eq!(restored.get(key.as_bytes()).unwrap().value, DbValue::Json(live));
+    }
+}